@@ -0,0 +1,22 @@
+#!/bin/bash
+# Download the pdf.js viewer into data/resources/pdfjs so it gets bundled as pdfjs.gresource.
+# Spidey downloads PDFs instead of showing them when the viewer isn't bundled.
+set -e
+VERSION="${1:-4.5.136}"
+
+cd "$(dirname "$0")/../data/resources"
+rm -rf pdfjs pdfjs.gresource.xml
+mkdir pdfjs
+curl -L "https://github.com/mozilla/pdf.js/releases/download/v$VERSION/pdfjs-$VERSION-dist.zip" -o pdfjs.zip
+unzip -q pdfjs.zip -d pdfjs
+rm pdfjs.zip pdfjs/web/compressed.tracemonkey-pldi-09.pdf
+find pdfjs -name '*.map' -delete
+
+{
+  echo '<?xml version="1.0" encoding="UTF-8"?>'
+  echo '<gresources>'
+  echo '  <gresource prefix="/com/github/kdwk/Spidey/">'
+  find pdfjs -type f | sort | sed 's|.*|    <file compressed="true">&</file>|'
+  echo '  </gresource>'
+  echo '</gresources>'
+} > pdfjs.gresource.xml
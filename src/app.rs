@@ -7,7 +7,7 @@ use relm4::{
     adw::prelude::*,
     gtk::{glib::clone, prelude::*},
     prelude::*,
-    ComponentController,
+    ComponentController, MessageBroker,
 };
 use reqwest;
 use std::{error::Error, thread};
@@ -15,6 +15,21 @@ use url::Url;
 use webkit6::prelude::WebViewExt;
 
 use crate::config::{APP_ID, PROFILE, VERSION};
+use crate::downloads::{
+    decide_download_destination, load_download_records, save_download_records, DownloadRecord,
+    DownloadRow, DownloadRowInput, DownloadRowOutput,
+};
+use crate::gsettings::gsettings;
+use crate::pagewatch::{start_page_watches, PageChange};
+use crate::pagewatchdialog::present_page_changes;
+use crate::pdfviewer::{
+    is_pdf_viewer_download, replace_pdf_viewer_download, show_download_in_pdf_viewer,
+};
+use crate::preferences::present_preferences;
+use crate::savedpages::{
+    load_saved_pages, save_saved_pages, SavedPage, SavedPageRow, SavedPageRowOutput,
+};
+use crate::webwindow::WebWindowInput;
 use crate::{
     recipe::{Discard, Log, Pass, Pipe, Recipe, Runnable, Step},
     whoops::{attempt, Catch, IntoWhoops, Whoops},
@@ -25,6 +40,10 @@ pub(super) struct App {
     url_entry_buffer: gtk::EntryBuffer,
     webwindowcontrolbars: relm4::factory::FactoryVecDeque<WebWindowControlBar>,
     user_content_filter_store_option: Option<webkit6::UserContentFilterStore>,
+    downloads: relm4::factory::FactoryVecDeque<DownloadRow>,
+    saved_pages: relm4::factory::FactoryVecDeque<SavedPageRow>,
+    /// The latest change found on each watched page, with its address and title, for the notification to show
+    page_changes: Vec<(String, String, PageChange)>,
 }
 
 relm4::new_action_group!(AppWindowActionGroup, "win");
@@ -34,17 +53,34 @@ relm4::new_stateless_action!(
     AppWindowActionGroup,
     "show_shortcuts"
 );
+relm4::new_stateless_action!(ShowPreferences, AppWindowActionGroup, "show_preferences");
+/// Lets Web Windows reach the main window, e.g. to move a download they showed a toast for
+pub static APP_BROKER: MessageBroker<AppInput> = MessageBroker::new();
+
 #[derive(Debug)]
 pub enum AppInput {
     NewWebWindow, // Also handles adding a WebWindowControlBar
     RemoveWebWindowControlBar(DynamicIndex),
     ShowAboutWindow,
     ShowKeyboardShortcutsWindow,
+    ShowPreferences,
     SetUpUserContentFilterStore,
     PresentWindow,
     SaveUrls,
     RestoreUrls,
     FocusUrlEntry,
+    DownloadStarted(webkit6::Download),
+    DownloadSaved(DownloadRecord),
+    SaveDownloads,
+    ChangeDownloadDestination(webkit6::Download, String),
+    RemoveDownload(DynamicIndex),
+    ClearFinishedDownloads,
+    PageSaved(SavedPage),
+    SaveSavedPages,
+    RemoveSavedPage(DynamicIndex),
+    OpenFiles(Vec<gtk::gio::File>),
+    WatchedPageChanged(String, String, PageChange),
+    ShowPageChanges(String),
 }
 
 #[relm4::component(pub)]
@@ -74,6 +110,91 @@ impl Component for App {
                     pack_start = &gtk::Button {
                         set_icon_name: "about",
                         connect_clicked => AppInput::ShowAboutWindow,
+                    },
+
+                    pack_end = &gtk::Button {
+                        set_icon_name: "preferences-system-symbolic",
+                        set_tooltip_text: Some("Preferences"),
+                        connect_clicked => AppInput::ShowPreferences,
+                    },
+
+                    #[name(saved_pages_btn)]
+                    pack_end = &gtk::MenuButton {
+                        set_icon_name: "folder-documents-symbolic",
+                        set_tooltip_text: Some("Saved Pages"),
+                        #[wrap(Some)]
+                        set_popover = &gtk::Popover {
+                            gtk::Box {
+                                set_orientation: gtk::Orientation::Vertical,
+                                set_spacing: 6,
+                                set_width_request: 350,
+
+                                gtk::Label {
+                                    add_css_class: "dim-label",
+                                    set_margin_all: 12,
+                                    set_label: "No saved pages",
+                                    #[watch]
+                                    set_visible: model.saved_pages.is_empty(),
+                                },
+
+                                gtk::ScrolledWindow {
+                                    set_propagate_natural_height: true,
+                                    set_max_content_height: 400,
+                                    set_hscrollbar_policy: gtk::PolicyType::Never,
+                                    #[watch]
+                                    set_visible: !model.saved_pages.is_empty(),
+
+                                    #[local_ref]
+                                    saved_page_list -> gtk::ListBox {
+                                        set_selection_mode: gtk::SelectionMode::None,
+                                        add_css_class: "navigation-sidebar",
+                                    }
+                                },
+                            }
+                        },
+                    },
+
+                    #[name(downloads_btn)]
+                    pack_end = &gtk::MenuButton {
+                        set_icon_name: "folder-download-symbolic",
+                        set_tooltip_text: Some("Downloads"),
+                        #[wrap(Some)]
+                        set_popover = &gtk::Popover {
+                            gtk::Box {
+                                set_orientation: gtk::Orientation::Vertical,
+                                set_spacing: 6,
+                                set_width_request: 350,
+
+                                gtk::Label {
+                                    add_css_class: "dim-label",
+                                    set_margin_all: 12,
+                                    set_label: "No downloads",
+                                    #[watch]
+                                    set_visible: model.downloads.is_empty(),
+                                },
+
+                                gtk::ScrolledWindow {
+                                    set_propagate_natural_height: true,
+                                    set_max_content_height: 400,
+                                    set_hscrollbar_policy: gtk::PolicyType::Never,
+                                    #[watch]
+                                    set_visible: !model.downloads.is_empty(),
+
+                                    #[local_ref]
+                                    download_list -> gtk::ListBox {
+                                        set_selection_mode: gtk::SelectionMode::None,
+                                        add_css_class: "navigation-sidebar",
+                                    }
+                                },
+
+                                gtk::Button {
+                                    set_label: "Clear Finished",
+                                    #[watch]
+                                    set_sensitive: model.downloads.iter().any(|download_row| !download_row.is_in_progress()),
+                                    connect_clicked => AppInput::ClearFinishedDownloads,
+                                },
+                            }
+                        },
                     }
                 },
 
@@ -147,13 +268,8 @@ impl Component for App {
         // let sender_clone = sender.clone();
         thread::spawn(clone!(@strong sender => move || {
             println!("Successfully entered adblock json download thread");
-            let gschema_id = if PROFILE == "Devel" {
-                "com.github.kdwk.Spidey.Devel"
-            } else {
-                "com.github.kdwk.Spidey"
-            };
             // Get the GSettings from GSchema file
-            let gsettings = gtk::gio::Settings::new(gschema_id);
+            let gsettings = gsettings();
             // Get when the XDG_DATA_DIR/adblock.json file has been last updated
             let adblock_json_last_updated_timestamp = gsettings.int64("adblock-json-last-updated");
             // Only download the file from the Internet again if the file has not been updated in the last 7 days
@@ -195,13 +311,60 @@ impl Component for App {
 
         sender.input(AppInput::RestoreUrls);
 
+        // Set up the downloads list, including downloads from previous sessions
+        let mut downloads = relm4::factory::FactoryVecDeque::builder()
+            .launch(gtk::ListBox::default())
+            .forward(sender.input_sender(), |output| match output {
+                DownloadRowOutput::Changed => AppInput::SaveDownloads,
+                DownloadRowOutput::Remove(index) => AppInput::RemoveDownload(index),
+            });
+        for record in load_download_records() {
+            downloads.guard().push_back((record, None));
+        }
+
+        // Set up the list of pages saved for offline reading
+        let mut saved_pages = relm4::factory::FactoryVecDeque::builder()
+            .launch(gtk::ListBox::default())
+            .forward(sender.input_sender(), |output| match output {
+                SavedPageRowOutput::Open(file) => AppInput::OpenFiles(vec![file]),
+                SavedPageRowOutput::Remove(index) => AppInput::RemoveSavedPage(index),
+            });
+        for saved_page in load_saved_pages() {
+            saved_pages.guard().push_back(saved_page);
+        }
+        webkit6::NetworkSession::default().connect_download_started(
+            clone!(@strong sender, @strong root => move |_, download_object| {
+                if let Some(record) = replace_pdf_viewer_download(download_object) {
+                    sender.input(AppInput::DownloadSaved(record));
+                    return;
+                }
+                if is_pdf_viewer_download(download_object) {
+                    show_download_in_pdf_viewer(download_object);
+                    return;
+                }
+                download_object.connect_decide_destination(clone!(@strong root => move |this_download_object, suggested_filename| {
+                    decide_download_destination(this_download_object, suggested_filename, root.upcast_ref());
+                    true
+                }));
+                sender.input(AppInput::DownloadStarted(download_object.clone()));
+            }),
+        );
+
+        // Check watched pages in the background, whether or not they are open
+        start_page_watches();
+
         // Standard component initialization procedures
         let model = App {
             webwindowcontrolbars: webwindowcontrolbars,
             url_entry_buffer: gtk::EntryBuffer::default(),
             user_content_filter_store_option: None,
+            downloads,
+            saved_pages,
+            page_changes: vec![],
         };
         let webwindowcontrolbar_box = model.webwindowcontrolbars.widget();
+        let download_list = model.downloads.widget();
+        let saved_page_list = model.saved_pages.widget();
         let widgets = view_output!();
         let app = relm4::main_adw_application();
         let mut app_window_action_group = RelmActionGroup::<AppWindowActionGroup>::new();
@@ -214,11 +377,26 @@ impl Component for App {
             RelmAction::new_stateless(clone!(@strong sender => move |_| {
                 sender.input(AppInput::ShowKeyboardShortcutsWindow);
             }));
+        let show_preferences: RelmAction<ShowPreferences> =
+            RelmAction::new_stateless(clone!(@strong sender => move |_| {
+                sender.input(AppInput::ShowPreferences);
+            }));
         app.set_accelerators_for_action::<ShowAboutWindow>(&["<Alt>A"]);
         app.set_accelerators_for_action::<ShowKeyboardShortcutsWindow>(&["<Ctrl>question"]);
+        app.set_accelerators_for_action::<ShowPreferences>(&["<Ctrl>comma"]);
         app_window_action_group.add_action(show_about_window);
         app_window_action_group.add_action(show_keyboard_shortcuts_window);
+        app_window_action_group.add_action(show_preferences);
         app_window_action_group.register_for_widget(root);
+        // Page change notifications say which page to show the changes of
+        let show_page_changes =
+            gtk::gio::SimpleAction::new("show-page-changes", Some(gtk::glib::VariantTy::STRING));
+        show_page_changes.connect_activate(clone!(@strong sender => move |_, url| {
+            if let Some(url) = url.and_then(|url| url.str()) {
+                sender.input(AppInput::ShowPageChanges(url.to_string()));
+            }
+        }));
+        app.add_action(&show_page_changes);
         ComponentParts {
             model: model,
             widgets: widgets,
@@ -266,6 +444,8 @@ impl Component for App {
                     .present(Some(root));
             }
 
+            AppInput::ShowPreferences => present_preferences(root),
+
             AppInput::SetUpUserContentFilterStore => {
                 with(
                     &[Document::at(
@@ -322,12 +502,7 @@ impl Component for App {
             }
 
             AppInput::RestoreUrls => {
-                let gschema_id = if PROFILE == "Devel" {
-                    "com.github.kdwk.Spidey.Devel"
-                } else {
-                    "com.github.kdwk.Spidey"
-                };
-                let gsettings = gtk::gio::Settings::new(gschema_id);
+                let gsettings = gsettings();
                 let urls = gsettings.string("urls").to_string();
                 let url_vec = if urls.len() > 0 {
                     urls.split(" ")
@@ -344,12 +519,7 @@ impl Component for App {
             }
 
             AppInput::SaveUrls => {
-                let gschema_id = if PROFILE == "Devel" {
-                    "com.github.kdwk.Spidey.Devel"
-                } else {
-                    "com.github.kdwk.Spidey"
-                };
-                let gsettings = gtk::gio::Settings::new(gschema_id);
+                let gsettings = gsettings();
                 let urls = self
                     .webwindowcontrolbars
                     .guard()
@@ -378,7 +548,140 @@ impl Component for App {
             AppInput::FocusUrlEntry => {
                 widgets.url_entry.grab_focus();
             }
+
+            AppInput::DownloadStarted(download) => {
+                self.downloads
+                    .guard()
+                    .push_front((DownloadRecord::new(&download), Some(download)));
+                sender.input(AppInput::SaveDownloads);
+            }
+
+            AppInput::DownloadSaved(record) => {
+                self.downloads.guard().push_front((record, None));
+                sender.input(AppInput::SaveDownloads);
+            }
+
+            AppInput::SaveDownloads => save_download_records(
+                &self
+                    .downloads
+                    .iter()
+                    .map(|download_row| download_row.record.clone())
+                    .collect::<Vec<DownloadRecord>>(),
+            ),
+
+            AppInput::ChangeDownloadDestination(download, destination) => {
+                let index = self
+                    .downloads
+                    .iter()
+                    .position(|download_row| download_row.is_download(&download));
+                if let Some(index) = index {
+                    self.downloads
+                        .send(index, DownloadRowInput::MoveTo(destination));
+                }
+            }
+
+            AppInput::RemoveDownload(index) => {
+                self.downloads.guard().remove(index.current_index());
+                sender.input(AppInput::SaveDownloads);
+            }
+
+            AppInput::ClearFinishedDownloads => {
+                let mut downloads_guard = self.downloads.guard();
+                for index in (0..downloads_guard.len()).rev() {
+                    if let Some(download_row) = downloads_guard
+                        .get_mut(index)
+                        .filter(|download_row| !download_row.is_in_progress())
+                    {
+                        download_row.record.discard_partial();
+                        downloads_guard.remove(index);
+                    }
+                }
+                drop(downloads_guard);
+                sender.input(AppInput::SaveDownloads);
+            }
+
+            AppInput::PageSaved(saved_page) => {
+                let mut saved_pages_guard = self.saved_pages.guard();
+                // Saving over a page that is already listed replaces it
+                let index = saved_pages_guard
+                    .iter()
+                    .position(|saved_page_row| saved_page_row.saved_page.path == saved_page.path);
+                if let Some(index) = index {
+                    saved_pages_guard.remove(index);
+                }
+                saved_pages_guard.push_front(saved_page);
+                drop(saved_pages_guard);
+                sender.input(AppInput::SaveSavedPages);
+            }
+
+            AppInput::SaveSavedPages => save_saved_pages(
+                &self
+                    .saved_pages
+                    .iter()
+                    .map(|saved_page_row| saved_page_row.saved_page.clone())
+                    .collect::<Vec<SavedPage>>(),
+            ),
+
+            AppInput::RemoveSavedPage(index) => {
+                self.saved_pages.guard().remove(index.current_index());
+                sender.input(AppInput::SaveSavedPages);
+            }
+
+            AppInput::OpenFiles(files) => {
+                for file in files {
+                    self.webwindowcontrolbars.guard().push_back((
+                        file.uri().to_string(),
+                        self.user_content_filter_store_option.clone(),
+                    ));
+                }
+                sender.input(AppInput::SaveUrls);
+            }
+
+            AppInput::WatchedPageChanged(url, title, page_change) => {
+                let mut shown = false;
+                for webwindowcontrolbar in self.webwindowcontrolbars.iter() {
+                    let webwindow = &webwindowcontrolbar.webwindow;
+                    if webwindow
+                        .widgets()
+                        .web_view
+                        .uri()
+                        .is_some_and(|uri| uri == url)
+                    {
+                        webwindow.emit(WebWindowInput::WatchedPageChanged(page_change.clone()));
+                        shown |= webwindow.widgets().web_window.is_active();
+                    }
+                }
+                // There is no need to notify the user about a page they are looking at
+                if !shown {
+                    let notification = gtk::gio::Notification::new(&format!("{title} changed"));
+                    notification.set_body(Some(&format!(
+                        "{:.1}% of the page is different",
+                        page_change.percent()
+                    )));
+                    notification.set_default_action_and_target_value(
+                        "app.show-page-changes",
+                        Some(&url.to_variant()),
+                    );
+                    relm4::main_application()
+                        .send_notification(Some(&format!("page-changed-{url}")), &notification);
+                }
+                self.page_changes
+                    .retain(|(changed_url, _, _)| *changed_url != url);
+                self.page_changes.push((url, title, page_change));
+            }
+
+            AppInput::ShowPageChanges(url) => {
+                if let Some((_, title, page_change)) = self
+                    .page_changes
+                    .iter()
+                    .find(|(changed_url, _, _)| *changed_url == url)
+                {
+                    root.present();
+                    present_page_changes(title.clone(), page_change.clone(), root);
+                }
+            }
         }
+        self.update_view(widgets, sender);
     }
 }
 
@@ -0,0 +1,145 @@
+#![allow(unused_imports)]
+#![allow(unused_variables)]
+use relm4::{adw::prelude::*, gtk::prelude::*, prelude::*};
+use webkit6::{gio, glib, prelude::*};
+
+use crate::security::describe_tls_errors;
+
+pub struct CertificateDialog {
+    host: String,
+    certificate: gio::TlsCertificate,
+    errors: gio::TlsCertificateFlags,
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for CertificateDialog {
+    type Init = (String, gio::TlsCertificate, gio::TlsCertificateFlags);
+    type Input = ();
+    type Output = ();
+
+    view! {
+        #[name(certificate_dialog)]
+        adw::Dialog {
+            set_title: "Certificate",
+            set_content_width: 450,
+
+            #[wrap(Some)]
+            set_child = &adw::ToolbarView {
+                add_top_bar = &adw::HeaderBar {},
+
+                #[wrap(Some)]
+                set_content = &adw::PreferencesPage {
+                    add = &adw::PreferencesGroup {
+                        set_title: &model.host,
+                        set_description: Some(&if model.errors.is_empty() {
+                            String::from("This certificate is valid")
+                        } else {
+                            format!(
+                                "This certificate {}",
+                                describe_tls_errors(model.errors, &model.host).join(", ")
+                            )
+                        }),
+
+                        adw::ActionRow {
+                            set_title: "Issued To",
+                            add_css_class: "property",
+                            set_subtitle_selectable: true,
+                            set_subtitle: &certificate_property(&model.certificate, "subject-name"),
+                        },
+
+                        adw::ActionRow {
+                            set_title: "Issued By",
+                            add_css_class: "property",
+                            set_subtitle_selectable: true,
+                            set_subtitle: &certificate_property(&model.certificate, "issuer-name"),
+                        },
+
+                        adw::ActionRow {
+                            set_title: "Valid From",
+                            add_css_class: "property",
+                            set_subtitle: &certificate_date(&model.certificate, "not-valid-before"),
+                        },
+
+                        adw::ActionRow {
+                            set_title: "Valid Until",
+                            add_css_class: "property",
+                            set_subtitle: &certificate_date(&model.certificate, "not-valid-after"),
+                        },
+
+                        adw::ActionRow {
+                            set_title: "SHA-256 Fingerprint",
+                            add_css_class: "property",
+                            set_subtitle_selectable: true,
+                            set_subtitle: &certificate_fingerprint(&model.certificate),
+                        },
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = CertificateDialog {
+            host: init.0,
+            certificate: init.1,
+            errors: init.2,
+        };
+        let widgets = view_output!();
+        ComponentParts {
+            model: model,
+            widgets: widgets,
+        }
+    }
+}
+
+// Read through properties because the getters need a newer GLib than Spidey targets
+fn certificate_property(certificate: &gio::TlsCertificate, property: &str) -> String {
+    certificate
+        .property::<Option<glib::GString>>(property)
+        .map(|value| value.to_string())
+        .unwrap_or(String::from("Unknown"))
+}
+
+fn certificate_date(certificate: &gio::TlsCertificate, property: &str) -> String {
+    certificate
+        .property::<Option<glib::DateTime>>(property)
+        .and_then(|date| date.to_local().ok())
+        .and_then(|date| date.format("%x %X").ok())
+        .map(|date| date.to_string())
+        .unwrap_or(String::from("Unknown"))
+}
+
+fn certificate_fingerprint(certificate: &gio::TlsCertificate) -> String {
+    certificate
+        .certificate()
+        .and_then(|der| glib::compute_checksum_for_data(glib::ChecksumType::Sha256, &der))
+        .map(|checksum| {
+            checksum
+                .as_bytes()
+                .chunks(2)
+                .map(|pair| String::from_utf8_lossy(pair).to_uppercase())
+                .collect::<Vec<String>>()
+                .join(":")
+        })
+        .unwrap_or(String::from("Unknown"))
+}
+
+/// Show the details of `certificate`, which was presented by `host`, on top of `parent`
+pub fn present_certificate_dialog(
+    host: String,
+    certificate: gio::TlsCertificate,
+    errors: gio::TlsCertificateFlags,
+    parent: &impl IsA<gtk::Widget>,
+) {
+    let certificate_dialog = CertificateDialog::builder()
+        .launch((host, certificate, errors))
+        .detach();
+    certificate_dialog
+        .widgets()
+        .certificate_dialog
+        .present(Some(parent));
+}
@@ -0,0 +1,272 @@
+#![allow(unused_imports)]
+#![allow(unused_variables)]
+use relm4::{
+    adw::prelude::*,
+    gtk::{glib::clone, prelude::*},
+    prelude::*,
+};
+use webkit6::{glib, prelude::*, WebsiteDataTypes};
+
+use crate::gsettings::gsettings;
+use crate::whoops::{attempt, Catch, IntoWhoops, Whoops};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeRange {
+    LastHour,
+    LastDay,
+    LastWeek,
+    AllTime,
+}
+
+impl TimeRange {
+    const ALL: [TimeRange; 4] = [
+        TimeRange::LastHour,
+        TimeRange::LastDay,
+        TimeRange::LastWeek,
+        TimeRange::AllTime,
+    ];
+    fn label(&self) -> &'static str {
+        match self {
+            TimeRange::LastHour => "Last hour",
+            TimeRange::LastDay => "Last 24 hours",
+            TimeRange::LastWeek => "Last 7 days",
+            TimeRange::AllTime => "All time",
+        }
+    }
+    /// A timespan of 0 tells WebKit to clear everything regardless of when it was modified
+    pub fn timespan(&self) -> glib::TimeSpan {
+        match self {
+            TimeRange::LastHour => glib::TimeSpan::from_hours(1),
+            TimeRange::LastDay => glib::TimeSpan::from_days(1),
+            TimeRange::LastWeek => glib::TimeSpan::from_days(7),
+            TimeRange::AllTime => glib::TimeSpan(0),
+        }
+    }
+}
+
+const DATA_TYPES: [(&str, &str, WebsiteDataTypes); 7] = [
+    (
+        "Cookies",
+        "Signs you out of most websites",
+        WebsiteDataTypes::COOKIES,
+    ),
+    (
+        "Cached Files",
+        "Pages load slower the next time you visit them",
+        WebsiteDataTypes::MEMORY_CACHE
+            .union(WebsiteDataTypes::DISK_CACHE)
+            .union(WebsiteDataTypes::DOM_CACHE),
+    ),
+    (
+        "Local Storage",
+        "Site preferences and offline data",
+        WebsiteDataTypes::LOCAL_STORAGE.union(WebsiteDataTypes::SESSION_STORAGE),
+    ),
+    (
+        "IndexedDB Databases",
+        "Larger amounts of data stored by web apps",
+        WebsiteDataTypes::INDEXEDDB_DATABASES,
+    ),
+    (
+        "Service Workers",
+        "Background scripts registered by websites",
+        WebsiteDataTypes::SERVICE_WORKER_REGISTRATIONS,
+    ),
+    (
+        "HSTS Policies",
+        "Which sites must always be visited over HTTPS",
+        WebsiteDataTypes::HSTS_CACHE,
+    ),
+    (
+        "Tracking Prevention Data",
+        "What Intelligent Tracking Prevention has learnt about trackers",
+        WebsiteDataTypes::ITP,
+    ),
+];
+
+pub struct ClearDataDialog {
+    session: webkit6::NetworkSession,
+    time_range: TimeRange,
+    data_types: WebsiteDataTypes,
+    clear_session: bool,
+    clearing: bool,
+}
+
+#[derive(Debug)]
+pub enum ClearDataInput {
+    SetTimeRange(u32),
+    ToggleDataType(WebsiteDataTypes, bool),
+    ToggleSession(bool),
+    Clear,
+    Cleared(Result<(), glib::Error>),
+}
+
+#[relm4::component(pub)]
+impl Component for ClearDataDialog {
+    type Init = webkit6::NetworkSession;
+    type Input = ClearDataInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        #[name(clear_data_dialog)]
+        adw::Dialog {
+            set_title: "Clear Browsing Data",
+            set_content_width: 450,
+
+            #[wrap(Some)]
+            set_child = &adw::ToolbarView {
+                add_top_bar = &adw::HeaderBar {},
+
+                #[wrap(Some)]
+                #[name(toast_overlay)]
+                set_content = &adw::ToastOverlay {
+                    adw::PreferencesPage {
+                        add = &adw::PreferencesGroup {
+                            adw::ComboRow {
+                                set_title: "Time Range",
+                                set_model: Some(&gtk::StringList::new(
+                                    &TimeRange::ALL.map(|time_range| time_range.label()),
+                                )),
+                                set_selected: 3,
+                                connect_selected_notify[sender] => move |this_row| {
+                                    sender.input(ClearDataInput::SetTimeRange(this_row.selected()));
+                                },
+                            },
+                        },
+
+                        #[name(data_types_group)]
+                        add = &adw::PreferencesGroup {
+                            set_title: "Website Data",
+                        },
+
+                        add = &adw::PreferencesGroup {
+                            set_title: "Spidey",
+
+                            adw::SwitchRow {
+                                set_title: "Web Window Session",
+                                #[watch]
+                                set_subtitle: if model.time_range == TimeRange::AllTime {
+                                    "Web Windows that are reopened when Spidey starts"
+                                } else {
+                                    "Not affected by this time range, as Spidey doesn't record when Web Windows were opened. Choose “All time” to clear it"
+                                },
+                                #[watch]
+                                set_sensitive: model.time_range == TimeRange::AllTime,
+                                connect_active_notify[sender] => move |this_row| {
+                                    sender.input(ClearDataInput::ToggleSession(this_row.is_active()));
+                                },
+                            },
+                        },
+
+                        add = &adw::PreferencesGroup {
+                            gtk::Button {
+                                set_label: "Clear Data",
+                                set_halign: gtk::Align::Center,
+                                add_css_class: "pill",
+                                add_css_class: "destructive-action",
+                                #[watch]
+                                set_sensitive: !model.clearing
+                                    && (!model.data_types.is_empty() || model.clear_session),
+                                connect_clicked => ClearDataInput::Clear,
+                            },
+                        },
+                    }
+                }
+            }
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = ClearDataDialog {
+            session: init,
+            time_range: TimeRange::AllTime,
+            data_types: WebsiteDataTypes::COOKIES
+                | WebsiteDataTypes::MEMORY_CACHE
+                | WebsiteDataTypes::DISK_CACHE
+                | WebsiteDataTypes::DOM_CACHE,
+            clear_session: false,
+            clearing: false,
+        };
+        let widgets = view_output!();
+        for (title, subtitle, data_type) in DATA_TYPES {
+            let data_type_row = adw::SwitchRow::builder()
+                .title(title)
+                .subtitle(subtitle)
+                .active(model.data_types.contains(data_type))
+                .build();
+            data_type_row.connect_active_notify(clone!(@strong sender => move |this_row| {
+                sender.input(ClearDataInput::ToggleDataType(data_type, this_row.is_active()));
+            }));
+            widgets.data_types_group.add(&data_type_row);
+        }
+        ComponentParts {
+            model: model,
+            widgets: widgets,
+        }
+    }
+
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::Input,
+        sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        attempt(|| {
+            match message {
+                ClearDataInput::SetTimeRange(index) => {
+                    self.time_range = *TimeRange::ALL.get(index as usize)?
+                }
+                ClearDataInput::ToggleDataType(data_type, active) => {
+                    self.data_types.set(data_type, active)
+                }
+                ClearDataInput::ToggleSession(active) => self.clear_session = active,
+                ClearDataInput::Clear => {
+                    self.clearing = true;
+                    if self.clear_session && self.time_range == TimeRange::AllTime {
+                        gsettings().reset("urls");
+                    }
+                    if self.data_types.is_empty() {
+                        sender.input(ClearDataInput::Cleared(Ok(())));
+                    } else {
+                        self.session.website_data_manager()?.clear(
+                            self.data_types,
+                            self.time_range.timespan(),
+                            gtk::gio::Cancellable::NONE,
+                            clone!(@strong sender => move |result| {
+                                sender.input(ClearDataInput::Cleared(result));
+                            }),
+                        );
+                    }
+                }
+                ClearDataInput::Cleared(result) => {
+                    self.clearing = false;
+                    widgets
+                        .toast_overlay
+                        .add_toast(adw::Toast::new(&match result {
+                            Ok(_) => String::from("Browsing data cleared"),
+                            Err(error) => format!("Could not clear browsing data: {error}"),
+                        }));
+                }
+            }
+            self.update_view(widgets, sender.clone());
+            Some(())
+        })
+        .catch(|error| eprintln!("{error}"));
+    }
+}
+
+/// Open the clear browsing data dialog for the given network session on top of `parent`
+pub fn present_clear_data(session: webkit6::NetworkSession, parent: &impl IsA<gtk::Widget>) {
+    let mut clear_data_dialog = ClearDataDialog::builder().launch(session).detach();
+    clear_data_dialog.detach_runtime();
+    clear_data_dialog
+        .widgets()
+        .clear_data_dialog
+        .present(Some(parent));
+}
@@ -0,0 +1,506 @@
+#![allow(unused_imports)]
+#![allow(unused_variables)]
+use documents::prelude::*;
+use relm4::{
+    adw::prelude::*,
+    gtk::{glib::clone, prelude::*},
+    prelude::*,
+};
+use serde_json::json;
+
+use webkit6::{gio, glib, prelude::*, soup};
+
+use crate::gsettings::gsettings;
+use crate::sitesettings::{site_values, SiteException};
+use crate::whoops::{attempt, Catch, IntoWhoops, Whoops};
+
+/// Set on a cookie manager once it deletes the cookies of sites that may not have any
+const COOKIE_EXCEPTIONS_WATCHED: &str = "spidey-cookie-exceptions-watched";
+/// The settings a user content manager follows to keep its cookie exceptions up to date
+const COOKIE_EXCEPTIONS_SETTINGS: &str = "spidey-cookie-exceptions-settings";
+
+pub struct CookieManagerDialog {
+    session: webkit6::NetworkSession,
+    cookies_by_domain: Vec<(String, Vec<soup::Cookie>)>,
+    search_query: String,
+    loading: bool,
+}
+
+#[derive(Debug)]
+pub enum CookieManagerInput {
+    Reload,
+    CookiesLoaded(Vec<soup::Cookie>),
+    Search(String),
+    DeleteCookie(String, usize),
+    DeleteDomain(String),
+    DeleteAll,
+    ShowToast(String),
+}
+
+#[relm4::component(pub)]
+impl Component for CookieManagerDialog {
+    type Init = webkit6::NetworkSession;
+    type Input = CookieManagerInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        #[name(cookie_manager_dialog)]
+        adw::Dialog {
+            set_title: "Cookies",
+            set_content_width: 500,
+            set_content_height: 600,
+
+            #[wrap(Some)]
+            set_child = &adw::ToolbarView {
+                add_top_bar = &adw::HeaderBar {
+                    pack_start = &gtk::Button {
+                        set_label: "Delete All",
+                        add_css_class: "destructive-action",
+                        #[watch]
+                        set_sensitive: !model.cookies_by_domain.is_empty(),
+                        connect_clicked => CookieManagerInput::DeleteAll,
+                    },
+                },
+
+                add_top_bar = &gtk::SearchEntry {
+                    set_margin_all: 6,
+                    set_placeholder_text: Some("Search domains and cookie names"),
+                    connect_search_changed[sender] => move |this_entry| {
+                        sender.input(CookieManagerInput::Search(this_entry.text().to_string()));
+                    },
+                },
+
+                #[wrap(Some)]
+                #[name(toast_overlay)]
+                set_content = &adw::ToastOverlay {
+                    gtk::Stack {
+                        #[watch]
+                        set_visible_child_name: if model.loading {
+                            "loading"
+                        } else if model.cookies_by_domain.is_empty() {
+                            "empty"
+                        } else {
+                            "cookies"
+                        },
+
+                        add_named[Some("loading")] = &gtk::Spinner {
+                            set_halign: gtk::Align::Center,
+                            set_valign: gtk::Align::Center,
+                            #[watch]
+                            set_spinning: model.loading,
+                        },
+
+                        add_named[Some("empty")] = &adw::StatusPage {
+                            set_icon_name: Some("padlock2"),
+                            set_title: "No Cookies",
+                            set_description: Some("Websites have not stored any cookies"),
+                        },
+
+                        add_named[Some("cookies")] = &gtk::ScrolledWindow {
+                            set_vexpand: true,
+
+                            adw::Clamp {
+                                set_margin_all: 12,
+
+                                #[name(domain_list)]
+                                gtk::ListBox {
+                                    add_css_class: "boxed-list",
+                                    set_valign: gtk::Align::Start,
+                                    set_selection_mode: gtk::SelectionMode::None,
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = CookieManagerDialog {
+            session: init,
+            cookies_by_domain: vec![],
+            search_query: String::new(),
+            loading: true,
+        };
+        let widgets = view_output!();
+        sender.input(CookieManagerInput::Reload);
+        ComponentParts {
+            model: model,
+            widgets: widgets,
+        }
+    }
+
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::Input,
+        sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        attempt(|| {
+            match message {
+                CookieManagerInput::Reload => {
+                    self.loading = true;
+                    self.session.cookie_manager()?.all_cookies(
+                        gtk::gio::Cancellable::NONE,
+                        clone!(@strong sender => move |cookies_result| match cookies_result {
+                            Ok(cookies) => sender.input(CookieManagerInput::CookiesLoaded(cookies)),
+                            Err(error) => {
+                                eprintln!("Could not fetch cookies: {error}");
+                                sender.input(CookieManagerInput::CookiesLoaded(vec![]));
+                            }
+                        }),
+                    );
+                }
+                CookieManagerInput::CookiesLoaded(cookies) => {
+                    self.loading = false;
+                    self.cookies_by_domain = group_by_domain(cookies);
+                    self.populate(&widgets.domain_list, &sender);
+                }
+                CookieManagerInput::Search(query) => {
+                    self.search_query = query.to_lowercase();
+                    self.populate(&widgets.domain_list, &sender);
+                }
+                CookieManagerInput::DeleteCookie(domain, index) => {
+                    let (_, cookies) = self
+                        .cookies_by_domain
+                        .iter()
+                        .find(|(cookie_domain, _)| *cookie_domain == domain)?;
+                    let mut cookie = cookies.get(index)?.clone();
+                    self.session.cookie_manager()?.delete_cookie(
+                        &mut cookie,
+                        gtk::gio::Cancellable::NONE,
+                        clone!(@strong sender => move |result| {
+                            if let Err(error) = result {
+                                sender.input(CookieManagerInput::ShowToast(format!("Could not delete cookie: {error}")));
+                            }
+                            sender.input(CookieManagerInput::Reload);
+                        }),
+                    );
+                }
+                CookieManagerInput::DeleteDomain(domain) => {
+                    let (_, cookies) = self
+                        .cookies_by_domain
+                        .iter()
+                        .find(|(cookie_domain, _)| *cookie_domain == domain)?;
+                    let cookie_manager = self.session.cookie_manager()?;
+                    let remaining = std::rc::Rc::new(std::cell::Cell::new(cookies.len()));
+                    for cookie in cookies {
+                        let mut cookie = cookie.clone();
+                        cookie_manager.delete_cookie(
+                            &mut cookie,
+                            gtk::gio::Cancellable::NONE,
+                            clone!(@strong sender, @strong remaining => move |_| {
+                                remaining.set(remaining.get() - 1);
+                                if remaining.get() == 0 {
+                                    sender.input(CookieManagerInput::Reload);
+                                }
+                            }),
+                        );
+                    }
+                }
+                CookieManagerInput::DeleteAll => {
+                    self.session.website_data_manager()?.clear(
+                        webkit6::WebsiteDataTypes::COOKIES,
+                        glib::TimeSpan(0),
+                        gtk::gio::Cancellable::NONE,
+                        clone!(@strong sender => move |result| {
+                            match result {
+                                Ok(_) => sender.input(CookieManagerInput::ShowToast("Deleted all cookies".to_string())),
+                                Err(error) => sender.input(CookieManagerInput::ShowToast(format!("Could not delete cookies: {error}"))),
+                            }
+                            sender.input(CookieManagerInput::Reload);
+                        }),
+                    );
+                }
+                CookieManagerInput::ShowToast(message) => {
+                    widgets.toast_overlay.add_toast(adw::Toast::new(&message))
+                }
+            }
+            self.update_view(widgets, sender.clone());
+            Some(())
+        })
+        .catch(|error| eprintln!("{error}"));
+    }
+}
+
+impl CookieManagerDialog {
+    fn populate(&self, domain_list: &gtk::ListBox, sender: &ComponentSender<Self>) {
+        domain_list.remove_all();
+        for (domain, cookies) in &self.cookies_by_domain {
+            let domain_matches = domain.to_lowercase().contains(&self.search_query);
+            let matching_cookies = cookies
+                .iter()
+                .enumerate()
+                .filter(|(_, cookie)| {
+                    domain_matches
+                        || cookie
+                            .clone()
+                            .name()
+                            .is_some_and(|name| name.to_lowercase().contains(&self.search_query))
+                })
+                .collect::<Vec<_>>();
+            if matching_cookies.is_empty() {
+                continue;
+            }
+
+            let domain_row = adw::ExpanderRow::builder()
+                .title(domain.as_str())
+                .subtitle(if cookies.len() == 1 {
+                    String::from("1 cookie")
+                } else {
+                    format!("{} cookies", cookies.len())
+                })
+                .build();
+            let delete_domain_button = gtk::Button::builder()
+                .icon_name("user-trash-symbolic")
+                .tooltip_text("Delete all cookies from this site")
+                .valign(gtk::Align::Center)
+                .css_classes(["flat", "circular"])
+                .build();
+            delete_domain_button.connect_clicked(
+                clone!(@strong sender, @strong domain => move |_| {
+                    sender.input(CookieManagerInput::DeleteDomain(domain.clone()));
+                }),
+            );
+            domain_row.add_suffix(&delete_domain_button);
+
+            for (index, cookie) in matching_cookies {
+                let cookie_row = adw::ActionRow::builder()
+                    .title(cookie.clone().name().unwrap_or_default().as_str())
+                    .subtitle(describe_cookie(&mut cookie.clone()))
+                    .use_markup(false)
+                    .build();
+                let delete_cookie_button = gtk::Button::builder()
+                    .icon_name("user-trash-symbolic")
+                    .tooltip_text("Delete cookie")
+                    .valign(gtk::Align::Center)
+                    .css_classes(["flat", "circular"])
+                    .build();
+                delete_cookie_button.connect_clicked(
+                    clone!(@strong sender, @strong domain => move |_| {
+                        sender.input(CookieManagerInput::DeleteCookie(domain.clone(), index));
+                    }),
+                );
+                cookie_row.add_suffix(&delete_cookie_button);
+                domain_row.add_row(&cookie_row);
+            }
+            domain_list.append(&domain_row);
+        }
+    }
+}
+
+fn group_by_domain(cookies: Vec<soup::Cookie>) -> Vec<(String, Vec<soup::Cookie>)> {
+    let mut cookies_by_domain: Vec<(String, Vec<soup::Cookie>)> = vec![];
+    for mut cookie in cookies {
+        let domain = cookie
+            .domain()
+            .map(|domain| domain.trim_start_matches('.').to_string())
+            .unwrap_or_default();
+        match cookies_by_domain
+            .iter_mut()
+            .find(|(cookie_domain, _)| *cookie_domain == domain)
+        {
+            Some((_, domain_cookies)) => domain_cookies.push(cookie),
+            None => cookies_by_domain.push((domain, vec![cookie])),
+        }
+    }
+    cookies_by_domain.sort_by(|(a, _), (b, _)| a.cmp(b));
+    cookies_by_domain
+}
+
+fn describe_cookie(cookie: &mut soup::Cookie) -> String {
+    let mut description = vec![match cookie.expires() {
+        Some(expires) => match expires.format("%x %X") {
+            Ok(expires) => format!("Expires {expires}"),
+            Err(_) => String::from("Expires at an unknown time"),
+        },
+        None => String::from("Expires when the session ends"),
+    }];
+    if cookie.is_secure() {
+        description.push(String::from("Secure"));
+    }
+    if cookie.is_http_only() {
+        description.push(String::from("HttpOnly"));
+    }
+    description.push(match cookie.same_site_policy() {
+        soup::SameSitePolicy::Strict => String::from("SameSite=Strict"),
+        soup::SameSitePolicy::Lax => String::from("SameSite=Lax"),
+        _ => String::from("SameSite=None"),
+    });
+    description.join(" · ")
+}
+
+/// Open the cookie manager for the given network session on top of `parent`
+pub fn present_cookie_manager(session: webkit6::NetworkSession, parent: &impl IsA<gtk::Widget>) {
+    let mut cookie_manager_dialog = CookieManagerDialog::builder().launch(session).detach();
+    cookie_manager_dialog.detach_runtime();
+    cookie_manager_dialog
+        .widgets()
+        .cookie_manager_dialog
+        .present(Some(parent));
+}
+
+/// Apply the cookie accept policy to a network session. Every Web Window shares the session's accept policy, so it
+/// only follows the preference; exceptions are enforced per site by `set_up_cookie_exceptions`.
+pub fn apply_cookie_policy(session: &webkit6::NetworkSession) {
+    let Some(cookie_manager) = session.cookie_manager() else {
+        return;
+    };
+    let has_allowed_hosts = site_values("cookie-exceptions")
+        .into_values()
+        .any(|value| SiteException::from_value(Some(value)) == SiteException::Allow);
+    let policy = match gsettings().string("cookie-accept-policy").as_str() {
+        "always" => webkit6::CookieAcceptPolicy::Always,
+        // Let allowed sites store cookies, every other site is kept from storing them by the cookie exceptions filter
+        "never" if has_allowed_hosts => webkit6::CookieAcceptPolicy::Always,
+        "never" => webkit6::CookieAcceptPolicy::Never,
+        _ => webkit6::CookieAcceptPolicy::NoThirdParty,
+    };
+    cookie_manager.set_accept_policy(policy);
+    // Each session has its own cookie manager, so each one is watched
+    // SAFETY: the flag is only ever stored and read as a bool
+    if unsafe { cookie_manager.data::<bool>(COOKIE_EXCEPTIONS_WATCHED) }.is_none() {
+        unsafe { cookie_manager.set_data(COOKIE_EXCEPTIONS_WATCHED, true) };
+        cookie_manager
+            .connect_changed(clone!(@weak session => move |_| delete_refused_cookies(&session)));
+    }
+    delete_refused_cookies(session);
+}
+
+/// Keep pages in `user_content_manager` from sending or storing cookies of sites that may not have any, now and
+/// whenever the cookie preferences change
+pub fn set_up_cookie_exceptions(user_content_manager: &webkit6::UserContentManager) {
+    apply_cookie_exceptions(user_content_manager, || {});
+    let settings = gsettings();
+    for key in ["cookie-accept-policy", "cookie-exceptions"] {
+        settings.connect_changed(
+            Some(key),
+            clone!(@weak user_content_manager => move |_, _| apply_cookie_exceptions(&user_content_manager, || {})),
+        );
+    }
+    // The settings only send change notifications while they are alive, so they live as long as the manager
+    // SAFETY: the settings are only ever stored, never read back
+    unsafe { user_content_manager.set_data(COOKIE_EXCEPTIONS_SETTINGS, settings) };
+}
+
+/// Content blocker rules that keep requests to sites that may not have cookies from sending or storing them
+fn cookie_exception_rules() -> Option<String> {
+    let url_filter = |host: &str| format!("^[^:]+://+([^:/]+\\.)?{}[:/]", host.replace('.', "\\."));
+    let mut allowed_hosts = vec![];
+    let mut blocked_hosts = vec![];
+    for (host, value) in site_values("cookie-exceptions") {
+        match SiteException::from_value(Some(value)) {
+            SiteException::Allow => allowed_hosts.push(host),
+            SiteException::Block => blocked_hosts.push(host),
+            SiteException::Default => {}
+        }
+    }
+    let mut rules = vec![];
+    // When cookies are refused but some sites are allowed, the accept policy lets every site have them
+    if gsettings().string("cookie-accept-policy") == "never" && !allowed_hosts.is_empty() {
+        rules.push(json!({"trigger": {"url-filter": ".*"}, "action": {"type": "block-cookies"}}));
+        for host in &allowed_hosts {
+            rules.push(json!({"trigger": {"url-filter": url_filter(host)}, "action": {"type": "ignore-previous-rules"}}));
+        }
+    }
+    for host in &blocked_hosts {
+        rules.push(json!({"trigger": {"url-filter": url_filter(host)}, "action": {"type": "block-cookies"}}));
+    }
+    if rules.is_empty() {
+        return None;
+    }
+    Some(serde_json::Value::Array(rules).to_string())
+}
+
+/// Replace the filter that keeps sites from having cookies they may not have according to the current settings,
+/// then call `then`
+pub fn apply_cookie_exceptions(
+    user_content_manager: &webkit6::UserContentManager,
+    then: impl FnOnce() + 'static,
+) {
+    user_content_manager.remove_filter_by_id("cookie-exceptions");
+    let Some(rules) = cookie_exception_rules() else {
+        then();
+        return;
+    };
+    let mut user_content_filter_store_option = None;
+    with(
+        &[Document::at(
+            Project(Data(&["UserContentFilterStore"]).with_id("com", "github.kdwk", "Spidey")),
+            "",
+            Create::OnlyIfNotExists,
+        )
+        .alias("UserContentFilterStore")],
+        |d| {
+            user_content_filter_store_option = Some(webkit6::UserContentFilterStore::new(
+                d["UserContentFilterStore"].path().as_str(),
+            ));
+            Ok(())
+        },
+    );
+    let Some(user_content_filter_store) = user_content_filter_store_option else {
+        then();
+        return;
+    };
+    let user_content_manager = user_content_manager.clone();
+    user_content_filter_store.save(
+        "cookie-exceptions",
+        &glib::Bytes::from_owned(rules),
+        gio::Cancellable::NONE,
+        move |user_content_filter_result| {
+            match user_content_filter_result {
+                Ok(user_content_filter) => user_content_manager.add_filter(&user_content_filter),
+                Err(error) => eprintln!("Could not compile cookie exceptions filter: {error}"),
+            }
+            then();
+        },
+    );
+}
+
+/// Delete cookies that sites aren't allowed to have. The cookie exceptions filter keeps them from being stored by
+/// requests, but cookies stored before a site was blocked, or set by scripts, still end up in the cookie jar.
+fn delete_refused_cookies(session: &webkit6::NetworkSession) {
+    let Some(cookie_manager) = session.cookie_manager() else {
+        return;
+    };
+    let mut allowed_hosts = vec![];
+    let mut blocked_hosts = vec![];
+    for (host, value) in site_values("cookie-exceptions") {
+        match SiteException::from_value(Some(value)) {
+            SiteException::Allow => allowed_hosts.push(host),
+            SiteException::Block => blocked_hosts.push(host),
+            SiteException::Default => {}
+        }
+    }
+    let refuse_others =
+        gsettings().string("cookie-accept-policy") == "never" && !allowed_hosts.is_empty();
+    if blocked_hosts.is_empty() && !refuse_others {
+        return;
+    }
+    cookie_manager.all_cookies(
+        gtk::gio::Cancellable::NONE,
+        clone!(@strong cookie_manager => move |cookies_result| {
+            let Ok(cookies) = cookies_result else {
+                return;
+            };
+            for mut cookie in cookies {
+                let blocked = blocked_hosts.iter().any(|host| cookie.domain_matches(host));
+                let allowed = allowed_hosts.iter().any(|host| cookie.domain_matches(host));
+                if blocked || (refuse_others && !allowed) {
+                    cookie_manager.delete_cookie(&mut cookie, gtk::gio::Cancellable::NONE, |result| {
+                        if let Err(error) = result {
+                            eprintln!("Could not delete cookie: {error}");
+                        }
+                    });
+                }
+            }
+        }),
+    );
+}
@@ -0,0 +1,1099 @@
+#![allow(unused_imports)]
+#![allow(unused_variables)]
+use std::{
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::offset::Utc;
+use documents::prelude::*;
+use relm4::{
+    adw::prelude::*,
+    gtk::{gio, glib, glib::clone, prelude::*},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use webkit6::{prelude::*, soup};
+
+use crate::app::{AppInput, APP_BROKER};
+use crate::gsettings::gsettings;
+use crate::proxy::proxied_client;
+use crate::sitesettings::{host_of, set_site_value, site_value, site_values};
+use crate::useragent::user_agent_for;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DownloadState {
+    InProgress,
+    Finished,
+    Failed,
+    Cancelled,
+}
+
+/// What is remembered about a download after Spidey quits
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DownloadRecord {
+    pub url: String,
+    pub source_page: Option<String>,
+    pub destination: Option<String>,
+    pub state: DownloadState,
+    /// Unix timestamp of when the download started
+    pub started: i64,
+    pub total_size: u64,
+    /// Why the download failed
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Data already received by a failed download or retry, which the next retry continues from
+    #[serde(default)]
+    pub partial: Option<String>,
+    /// The ETag or Last-Modified date the partial data was sent with, so it is only continued while the file is unchanged
+    #[serde(default)]
+    pub validator: Option<String>,
+}
+
+impl DownloadRecord {
+    pub fn new(download: &webkit6::Download) -> Self {
+        Self {
+            url: download
+                .request()
+                .and_then(|request| request.uri())
+                .map(|uri| uri.to_string())
+                .unwrap_or_default(),
+            source_page: download
+                .web_view()
+                .and_then(|web_view| web_view.uri())
+                .map(|uri| uri.to_string()),
+            destination: None,
+            state: DownloadState::InProgress,
+            started: Utc::now().timestamp(),
+            total_size: 0,
+            error: None,
+            partial: None,
+            validator: None,
+        }
+    }
+    /// Delete the data kept for resuming, e.g. when the download is removed from the list
+    pub fn discard_partial(&mut self) {
+        if let Some(partial) = self.partial.take() {
+            _ = fs::remove_file(partial);
+        }
+        self.validator = None;
+    }
+    pub fn file_name(&self) -> String {
+        self.destination
+            .as_deref()
+            .and_then(|destination| std::path::Path::new(destination).file_name())
+            .map(|file_name| file_name.to_string_lossy().to_string())
+            .or_else(|| {
+                url::Url::parse(&self.url)
+                    .ok()?
+                    .path_segments()?
+                    .last()
+                    .filter(|segment| !segment.is_empty())
+                    .map(|segment| segment.to_string())
+            })
+            .unwrap_or(String::from("Download"))
+    }
+}
+
+/// The folder downloads go to when no rule matches them
+pub fn default_download_folder() -> PathBuf {
+    match gsettings().string("download-folder").as_str() {
+        "" => glib::user_special_dir(glib::UserDirectory::Downloads)
+            .unwrap_or_else(|| glib::home_dir().join("Downloads")),
+        download_folder => PathBuf::from(download_folder),
+    }
+}
+
+/// Rules are keyed by a file extension like "pdf", a MIME type like "application/pdf" or a
+/// whole kind of MIME type like "image/*"
+pub fn normalize_download_rule(file_type: &str) -> Option<String> {
+    let file_type = file_type
+        .trim()
+        .trim_start_matches("*.")
+        .trim_start_matches('.')
+        .to_lowercase();
+    match file_type.as_str() {
+        // A rule for everything is what the default download folder is for
+        "" | "*" | "*/*" => None,
+        file_type if file_type.contains(char::is_whitespace) => None,
+        file_type => Some(file_type.to_string()),
+    }
+}
+
+pub fn download_rules() -> Vec<(String, String)> {
+    let mut download_rules = site_values("download-rules")
+        .into_iter()
+        .collect::<Vec<_>>();
+    download_rules.sort();
+    download_rules
+}
+
+pub fn set_download_rule(file_type: &str, folder: Option<&str>) {
+    set_site_value("download-rules", file_type, folder);
+}
+
+/// The rules that could apply to a file, most specific first: its exact MIME type, its kind of MIME type,
+/// then its extensions, e.g. "tar.gz" before "gz"
+fn download_rule_candidates(file_name: &str, mime_type: Option<&str>) -> Vec<String> {
+    let mut candidates = vec![];
+    if let Some(mime_type) = mime_type.map(|mime_type| mime_type.trim().to_lowercase()) {
+        let mime_type = mime_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        if let Some((kind, _)) = mime_type.split_once('/') {
+            candidates.push(mime_type.clone());
+            candidates.push(format!("{kind}/*"));
+        }
+    }
+    let file_name = file_name.trim_start_matches('.').to_lowercase();
+    candidates.extend(
+        file_name
+            .match_indices('.')
+            .map(|(index, _)| file_name[index + 1..].to_string())
+            .filter(|extension| !extension.is_empty()),
+    );
+    candidates
+}
+
+/// Pick the folder for a file, preferring a rule for its exact MIME type, then its kind of MIME type,
+/// then its extension
+pub fn download_folder_for(file_name: &str, mime_type: Option<&str>) -> PathBuf {
+    download_rule_candidates(file_name, mime_type)
+        .iter()
+        .find_map(|file_type| site_value("download-rules", file_type))
+        .map(PathBuf::from)
+        .unwrap_or_else(default_download_folder)
+}
+
+/// A path in `folder` for `file_name` that doesn't overwrite anything, e.g. "paper (1).pdf"
+pub fn available_path(folder: &Path, file_name: &str) -> PathBuf {
+    let file_name = Path::new(file_name)
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or(String::from("Download"));
+    let path = folder.join(&file_name);
+    if !path.exists() {
+        return path;
+    }
+    // Keep compressed tarballs' double extension together, e.g. "source (1).tar.gz"
+    let (stem, extension) = match file_name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => match stem.rsplit_once('.') {
+            Some((tarball, "tar")) if !tarball.is_empty() => {
+                (tarball.to_string(), format!(".tar.{extension}"))
+            }
+            _ => (stem.to_string(), format!(".{extension}")),
+        },
+        _ => (file_name.clone(), String::new()),
+    };
+    (1..)
+        .map(|number| folder.join(format!("{stem} ({number}){extension}")))
+        .find(|path| !path.exists())
+        .unwrap_or(path)
+}
+
+/// Save a download to the folder its rules or the preferences say, or ask where to save it
+/// if the user wants to be asked every time
+pub fn decide_download_destination(
+    download: &webkit6::Download,
+    suggested_filename: &str,
+    parent: &gtk::Window,
+) {
+    let mime_type = download
+        .response()
+        .and_then(|response| response.mime_type())
+        .map(|mime_type| mime_type.to_string());
+    let folder = download_folder_for(suggested_filename, mime_type.as_deref());
+    if gsettings().boolean("download-ask") {
+        let parent = download
+            .web_view()
+            .and_then(|web_view| web_view.root())
+            .and_downcast::<gtk::Window>()
+            .unwrap_or(parent.clone());
+        let file_dialog = gtk::FileDialog::builder()
+            .title("Save Download")
+            .initial_name(suggested_filename)
+            .modal(true)
+            .build();
+        if folder.is_dir() {
+            file_dialog.set_initial_folder(Some(&gio::File::for_path(&folder)));
+        }
+        file_dialog.save(
+            Some(&parent),
+            gio::Cancellable::NONE,
+            clone!(@strong download => move |file_result| {
+                match file_result.ok().and_then(|file| file.path()) {
+                    Some(path) => {
+                        // The file chooser has already asked about replacing an existing file
+                        download.set_allow_overwrite(true);
+                        download.set_destination(&path.to_string_lossy());
+                    }
+                    None => download.cancel(),
+                }
+            }),
+        );
+    } else {
+        if let Err(error) = fs::create_dir_all(&folder) {
+            eprintln!("Could not create download folder: {error}");
+        }
+        download.set_destination(&available_path(&folder, suggested_filename).to_string_lossy());
+    }
+}
+
+/// Let the user pick somewhere else for a download. It is moved there once it has finished.
+pub fn change_download_destination(download: &webkit6::Download, parent: &impl IsA<gtk::Widget>) {
+    let parent_window = parent.root().and_downcast::<gtk::Window>();
+    let file_dialog = gtk::FileDialog::builder()
+        .title("Change Download Location")
+        .modal(true)
+        .build();
+    if let Some(destination) = download.destination() {
+        file_dialog.set_initial_file(Some(&gio::File::for_path(destination.as_str())));
+    }
+    file_dialog.save(
+        parent_window.as_ref(),
+        gio::Cancellable::NONE,
+        clone!(@strong download => move |file_result| {
+            if let Some(path) = file_result.ok().and_then(|file| file.path()) {
+                APP_BROKER.send(AppInput::ChangeDownloadDestination(
+                    download,
+                    path.to_string_lossy().to_string(),
+                ));
+            }
+        }),
+    );
+}
+
+/// URLs of downloads that should be opened in the default app once they finish
+static OPEN_WHEN_FINISHED: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+pub fn open_when_finished(url: &str) {
+    if let Ok(mut urls) = OPEN_WHEN_FINISHED.lock() {
+        urls.push(url.to_string());
+    }
+}
+
+fn take_open_when_finished(urls_of_download: &[String]) -> bool {
+    let Ok(mut urls) = OPEN_WHEN_FINISHED.lock() else {
+        return false;
+    };
+    match urls.iter().position(|url| urls_of_download.contains(url)) {
+        Some(index) => {
+            urls.remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
+fn with_downloads_json(closure: impl FnOnce(String)) {
+    let mut closure = Some(closure);
+    with(
+        &[Document::at(
+            Project(Data(&[]).with_id("com", "github.kdwk", "Spidey")),
+            "downloads.json",
+            Create::No,
+        )],
+        |d| {
+            if let Some(closure) = closure.take() {
+                closure(d["downloads.json"].path());
+            }
+            Ok(())
+        },
+    );
+}
+
+/// Get the downloads from previous sessions. Ones that were still going when Spidey quit are marked as failed.
+pub fn load_download_records() -> Vec<DownloadRecord> {
+    let mut records: Vec<DownloadRecord> = vec![];
+    with_downloads_json(|path| {
+        if let Ok(json) = fs::read_to_string(path) {
+            records = serde_json::from_str(&json).unwrap_or_default();
+        }
+    });
+    for record in &mut records {
+        if record.state == DownloadState::InProgress {
+            record.state = DownloadState::Failed;
+            record.error = Some(String::from("Spidey quit before it finished"));
+        }
+    }
+    records
+}
+
+pub fn save_download_records(records: &[DownloadRecord]) {
+    with_downloads_json(|path| {
+        let result = serde_json::to_string(records)
+            .map_err(|error| error.to_string())
+            .and_then(|json| fs::write(path, json).map_err(|error| error.to_string()));
+        if let Err(error) = result {
+            eprintln!("Could not save downloads: {error}");
+        }
+    });
+}
+
+pub struct DownloadRow {
+    id: DynamicIndex,
+    pub record: DownloadRecord,
+    download: Option<webkit6::Download>,
+    received: u64,
+    progress: f64,
+    speed: Option<u64>,
+    speed_sample: (Instant, u64),
+    pending_destination: Option<String>,
+    /// Set to stop a retry, which Spidey downloads itself rather than through WebKit
+    retry_cancelled: Option<Arc<AtomicBool>>,
+}
+
+#[derive(Debug)]
+pub enum DownloadRowInput {
+    Progress,
+    DestinationCreated(String),
+    MoveTo(String),
+    Failed { cancelled: bool, reason: String },
+    Finished,
+    Cancel,
+    Retry,
+    Open,
+    ShowInFolder,
+    Remove,
+}
+
+#[derive(Debug)]
+pub enum DownloadRowOutput {
+    Changed,
+    Remove(DynamicIndex),
+}
+
+#[derive(Debug)]
+pub enum RetryProgress {
+    Started {
+        resumed: bool,
+        received: u64,
+        total_size: u64,
+        validator: Option<String>,
+    },
+    Received(u64),
+    Finished,
+    Failed {
+        cancelled: bool,
+        reason: String,
+    },
+}
+
+#[relm4::factory(pub)]
+impl FactoryComponent for DownloadRow {
+    type Init = (DownloadRecord, Option<webkit6::Download>);
+    type Input = DownloadRowInput;
+    type Output = DownloadRowOutput;
+    type CommandOutput = RetryProgress;
+    type ParentWidget = gtk::ListBox;
+
+    view! {
+        gtk::Box {
+            set_orientation: gtk::Orientation::Vertical,
+            set_spacing: 6,
+            set_margin_all: 6,
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 6,
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_hexpand: true,
+                    set_valign: gtk::Align::Center,
+
+                    gtk::Label {
+                        set_xalign: 0.0,
+                        set_ellipsize: gtk::pango::EllipsizeMode::Middle,
+                        #[watch]
+                        set_label: &self.record.file_name(),
+                    },
+
+                    gtk::Label {
+                        add_css_class: "caption",
+                        add_css_class: "dim-label",
+                        set_xalign: 0.0,
+                        set_ellipsize: gtk::pango::EllipsizeMode::End,
+                        #[watch]
+                        set_label: &self.describe(),
+                    },
+                },
+
+                gtk::Button {
+                    set_icon_name: "process-stop-symbolic",
+                    set_tooltip_text: Some("Cancel"),
+                    set_valign: gtk::Align::Center,
+                    add_css_class: "flat",
+                    add_css_class: "circular",
+                    #[watch]
+                    set_visible: self.record.state == DownloadState::InProgress,
+                    connect_clicked => DownloadRowInput::Cancel,
+                },
+
+                gtk::Button {
+                    set_icon_name: "arrow-circular-top-right",
+                    set_tooltip_text: Some("Retry"),
+                    set_valign: gtk::Align::Center,
+                    add_css_class: "flat",
+                    add_css_class: "circular",
+                    #[watch]
+                    set_visible: matches!(self.record.state, DownloadState::Failed | DownloadState::Cancelled),
+                    connect_clicked => DownloadRowInput::Retry,
+                },
+
+                gtk::Button {
+                    set_icon_name: "document-open-symbolic",
+                    set_tooltip_text: Some("Open"),
+                    set_valign: gtk::Align::Center,
+                    add_css_class: "flat",
+                    add_css_class: "circular",
+                    #[watch]
+                    set_visible: self.record.state == DownloadState::Finished,
+                    connect_clicked => DownloadRowInput::Open,
+                },
+
+                gtk::Button {
+                    set_icon_name: "folder-open-symbolic",
+                    set_tooltip_text: Some("Show in folder"),
+                    set_valign: gtk::Align::Center,
+                    add_css_class: "flat",
+                    add_css_class: "circular",
+                    #[watch]
+                    set_visible: self.record.state == DownloadState::Finished,
+                    connect_clicked => DownloadRowInput::ShowInFolder,
+                },
+
+                gtk::Button {
+                    set_icon_name: "cross",
+                    set_tooltip_text: Some("Remove from list"),
+                    set_valign: gtk::Align::Center,
+                    add_css_class: "flat",
+                    add_css_class: "circular",
+                    #[watch]
+                    set_visible: self.record.state != DownloadState::InProgress,
+                    connect_clicked => DownloadRowInput::Remove,
+                },
+            },
+
+            gtk::ProgressBar {
+                #[watch]
+                set_visible: self.record.state == DownloadState::InProgress,
+                #[watch]
+                set_fraction: self.progress,
+            },
+        }
+    }
+
+    fn init_model(init: Self::Init, index: &Self::Index, sender: FactorySender<Self>) -> Self {
+        let (record, download) = init;
+        if let Some(download) = &download {
+            download.connect_received_data(clone!(@strong sender => move |_, _| {
+                sender.input(DownloadRowInput::Progress);
+            }));
+            download.connect_created_destination(clone!(@strong sender => move |_, destination| {
+                sender.input(DownloadRowInput::DestinationCreated(destination.to_string()));
+            }));
+            download.connect_failed(clone!(@strong sender => move |_, error| {
+                eprintln!("Download failed: {error}");
+                sender.input(DownloadRowInput::Failed {
+                    cancelled: error.matches(webkit6::DownloadError::CancelledByUser),
+                    reason: error.message().to_string(),
+                });
+            }));
+            download.connect_finished(clone!(@strong sender => move |_| {
+                sender.input(DownloadRowInput::Finished);
+            }));
+        }
+        Self {
+            id: index.clone(),
+            record,
+            download,
+            received: 0,
+            progress: 0.0,
+            speed: None,
+            speed_sample: (Instant::now(), 0),
+            pending_destination: None,
+            retry_cancelled: None,
+        }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: FactorySender<Self>) {
+        match message {
+            DownloadRowInput::Progress => {
+                let Some(download) = &self.download else {
+                    return;
+                };
+                self.received = download.received_data_length();
+                self.progress = download.estimated_progress();
+                if let Some(response) = download.response() {
+                    self.record.total_size = response.content_length();
+                }
+                let (sample_time, sample_received) = self.speed_sample;
+                let elapsed = sample_time.elapsed().as_secs_f64();
+                if elapsed >= 1.0 {
+                    self.speed = Some(
+                        (self.received.saturating_sub(sample_received) as f64 / elapsed) as u64,
+                    );
+                    self.speed_sample = (Instant::now(), self.received);
+                }
+            }
+            DownloadRowInput::DestinationCreated(destination) => {
+                // WebKit deletes the file when the download fails, but a second name for it keeps what was received
+                // for the first retry to continue from
+                let partial = format!("{destination}.part");
+                _ = fs::remove_file(&partial);
+                match fs::hard_link(&destination, &partial) {
+                    Ok(_) => self.record.partial = Some(partial),
+                    Err(error) => eprintln!("Could not keep download for resuming: {error}"),
+                }
+                self.record.validator = self
+                    .download
+                    .as_ref()
+                    .and_then(|download| download.response())
+                    .and_then(|response| validator_of(&response));
+                self.record.destination = Some(destination);
+                _ = sender.output(DownloadRowOutput::Changed);
+            }
+            DownloadRowInput::Failed { cancelled, reason } => {
+                if let Some(download) = &self.download {
+                    if cancelled {
+                        self.record.discard_partial();
+                    } else if let Some(partial) = &self.record.partial {
+                        // Only keep what WebKit had received when it failed
+                        let received = download.received_data_length();
+                        if let Err(error) = fs::OpenOptions::new()
+                            .write(true)
+                            .open(partial)
+                            .and_then(|file| {
+                                let kept = file.metadata()?.len().min(received);
+                                file.set_len(kept)
+                            })
+                        {
+                            eprintln!("Could not keep download for resuming: {error}");
+                            self.record.discard_partial();
+                        }
+                    }
+                }
+                self.record.state = if cancelled {
+                    DownloadState::Cancelled
+                } else {
+                    DownloadState::Failed
+                };
+                self.record.error = (!cancelled).then_some(reason);
+                _ = sender.output(DownloadRowOutput::Changed);
+            }
+            DownloadRowInput::Finished => {
+                // WebKit also emits "finished" after "failed"
+                if self.record.state == DownloadState::InProgress {
+                    self.record.state = DownloadState::Finished;
+                    self.record.discard_partial();
+                    self.progress = 1.0;
+                    if let Some(pending_destination) = self.pending_destination.take() {
+                        sender.input(DownloadRowInput::MoveTo(pending_destination));
+                    }
+                    let mut urls_of_download = vec![self.record.url.clone()];
+                    if let Some(response_uri) = self
+                        .download
+                        .as_ref()
+                        .and_then(|download| download.response())
+                        .and_then(|response| response.uri())
+                    {
+                        urls_of_download.push(response_uri.to_string());
+                    }
+                    if take_open_when_finished(&urls_of_download) {
+                        if let Some(destination) = &self.record.destination {
+                            if let Err(error) = open::that(destination) {
+                                eprintln!("Could not open download: {error}");
+                            }
+                        }
+                    }
+                    _ = sender.output(DownloadRowOutput::Changed);
+                }
+            }
+            DownloadRowInput::MoveTo(new_destination) => match self.record.state {
+                // WebKit can't change where a download is written once it has started
+                DownloadState::InProgress => self.pending_destination = Some(new_destination),
+                DownloadState::Finished => {
+                    let Some(destination) = &self.record.destination else {
+                        return;
+                    };
+                    match gio::File::for_path(destination).move_(
+                        &gio::File::for_path(&new_destination),
+                        gio::FileCopyFlags::OVERWRITE,
+                        gio::Cancellable::NONE,
+                        None,
+                    ) {
+                        Ok(_) => {
+                            self.record.destination = Some(new_destination);
+                            _ = sender.output(DownloadRowOutput::Changed);
+                        }
+                        Err(error) => eprintln!("Could not move download: {error}"),
+                    }
+                }
+                DownloadState::Failed | DownloadState::Cancelled => {}
+            },
+            DownloadRowInput::Cancel => {
+                if let Some(retry_cancelled) = &self.retry_cancelled {
+                    retry_cancelled.store(true, Ordering::Relaxed);
+                } else if let Some(download) = &self.download {
+                    download.cancel();
+                }
+            }
+            DownloadRowInput::Retry => self.retry(&sender),
+            DownloadRowInput::Open => {
+                if let Some(destination) = &self.record.destination {
+                    gtk::FileLauncher::new(Some(&gio::File::for_path(destination))).launch(
+                        gtk::Window::NONE,
+                        gio::Cancellable::NONE,
+                        |result| {
+                            if let Err(error) = result {
+                                eprintln!("Could not open download: {error}");
+                            }
+                        },
+                    );
+                }
+            }
+            DownloadRowInput::ShowInFolder => {
+                if let Some(destination) = &self.record.destination {
+                    gtk::FileLauncher::new(Some(&gio::File::for_path(destination)))
+                        .open_containing_folder(
+                            gtk::Window::NONE,
+                            gio::Cancellable::NONE,
+                            |result| {
+                                if let Err(error) = result {
+                                    eprintln!("Could not show download in folder: {error}");
+                                }
+                            },
+                        );
+                }
+            }
+            DownloadRowInput::Remove => {
+                self.record.discard_partial();
+                _ = sender.output(DownloadRowOutput::Remove(self.id.clone()));
+            }
+        }
+    }
+
+    fn update_cmd(&mut self, message: Self::CommandOutput, sender: FactorySender<Self>) {
+        match message {
+            RetryProgress::Started {
+                resumed,
+                received,
+                total_size,
+                validator,
+            } => {
+                self.received = received;
+                self.record.total_size = total_size;
+                self.record.validator = validator;
+                self.speed_sample = (Instant::now(), received);
+                _ = sender.output(DownloadRowOutput::Changed);
+            }
+            RetryProgress::Received(received) => {
+                self.received = received;
+                if self.record.total_size > 0 {
+                    self.progress = received as f64 / self.record.total_size as f64;
+                }
+                let (sample_time, sample_received) = self.speed_sample;
+                let elapsed = sample_time.elapsed().as_secs_f64();
+                if elapsed >= 1.0 {
+                    self.speed =
+                        Some((received.saturating_sub(sample_received) as f64 / elapsed) as u64);
+                    self.speed_sample = (Instant::now(), received);
+                }
+            }
+            RetryProgress::Finished => {
+                self.retry_cancelled = None;
+                let (Some(partial), Some(destination)) =
+                    (self.record.partial.take(), &self.record.destination)
+                else {
+                    return;
+                };
+                match fs::rename(&partial, destination) {
+                    Ok(_) => sender.input(DownloadRowInput::Finished),
+                    Err(error) => {
+                        self.record.partial = Some(partial);
+                        sender.input(DownloadRowInput::Failed {
+                            cancelled: false,
+                            reason: format!("Could not save file: {error}"),
+                        });
+                    }
+                }
+            }
+            RetryProgress::Failed { cancelled, reason } => {
+                self.retry_cancelled = None;
+                if cancelled {
+                    self.record.discard_partial();
+                }
+                sender.input(DownloadRowInput::Failed { cancelled, reason });
+            }
+        }
+    }
+}
+
+impl DownloadRow {
+    /// WebKit throws away what it received when a download fails and can't resume one, so retries are downloaded
+    /// by Spidey into a partial file that later retries can pick up from. The first retry continues from what WebKit
+    /// had received, which is kept under a second name while WebKit downloads.
+    /// They send the cookies and user agent WebKit would and go through the same proxy.
+    fn retry(&mut self, sender: &FactorySender<Self>) {
+        let destination = match &self.record.destination {
+            Some(destination) => PathBuf::from(destination),
+            None => {
+                let file_name = self.record.file_name();
+                let folder = download_folder_for(&file_name, None);
+                if let Err(error) = fs::create_dir_all(&folder) {
+                    eprintln!("Could not create download folder: {error}");
+                }
+                available_path(&folder, &file_name)
+            }
+        };
+        let partial = self
+            .record
+            .partial
+            .clone()
+            .unwrap_or(format!("{}.part", destination.to_string_lossy()));
+        self.record.destination = Some(destination.to_string_lossy().to_string());
+        self.record.partial = Some(partial.clone());
+        self.record.state = DownloadState::InProgress;
+        self.record.error = None;
+        self.download = None;
+        self.progress = 0.0;
+        self.speed = None;
+        let retry_cancelled = Arc::new(AtomicBool::new(false));
+        self.retry_cancelled = Some(retry_cancelled.clone());
+        let url = self.record.url.clone();
+        // The Web Window the download came from showed the user agent of its page
+        let user_agent = user_agent_for(
+            self.record
+                .source_page
+                .as_deref()
+                .and_then(host_of)
+                .or_else(|| host_of(&url))
+                .as_deref(),
+        )
+        .or_else(|| {
+            webkit6::Settings::new()
+                .user_agent()
+                .map(|user_agent| user_agent.to_string())
+        });
+        let validator = self.record.validator.clone();
+        let Some(cookie_manager) = webkit6::NetworkSession::default().cookie_manager() else {
+            return;
+        };
+        cookie_manager.cookies(
+            &self.record.url,
+            gio::Cancellable::NONE,
+            clone!(@strong sender => move |cookies_result| {
+                let cookie_header = cookies_result
+                    .ok()
+                    .filter(|cookies| !cookies.is_empty())
+                    .map(|cookies| soup::cookies_to_cookie_header(&cookies).to_string());
+                let retry_request = RetryRequest {
+                    url,
+                    user_agent,
+                    cookie_header,
+                    validator,
+                };
+                sender.spawn_command(move |out| {
+                    let result = fetch_resumable(&retry_request, Path::new(&partial), &retry_cancelled, &out, true);
+                    _ = out.send(match result {
+                        Ok(_) => RetryProgress::Finished,
+                        Err(reason) => RetryProgress::Failed {
+                            cancelled: retry_cancelled.load(Ordering::Relaxed),
+                            reason,
+                        },
+                    });
+                });
+            }),
+        );
+        _ = sender.output(DownloadRowOutput::Changed);
+    }
+    pub fn is_download(&self, download: &webkit6::Download) -> bool {
+        self.download.as_ref() == Some(download)
+    }
+    pub fn is_in_progress(&self) -> bool {
+        self.record.state == DownloadState::InProgress
+    }
+    fn describe(&self) -> String {
+        let mut description = vec![];
+        match self.record.state {
+            DownloadState::InProgress => {
+                description.push(if self.record.total_size > 0 {
+                    format!(
+                        "{} of {}",
+                        glib::format_size(self.received),
+                        glib::format_size(self.record.total_size)
+                    )
+                } else {
+                    glib::format_size(self.received).to_string()
+                });
+                if let Some(speed) = self.speed {
+                    description.push(format!("{}/s", glib::format_size(speed)));
+                }
+            }
+            DownloadState::Finished => {
+                description.push(String::from("Finished"));
+                if self.record.total_size > 0 {
+                    description.push(glib::format_size(self.record.total_size).to_string());
+                }
+            }
+            DownloadState::Failed => {
+                description.push(match &self.record.error {
+                    Some(error) => format!("Failed: {error}"),
+                    None => String::from("Failed"),
+                });
+                let partial_size = self
+                    .record
+                    .partial
+                    .as_ref()
+                    .and_then(|partial| fs::metadata(partial).ok())
+                    .map(|metadata| metadata.len())
+                    .unwrap_or(0);
+                if partial_size > 0 {
+                    description.push(format!("{} kept", glib::format_size(partial_size)));
+                }
+            }
+            DownloadState::Cancelled => description.push(String::from("Cancelled")),
+        }
+        if let Some(source_host) = self.record.source_page.as_deref().and_then(host_of) {
+            description.push(format!("from {source_host}"));
+        }
+        description.join(" · ")
+    }
+}
+
+/// The strong ETag or Last-Modified date of `response`, which tells a server in If-Range which version of a file
+/// partial data is from
+fn validator_of(response: &webkit6::URIResponse) -> Option<String> {
+    let headers = response.http_headers()?;
+    headers
+        .one("ETag")
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| headers.one("Last-Modified"))
+        .map(|validator| validator.to_string())
+}
+
+/// What a retry sends so the server treats it like the request WebKit made
+struct RetryRequest {
+    url: String,
+    user_agent: Option<String>,
+    cookie_header: Option<String>,
+    validator: Option<String>,
+}
+
+/// Download the file `retry_request` asks for into `partial`, continuing from what is already there if the server
+/// accepts a Range request for the same version of the file and starting over if it doesn't
+fn fetch_resumable(
+    retry_request: &RetryRequest,
+    partial: &Path,
+    cancelled: &AtomicBool,
+    out: &relm4::Sender<RetryProgress>,
+    may_resume: bool,
+) -> Result<(), String> {
+    // Without a validator there is no way to tell whether the partial data is from the same file
+    let already_received = match (may_resume, &retry_request.validator) {
+        (true, Some(_)) => fs::metadata(partial)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0),
+        _ => 0,
+    };
+    let mut request = proxied_client(&retry_request.url)?.get(&retry_request.url);
+    if let Some(user_agent) = &retry_request.user_agent {
+        request = request.header(reqwest::header::USER_AGENT, user_agent);
+    }
+    if let Some(cookie_header) = &retry_request.cookie_header {
+        request = request.header(reqwest::header::COOKIE, cookie_header);
+    }
+    if let (true, Some(validator)) = (already_received > 0, &retry_request.validator) {
+        // The server sends the whole file instead if it changed since the partial data was received
+        request = request
+            .header(reqwest::header::RANGE, format!("bytes={already_received}-"))
+            .header(reqwest::header::IF_RANGE, validator);
+    }
+    let response = request.send().map_err(|error| error.to_string())?;
+    if response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        return fetch_resumable(retry_request, partial, cancelled, out, false);
+    }
+    let mut response = response
+        .error_for_status()
+        .map_err(|error| error.to_string())?;
+    let resumed = response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+    // Weak ETags can't be used with If-Range
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string())
+    };
+    let validator = header(reqwest::header::ETAG)
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header(reqwest::header::LAST_MODIFIED));
+    let mut received = if resumed { already_received } else { 0 };
+    let total_size = response
+        .content_length()
+        .map(|content_length| content_length + received)
+        .unwrap_or(0);
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(partial)
+        .map_err(|error| error.to_string())?;
+    _ = out.send(RetryProgress::Started {
+        resumed,
+        received,
+        total_size,
+        validator,
+    });
+    let mut buffer = vec![0; 64 * 1024];
+    let mut last_reported = Instant::now();
+    loop {
+        if cancelled.load(Ordering::Relaxed) {
+            return Err(String::from("Cancelled"));
+        }
+        let read = response
+            .read(&mut buffer)
+            .map_err(|error| error.to_string())?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buffer[..read])
+            .map_err(|error| error.to_string())?;
+        received += read as u64;
+        if last_reported.elapsed() >= Duration::from_millis(200) {
+            _ = out.send(RetryProgress::Received(received));
+            last_reported = Instant::now();
+        }
+    }
+    _ = out.send(RetryProgress::Received(received));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temporary_folder(name: &str) -> PathBuf {
+        let folder =
+            std::env::temp_dir().join(format!("spidey-test-{name}-{}", std::process::id()));
+        _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    #[test]
+    fn available_path_numbers_name_collisions() {
+        let folder = temporary_folder("collisions");
+        assert_eq!(
+            available_path(&folder, "paper.pdf"),
+            folder.join("paper.pdf")
+        );
+        fs::write(folder.join("paper.pdf"), "").unwrap();
+        assert_eq!(
+            available_path(&folder, "paper.pdf"),
+            folder.join("paper (1).pdf")
+        );
+        fs::write(folder.join("paper (1).pdf"), "").unwrap();
+        assert_eq!(
+            available_path(&folder, "paper.pdf"),
+            folder.join("paper (2).pdf")
+        );
+        fs::write(folder.join("README"), "").unwrap();
+        assert_eq!(available_path(&folder, "README"), folder.join("README (1)"));
+        fs::write(folder.join(".hidden"), "").unwrap();
+        assert_eq!(
+            available_path(&folder, ".hidden"),
+            folder.join(".hidden (1)")
+        );
+        _ = fs::remove_dir_all(&folder);
+    }
+
+    #[test]
+    fn available_path_keeps_multi_dot_extensions() {
+        let folder = temporary_folder("extensions");
+        fs::write(folder.join("source.tar.gz"), "").unwrap();
+        assert_eq!(
+            available_path(&folder, "source.tar.gz"),
+            folder.join("source (1).tar.gz")
+        );
+        fs::write(folder.join("report.final.pdf"), "").unwrap();
+        assert_eq!(
+            available_path(&folder, "report.final.pdf"),
+            folder.join("report.final (1).pdf")
+        );
+        _ = fs::remove_dir_all(&folder);
+    }
+
+    #[test]
+    fn available_path_ignores_folders_in_the_file_name() {
+        let folder = temporary_folder("traversal");
+        assert_eq!(
+            available_path(&folder, "../../etc/passwd"),
+            folder.join("passwd")
+        );
+        assert_eq!(available_path(&folder, ".."), folder.join("Download"));
+        _ = fs::remove_dir_all(&folder);
+    }
+
+    #[test]
+    fn download_rules_are_normalized() {
+        assert_eq!(normalize_download_rule("PDF"), Some(String::from("pdf")));
+        assert_eq!(
+            normalize_download_rule(" *.pdf "),
+            Some(String::from("pdf"))
+        );
+        assert_eq!(
+            normalize_download_rule(".tar.gz"),
+            Some(String::from("tar.gz"))
+        );
+        assert_eq!(
+            normalize_download_rule("Image/*"),
+            Some(String::from("image/*"))
+        );
+        assert_eq!(
+            normalize_download_rule("application/pdf"),
+            Some(String::from("application/pdf"))
+        );
+    }
+
+    #[test]
+    fn empty_and_catch_all_download_rules_are_rejected() {
+        assert_eq!(normalize_download_rule(""), None);
+        assert_eq!(normalize_download_rule("   "), None);
+        assert_eq!(normalize_download_rule("*.*"), None);
+        assert_eq!(normalize_download_rule("*"), None);
+        assert_eq!(normalize_download_rule("*/*"), None);
+        assert_eq!(normalize_download_rule("pdf files"), None);
+    }
+
+    #[test]
+    fn download_rules_are_tried_most_specific_first() {
+        assert_eq!(
+            download_rule_candidates("Paper.PDF", Some("application/pdf; charset=binary")),
+            vec!["application/pdf", "application/*", "pdf"]
+        );
+        assert_eq!(
+            download_rule_candidates("source.tar.gz", None),
+            vec!["tar.gz", "gz"]
+        );
+        assert_eq!(
+            download_rule_candidates(".bashrc", Some("unknown")),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            download_rule_candidates("README", Some("text/plain")),
+            vec!["text/plain", "text/*"]
+        );
+    }
+}
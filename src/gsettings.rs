@@ -0,0 +1,13 @@
+use relm4::gtk::gio;
+
+use crate::config::PROFILE;
+
+/// Get the GSettings for the current profile
+pub fn gsettings() -> gio::Settings {
+    let gschema_id = if PROFILE == "Devel" {
+        "com.github.kdwk.Spidey.Devel"
+    } else {
+        "com.github.kdwk.Spidey"
+    };
+    gio::Settings::new(gschema_id)
+}
@@ -0,0 +1,150 @@
+use relm4::{adw::prelude::*, prelude::*};
+use webkit6::prelude::*;
+
+use crate::passwords::{load_credentials, merge_credentials, update_credentials, Credential};
+use crate::sitesettings::origin_of;
+
+fn origin_of_request(request: &webkit6::AuthenticationRequest) -> Option<String> {
+    let security_origin = request.security_origin()?;
+    let protocol = security_origin.protocol()?;
+    let host = security_origin.host()?;
+    match security_origin.port() {
+        0 => origin_of(&format!("{protocol}://{host}")),
+        port => origin_of(&format!("{protocol}://{host}:{port}")),
+    }
+}
+
+/// Answer an HTTP authentication request with the saved credential for its origin and realm,
+/// or ask the user for one if there is none or it was just rejected
+pub fn handle_authentication(
+    request: webkit6::AuthenticationRequest,
+    parent: &impl IsA<gtk::Widget>,
+) {
+    let parent = parent.clone().upcast::<gtk::Widget>();
+    relm4::spawn_local(async move {
+        let origin = origin_of_request(&request);
+        let realm = request
+            .realm()
+            .map(|realm| realm.to_string())
+            .unwrap_or_default();
+        let saved_credential = match &origin {
+            Some(origin) => load_credentials()
+                .await
+                .unwrap_or_else(|error| {
+                    eprintln!("Could not load saved passwords: {error}");
+                    vec![]
+                })
+                .into_iter()
+                .find(|credential| {
+                    credential.origin == *origin
+                        && credential.realm.as_deref() == Some(realm.as_str())
+                }),
+            None => None,
+        };
+        match saved_credential {
+            Some(saved_credential) if !request.is_retry() => {
+                request.authenticate(Some(&webkit6::Credential::new(
+                    &saved_credential.username,
+                    &saved_credential.password,
+                    webkit6::CredentialPersistence::ForSession,
+                )));
+            }
+            saved_credential => present_authentication_dialog(
+                request,
+                origin,
+                realm,
+                saved_credential.map(|saved_credential| saved_credential.username),
+                &parent,
+            ),
+        }
+    });
+}
+
+fn present_authentication_dialog(
+    request: webkit6::AuthenticationRequest,
+    origin: Option<String>,
+    realm: String,
+    username: Option<String>,
+    parent: &gtk::Widget,
+) {
+    let host = request
+        .host()
+        .map(|host| host.to_string())
+        .unwrap_or_default();
+    let mut body = if realm.is_empty() {
+        format!("{host} needs a username and password")
+    } else {
+        format!("{host} needs a username and password for “{realm}”")
+    };
+    if request.is_retry() {
+        body.push_str(". The username or password you entered last time was not accepted.");
+    }
+    if request.scheme() == webkit6::AuthenticationScheme::HttpBasic
+        && !origin
+            .as_deref()
+            .is_some_and(|origin| origin.starts_with("https://"))
+    {
+        body.push_str("\n\nThis site is not secure, so your password will be sent unencrypted.");
+    }
+
+    let username_row = adw::EntryRow::builder().title("Username").build();
+    username_row.set_text(username.as_deref().unwrap_or(""));
+    let password_row = adw::PasswordEntryRow::builder().title("Password").build();
+    let remember_row = adw::SwitchRow::builder()
+        .title("Remember")
+        .subtitle("Log in automatically next time")
+        .sensitive(origin.is_some())
+        .build();
+    let credential_list = gtk::ListBox::builder()
+        .css_classes(["boxed-list"])
+        .selection_mode(gtk::SelectionMode::None)
+        .build();
+    credential_list.append(&username_row);
+    credential_list.append(&password_row);
+    credential_list.append(&remember_row);
+
+    let authentication_dialog = adw::AlertDialog::builder()
+        .heading(if request.is_for_proxy() {
+            "Log In to Proxy"
+        } else {
+            "Log In"
+        })
+        .body(body)
+        .extra_child(&credential_list)
+        .close_response("cancel")
+        .default_response("log-in")
+        .build();
+    authentication_dialog.add_responses(&[("cancel", "Cancel"), ("log-in", "Log In")]);
+    authentication_dialog.set_response_appearance("log-in", adw::ResponseAppearance::Suggested);
+    authentication_dialog.connect_response(None, move |_, response| {
+        if response != "log-in" {
+            request.cancel();
+            return;
+        }
+        let username = username_row.text().to_string();
+        let password = password_row.text().to_string();
+        request.authenticate(Some(&webkit6::Credential::new(
+            &username,
+            &password,
+            webkit6::CredentialPersistence::ForSession,
+        )));
+        if let (true, Some(origin)) = (remember_row.is_active(), origin.clone()) {
+            let credential = Credential {
+                origin,
+                username,
+                password,
+                realm: Some(realm.clone()),
+            };
+            relm4::spawn_local(async move {
+                if let Err(error) = update_credentials(|credentials| {
+                    merge_credentials(credentials, vec![credential])
+                })
+                .await
+                {
+                    eprintln!("Could not remember password: {error}");
+                }
+            });
+        }
+    });
+    authentication_dialog.present(Some(parent));
+}
@@ -1,9 +1,32 @@
 #[rustfmt::skip]
 mod config;
 mod app;
+mod certificatedialog;
+mod cleardata;
+mod cookiemanager;
+mod downloads;
+mod gsettings;
+mod httpauth;
+mod mimepolicy;
+mod pagewatch;
+mod pagewatchdialog;
+mod passwordmanager;
+mod passwords;
+mod pdfviewer;
+mod permissions;
+mod preferences;
+mod printing;
+mod proxy;
 mod recipe;
+mod savedpages;
+mod screenshoteditor;
+mod screenshots;
+mod security;
 mod setup;
+mod sitesettings;
 mod smallwebwindow;
+mod trackers;
+mod useragent;
 mod webwindow;
 mod webwindowcontrolbar;
 mod whoops;
@@ -14,7 +37,7 @@ use relm4::{
     main_application, RelmApp,
 };
 
-use app::App;
+use app::{App, AppInput, APP_BROKER};
 use setup::setup;
 
 relm4::new_action_group!(AppActionGroup, "app");
@@ -44,7 +67,17 @@ fn main() {
 
     app.set_accelerators_for_action::<QuitAction>(&["<primary>q"]);
 
-    let app = RelmApp::from_app(app);
+    // Open pages given on the command line or from the file manager, e.g. saved .mhtml pages
+    app.set_flags(app.flags() | gio::ApplicationFlags::HANDLES_OPEN);
+    app.connect_open(|app, files, _| {
+        // Opening files skips "activate", which is where the main window is created
+        app.activate();
+        APP_BROKER.send(AppInput::OpenFiles(files.to_vec()));
+    });
+
+    let app = RelmApp::from_app(app)
+        .with_broker(&APP_BROKER)
+        .with_args(std::env::args().collect());
     setup(&app);
     relm4_icons::initialize_icons();
     app.run::<App>(());
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+
+use webkit6::prelude::*;
+
+use crate::downloads::open_when_finished;
+use crate::pdfviewer::{pdf_viewer_available, view_pdf_response};
+use crate::sitesettings::{set_site_value, site_value, site_values};
+
+/// What to do with a page whose type is `mime_type`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MimeAction {
+    Display,
+    Download,
+    Open,
+}
+
+impl MimeAction {
+    pub const ALL: [MimeAction; 3] = [MimeAction::Display, MimeAction::Download, MimeAction::Open];
+    pub fn id(&self) -> &'static str {
+        match self {
+            MimeAction::Display => "display",
+            MimeAction::Download => "download",
+            MimeAction::Open => "open",
+        }
+    }
+    pub fn from_id(id: &str) -> Option<Self> {
+        MimeAction::ALL
+            .into_iter()
+            .find(|mime_action| mime_action.id() == id)
+    }
+    pub fn title(&self) -> &'static str {
+        match self {
+            MimeAction::Display => "Show in Spidey",
+            MimeAction::Download => "Download",
+            MimeAction::Open => "Open in Default App",
+        }
+    }
+}
+
+/// Types shown in the preferences even before the user has chosen anything for them
+pub const COMMON_MIME_TYPES: [(&str, &str); 6] = [
+    ("application/pdf", "PDF Documents"),
+    ("image/*", "Images"),
+    ("video/*", "Videos"),
+    ("audio/*", "Audio"),
+    ("text/plain", "Plain Text"),
+    ("application/zip", "ZIP Archives"),
+];
+
+/// Labels for a combo row, where the first one means "let Spidey decide"
+pub fn mime_action_labels() -> Vec<&'static str> {
+    let mut labels = vec!["Default"];
+    labels.extend(
+        MimeAction::ALL
+            .iter()
+            .map(|mime_action| mime_action.title()),
+    );
+    labels
+}
+
+pub fn mime_action_index(mime_action: Option<MimeAction>) -> u32 {
+    mime_action
+        .and_then(|mime_action| {
+            MimeAction::ALL
+                .iter()
+                .position(|action| *action == mime_action)
+        })
+        .map(|index| index as u32 + 1)
+        .unwrap_or(0)
+}
+
+pub fn mime_action_from_index(index: u32) -> Option<MimeAction> {
+    MimeAction::ALL
+        .get((index as usize).checked_sub(1)?)
+        .copied()
+}
+
+/// The action chosen for exactly `mime_type`, not counting "image/*" style choices
+pub fn chosen_mime_action(mime_type: &str) -> Option<MimeAction> {
+    MimeAction::from_id(&site_value("mime-policies", mime_type)?)
+}
+
+/// Every type the user has made a choice for
+pub fn chosen_mime_types() -> Vec<String> {
+    let mut mime_types = site_values("mime-policies")
+        .into_keys()
+        .collect::<Vec<String>>();
+    mime_types.sort();
+    mime_types
+}
+
+pub fn set_mime_action(mime_type: &str, mime_action: Option<MimeAction>) {
+    set_site_value(
+        "mime-policies",
+        &mime_type.to_lowercase(),
+        mime_action.map(|mime_action| mime_action.id()),
+    );
+}
+
+/// `mime_type` without parameters like "; charset=utf-8", in lowercase
+fn mime_essence(mime_type: &str) -> String {
+    mime_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+}
+
+/// The choice for `mime_type`, falling back to the choice for its kind, e.g. "image/*"
+pub fn mime_action_for(mime_type: &str) -> Option<MimeAction> {
+    mime_action_among(&site_values("mime-policies"), mime_type)
+}
+
+fn mime_action_among(
+    mime_policies: &HashMap<String, String>,
+    mime_type: &str,
+) -> Option<MimeAction> {
+    let mime_type = mime_essence(mime_type);
+    let (kind, _) = mime_type.split_once('/')?;
+    mime_policies
+        .get(&mime_type)
+        .or_else(|| mime_policies.get(&format!("{kind}/*")))
+        .and_then(|id| MimeAction::from_id(id))
+}
+
+/// Apply the MIME type policy to a response for a page. Returns whether the decision was handled.
+pub fn decide_response_policy(
+    web_view: &webkit6::WebView,
+    decision: &webkit6::PolicyDecision,
+) -> bool {
+    let Some(response_decision) = decision.downcast_ref::<webkit6::ResponsePolicyDecision>() else {
+        return false;
+    };
+    // Images and other resources inside a page always load normally
+    if !response_decision.is_main_frame_main_resource() {
+        return false;
+    }
+    let Some(response) = response_decision.response() else {
+        return false;
+    };
+    let mime_type = response
+        .mime_type()
+        .map(|mime_type| mime_essence(&mime_type))
+        .unwrap_or_default();
+    let can_display = web_view.can_show_mime_type(&mime_type);
+    let mime_action = mime_action_for(&mime_type);
+    if mime_type == "application/pdf"
+        && matches!(mime_action, Some(MimeAction::Display) | None)
+        && pdf_viewer_available()
+    {
+        view_pdf_response(response_decision);
+        return true;
+    }
+    match mime_action {
+        // WebKit can't show what it doesn't understand, so downloading is the best it can do
+        Some(MimeAction::Display) | None if can_display => decision.use_(),
+        Some(MimeAction::Display) | Some(MimeAction::Download) | None => decision.download(),
+        Some(MimeAction::Open) => {
+            if let Some(uri) = response.uri() {
+                open_when_finished(&uri);
+            }
+            decision.download();
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mime_policies(policies: &[(&str, MimeAction)]) -> HashMap<String, String> {
+        policies
+            .iter()
+            .map(|(mime_type, mime_action)| (mime_type.to_string(), mime_action.id().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn exact_types_win_over_wildcards() {
+        let mime_policies = mime_policies(&[
+            ("image/*", MimeAction::Download),
+            ("image/png", MimeAction::Display),
+        ]);
+        assert_eq!(
+            mime_action_among(&mime_policies, "image/png"),
+            Some(MimeAction::Display)
+        );
+        assert_eq!(
+            mime_action_among(&mime_policies, "image/jpeg"),
+            Some(MimeAction::Download)
+        );
+        assert_eq!(mime_action_among(&mime_policies, "video/mp4"), None);
+    }
+
+    #[test]
+    fn parameters_and_case_are_ignored() {
+        let mime_policies = mime_policies(&[
+            ("text/plain", MimeAction::Open),
+            ("application/*", MimeAction::Download),
+        ]);
+        assert_eq!(
+            mime_action_among(&mime_policies, "text/plain; charset=utf-8"),
+            Some(MimeAction::Open)
+        );
+        assert_eq!(
+            mime_action_among(&mime_policies, "Text/Plain;charset=\"UTF-8\""),
+            Some(MimeAction::Open)
+        );
+        assert_eq!(
+            mime_action_among(&mime_policies, "application/json; charset=utf-8"),
+            Some(MimeAction::Download)
+        );
+    }
+
+    #[test]
+    fn types_without_a_kind_have_no_action() {
+        let mime_policies = mime_policies(&[("*/*", MimeAction::Download)]);
+        assert_eq!(mime_action_among(&mime_policies, ""), None);
+        assert_eq!(mime_action_among(&mime_policies, "unknown"), None);
+    }
+
+    #[test]
+    fn unknown_actions_are_ignored() {
+        let mut mime_policies = mime_policies(&[]);
+        mime_policies.insert(String::from("application/pdf"), String::from("print"));
+        assert_eq!(mime_action_among(&mime_policies, "application/pdf"), None);
+    }
+}
@@ -0,0 +1,689 @@
+use std::{
+    cell::RefCell,
+    fs,
+    path::PathBuf,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+};
+
+use documents::prelude::*;
+use relm4::gtk::{gdk, gio, glib, graphene, prelude::*};
+use serde::{Deserialize, Serialize};
+use webkit6::prelude::*;
+
+use crate::app::{AppInput, APP_BROKER};
+use crate::cookiemanager::apply_cookie_exceptions;
+use crate::sitesettings::{host_of, javascript_enabled_for, origin_of};
+use crate::useragent::user_agent_for;
+
+const PAGE_WATCH_WORLD: &str = "spidey-page-watch";
+
+/// Watched pages are laid out at this size whatever size their Web Windows are, so that snapshots
+/// from different checks line up
+const PAGE_WATCH_WIDTH: i32 = 1280;
+const PAGE_WATCH_HEIGHT: i32 = 800;
+
+/// The watched pages with checks scheduled, and the generation of their schedule. A check that finds
+/// a different generation for its page was scheduled before the watch was changed or stopped.
+static SCHEDULED_PAGE_WATCHES: Mutex<Vec<(String, u32)>> = Mutex::new(vec![]);
+static NEXT_GENERATION: AtomicU32 = AtomicU32::new(0);
+
+/// How a watched page is compared with the last time it was checked
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum WatchMode {
+    Appearance,
+    Text,
+}
+
+impl WatchMode {
+    pub const ALL: [WatchMode; 2] = [WatchMode::Appearance, WatchMode::Text];
+    pub fn label(&self) -> &'static str {
+        match self {
+            WatchMode::Appearance => "Appearance",
+            WatchMode::Text => "Text",
+        }
+    }
+}
+
+/// A page that is loaded every so often to see whether it has changed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PageWatch {
+    pub url: String,
+    pub interval_minutes: u32,
+    pub mode: WatchMode,
+    /// How much of the page, in percent, has to change before the user is told about it
+    pub threshold: f64,
+    /// CSS selectors for parts of the page that change all the time, e.g. clocks or adverts
+    pub ignored: Vec<String>,
+}
+
+impl PageWatch {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            interval_minutes: 60,
+            mode: WatchMode::Appearance,
+            threshold: 1.0,
+            ignored: vec![],
+        }
+    }
+}
+
+/// What changed on a watched page
+#[derive(Debug, Clone)]
+pub enum PageChange {
+    /// The page as it is now, with the parts that changed highlighted
+    Appearance {
+        percent: f64,
+        highlighted: gdk::Texture,
+    },
+    Text {
+        percent: f64,
+        lines: Vec<DiffLine>,
+    },
+}
+
+impl PageChange {
+    pub fn percent(&self) -> f64 {
+        match self {
+            PageChange::Appearance { percent, .. } | PageChange::Text { percent, .. } => *percent,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffLine {
+    Same(String),
+    Added(String),
+    Removed(String),
+}
+
+fn with_page_watches_json(closure: impl FnOnce(String)) {
+    let mut closure = Some(closure);
+    with(
+        &[Document::at(
+            Project(Data(&[]).with_id("com", "github.kdwk", "Spidey")),
+            "page-watches.json",
+            Create::No,
+        )],
+        |d| {
+            if let Some(closure) = closure.take() {
+                closure(d["page-watches.json"].path());
+            }
+            Ok(())
+        },
+    );
+}
+
+fn page_watches() -> Vec<PageWatch> {
+    let mut page_watches: Vec<PageWatch> = vec![];
+    with_page_watches_json(|path| {
+        if let Ok(json) = fs::read_to_string(path) {
+            page_watches = serde_json::from_str(&json).unwrap_or_default();
+        }
+    });
+    page_watches
+}
+
+pub fn page_watch_for(url: &str) -> Option<PageWatch> {
+    page_watches()
+        .into_iter()
+        .find(|page_watch| page_watch.url == url)
+}
+
+/// Start, change or stop (with None) watching the page at `url`
+pub fn set_page_watch(url: &str, page_watch: Option<PageWatch>) {
+    let mut page_watches = page_watches();
+    page_watches.retain(|existing_page_watch| existing_page_watch.url != url);
+    match &page_watch {
+        Some(page_watch) => page_watches.push(page_watch.clone()),
+        None => {
+            for extension in ["png", "txt"] {
+                if let Some(path) = last_check_path(url, extension) {
+                    _ = fs::remove_file(path);
+                }
+            }
+        }
+    }
+    with_page_watches_json(|path| {
+        let result = serde_json::to_string(&page_watches)
+            .map_err(|error| error.to_string())
+            .and_then(|json| fs::write(path, json).map_err(|error| error.to_string()));
+        if let Err(error) = result {
+            eprintln!("Could not save watched pages: {error}");
+        }
+    });
+    match page_watch {
+        Some(page_watch) => schedule_page_watch(&page_watch),
+        None => SCHEDULED_PAGE_WATCHES
+            .lock()
+            .unwrap()
+            .retain(|(scheduled_url, _)| scheduled_url != url),
+    }
+}
+
+/// Start checking every watched page, whether or not it is open in a Web Window
+pub fn start_page_watches() {
+    for page_watch in page_watches() {
+        schedule_page_watch(&page_watch);
+    }
+}
+
+/// Check `page_watch` every so often from now on, instead of how it was checked before
+fn schedule_page_watch(page_watch: &PageWatch) {
+    let generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+    let mut scheduled_page_watches = SCHEDULED_PAGE_WATCHES.lock().unwrap();
+    scheduled_page_watches.retain(|(scheduled_url, _)| *scheduled_url != page_watch.url);
+    scheduled_page_watches.push((page_watch.url.clone(), generation));
+    drop(scheduled_page_watches);
+    // Remember how the page looks now, so the first check has something to compare with
+    if !has_last_check(page_watch) {
+        check_watched_page(page_watch.clone());
+    }
+    let url = page_watch.url.clone();
+    glib::timeout_add_seconds_local_once(page_watch.interval_minutes * 60, move || {
+        run_scheduled_check(url, generation)
+    });
+}
+
+fn run_scheduled_check(url: String, generation: u32) {
+    let is_current = SCHEDULED_PAGE_WATCHES
+        .lock()
+        .unwrap()
+        .contains(&(url.clone(), generation));
+    // The settings may have changed since the check was scheduled, in which case it is stale
+    let Some(page_watch) = page_watch_for(&url).filter(|_| is_current) else {
+        return;
+    };
+    glib::timeout_add_seconds_local_once(page_watch.interval_minutes * 60, move || {
+        run_scheduled_check(url, generation)
+    });
+    check_watched_page(page_watch);
+}
+
+/// Load the watched page in a web view that is never shown, so checks don't disturb Web Windows,
+/// and tell the main window if it changed
+fn check_watched_page(page_watch: PageWatch) {
+    let web_view = webkit6::WebView::builder()
+        .network_session(&webkit6::NetworkSession::default())
+        .build();
+    if let Some(web_view_settings) = webkit6::prelude::WebViewExt::settings(&web_view) {
+        web_view_settings.set_enable_javascript(javascript_enabled_for(
+            origin_of(&page_watch.url).as_deref(),
+        ));
+        if let Some(user_agent) = user_agent_for(host_of(&page_watch.url).as_deref()) {
+            web_view_settings.set_user_agent(Some(&user_agent));
+        }
+    }
+    web_view.allocate(PAGE_WATCH_WIDTH, PAGE_WATCH_HEIGHT, -1, None);
+    // Nothing else holds on to the web view until the page has loaded. Pages that redirect or navigate
+    // themselves finish loading more than once, but are only checked the first time.
+    let unchecked_web_view = Rc::new(RefCell::new(Some(web_view.clone())));
+    let url = page_watch.url.clone();
+    web_view.connect_load_changed(move |_, load_event| {
+        if load_event != webkit6::LoadEvent::Finished {
+            return;
+        }
+        let Some(web_view) = unchecked_web_view.borrow_mut().take() else {
+            return;
+        };
+        let page_watch = page_watch.clone();
+        // Give scripts on the page a moment to fill it in
+        glib::timeout_add_seconds_local_once(2, move || {
+            let title = web_view
+                .title()
+                .map(|title| title.to_string())
+                .unwrap_or_else(|| page_watch.url.clone());
+            let url = page_watch.url.clone();
+            // The web view is dropped once the check is done
+            check_page(&web_view, &page_watch, move |page_change| {
+                if let Some(page_change) = page_change {
+                    APP_BROKER.send(AppInput::WatchedPageChanged(url, title, page_change));
+                }
+            });
+        });
+    });
+    // Sites that may not have cookies in Web Windows may not have them here either
+    match web_view.user_content_manager() {
+        Some(user_content_manager) => apply_cookie_exceptions(
+            &user_content_manager,
+            glib::clone!(@strong web_view => move || web_view.load_uri(&url)),
+        ),
+        None => web_view.load_uri(&url),
+    }
+}
+
+/// Where the snapshot or text of the page at `url` from the last check is kept
+fn last_check_path(url: &str, extension: &str) -> Option<PathBuf> {
+    let mut folder = None;
+    with(
+        &[Document::at(
+            Project(Data(&["PageWatches"]).with_id("com", "github.kdwk", "Spidey")),
+            "",
+            Create::OnlyIfNotExists,
+        )
+        .alias("PageWatches")],
+        |d| {
+            folder = Some(PathBuf::from(d["PageWatches"].path()));
+            Ok(())
+        },
+    );
+    let digest = ring::digest::digest(&ring::digest::SHA256, url.as_bytes());
+    let file_name = digest
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    Some(folder?.join(format!("{file_name}.{extension}")))
+}
+
+/// Whether the page has been checked before, so there is something to compare with
+fn has_last_check(page_watch: &PageWatch) -> bool {
+    let extension = match page_watch.mode {
+        WatchMode::Appearance => "png",
+        WatchMode::Text => "txt",
+    };
+    last_check_path(&page_watch.url, extension).is_some_and(|path| path.is_file())
+}
+
+/// The text of the page and where the ignored parts are, with the ignored parts left out of the text
+fn page_state_script(ignored: &[String]) -> String {
+    format!(
+        r#"
+(() => {{
+    const ignored = Array.from({}).flatMap((selector) => {{
+        try {{
+            return Array.from(document.querySelectorAll(selector));
+        }} catch {{
+            return [];
+        }}
+    }});
+    const ignoredBounds = ignored.map((element) => {{
+        const bounds = element.getBoundingClientRect();
+        return {{ x: bounds.left + window.scrollX, y: bounds.top + window.scrollY, width: bounds.width, height: bounds.height }};
+    }});
+    const displays = ignored.map((element) => element.style.getPropertyValue("display"));
+    ignored.forEach((element) => element.style.setProperty("display", "none"));
+    const text = document.body ? document.body.innerText : "";
+    ignored.forEach((element, index) => element.style.setProperty("display", displays[index]));
+    return {{
+        text,
+        ignored: ignoredBounds,
+        documentWidth: Math.max(document.documentElement.scrollWidth, document.documentElement.clientWidth),
+    }};
+}})()
+"#,
+        serde_json::to_string(ignored).unwrap_or_else(|_| String::from("[]"))
+    )
+}
+
+#[derive(Deserialize)]
+struct IgnoredBounds {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageState {
+    text: String,
+    ignored: Vec<IgnoredBounds>,
+    document_width: f32,
+}
+
+/// Compare the page in `web_view` with the last check of `page_watch`. `on_checked` gets what changed,
+/// or None if nothing did, not enough did, or there was nothing to compare with yet.
+fn check_page(
+    web_view: &webkit6::WebView,
+    page_watch: &PageWatch,
+    on_checked: impl FnOnce(Option<PageChange>) + 'static,
+) {
+    let page_watch = page_watch.clone();
+    web_view.evaluate_javascript(
+        &page_state_script(&page_watch.ignored),
+        Some(PAGE_WATCH_WORLD),
+        None,
+        gio::Cancellable::NONE,
+        glib::clone!(@strong web_view => move |result| {
+            let page_state = match result
+                .map_err(|error| error.to_string())
+                .and_then(|value| value.to_json(0).ok_or_else(|| String::from("The page gave nothing back")))
+                .and_then(|json| serde_json::from_str::<PageState>(&json).map_err(|error| error.to_string()))
+            {
+                Ok(page_state) => page_state,
+                Err(error) => {
+                    eprintln!("Could not check watched page: {error}");
+                    on_checked(None);
+                    return;
+                }
+            };
+            match page_watch.mode {
+                WatchMode::Text => on_checked(check_text(&page_watch, &page_state.text)),
+                WatchMode::Appearance => web_view.snapshot(
+                    webkit6::SnapshotRegion::FullDocument,
+                    webkit6::SnapshotOptions::NONE,
+                    gio::Cancellable::NONE,
+                    move |snapshot_result| match snapshot_result {
+                        Ok(texture) => {
+                            // A full-page snapshot is as wide as the page, so this is how many pixels there are per CSS pixel
+                            let scale = texture.width() as f32 / page_state.document_width.max(1.0);
+                            let ignored = page_state
+                                .ignored
+                                .iter()
+                                .map(|bounds| {
+                                    graphene::Rect::new(
+                                        bounds.x * scale,
+                                        bounds.y * scale,
+                                        bounds.width * scale,
+                                        bounds.height * scale,
+                                    )
+                                })
+                                .collect::<Vec<graphene::Rect>>();
+                            on_checked(check_appearance(&page_watch, &texture, &ignored));
+                        }
+                        Err(error) => {
+                            eprintln!("Could not take snapshot of watched page: {error}");
+                            on_checked(None);
+                        }
+                    },
+                ),
+            }
+        }),
+    );
+}
+
+// The last check is only replaced when a change is reported, so that small changes which add up
+// over several checks are still noticed
+fn check_text(page_watch: &PageWatch, text: &str) -> Option<PageChange> {
+    let path = last_check_path(&page_watch.url, "txt")?;
+    let Ok(last_text) = fs::read_to_string(&path) else {
+        _ = fs::write(&path, text);
+        return None;
+    };
+    let lines = diff_lines(&last_text, text);
+    let changed = lines
+        .iter()
+        .filter(|line| !matches!(line, DiffLine::Same(_)))
+        .count();
+    let percent = changed as f64 * 100.0 / lines.len().max(1) as f64;
+    if changed == 0 || percent < page_watch.threshold {
+        return None;
+    }
+    _ = fs::write(&path, text);
+    Some(PageChange::Text { percent, lines })
+}
+
+fn check_appearance(
+    page_watch: &PageWatch,
+    texture: &gdk::Texture,
+    ignored: &[graphene::Rect],
+) -> Option<PageChange> {
+    let path = last_check_path(&page_watch.url, "png")?;
+    // Snapshots from before pages were checked at a fixed width can't be compared with
+    let Some(last_texture) = gdk::Texture::from_filename(&path)
+        .ok()
+        .filter(|last_texture| last_texture.width() == texture.width())
+    else {
+        _ = texture.save_to_png(&path);
+        return None;
+    };
+    let (percent, highlighted) = diff_pixels(&last_texture, texture, ignored);
+    if percent == 0.0 || percent < page_watch.threshold {
+        return None;
+    }
+    _ = texture.save_to_png(&path);
+    Some(PageChange::Appearance {
+        percent,
+        highlighted,
+    })
+}
+
+/// Lines of text with nothing but whitespace in them don't count as changes
+fn meaningful_lines(text: &str) -> Vec<&str> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// Compare two texts line by line, keeping the order of the lines
+fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old = meaningful_lines(old);
+    let new = meaningful_lines(new);
+    let prefix = old
+        .iter()
+        .zip(&new)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+    let mut lines = old[..prefix]
+        .iter()
+        .map(|line| DiffLine::Same(line.to_string()))
+        .collect::<Vec<DiffLine>>();
+    // Finding the longest common subsequence takes time and memory for every pair of lines, so very
+    // different pages are just shown as everything removed and everything added
+    if old_middle.len() * new_middle.len() > 4_000_000 {
+        lines.extend(
+            old_middle
+                .iter()
+                .map(|line| DiffLine::Removed(line.to_string())),
+        );
+        lines.extend(
+            new_middle
+                .iter()
+                .map(|line| DiffLine::Added(line.to_string())),
+        );
+    } else {
+        let mut common = vec![vec![0u32; new_middle.len() + 1]; old_middle.len() + 1];
+        for old_index in (0..old_middle.len()).rev() {
+            for new_index in (0..new_middle.len()).rev() {
+                common[old_index][new_index] = if old_middle[old_index] == new_middle[new_index] {
+                    common[old_index + 1][new_index + 1] + 1
+                } else {
+                    common[old_index + 1][new_index].max(common[old_index][new_index + 1])
+                };
+            }
+        }
+        let (mut old_index, mut new_index) = (0, 0);
+        while old_index < old_middle.len() && new_index < new_middle.len() {
+            if old_middle[old_index] == new_middle[new_index] {
+                lines.push(DiffLine::Same(old_middle[old_index].to_string()));
+                old_index += 1;
+                new_index += 1;
+            } else if common[old_index + 1][new_index] >= common[old_index][new_index + 1] {
+                lines.push(DiffLine::Removed(old_middle[old_index].to_string()));
+                old_index += 1;
+            } else {
+                lines.push(DiffLine::Added(new_middle[new_index].to_string()));
+                new_index += 1;
+            }
+        }
+        lines.extend(
+            old_middle[old_index..]
+                .iter()
+                .map(|line| DiffLine::Removed(line.to_string())),
+        );
+        lines.extend(
+            new_middle[new_index..]
+                .iter()
+                .map(|line| DiffLine::Added(line.to_string())),
+        );
+    }
+    lines.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|line| DiffLine::Same(line.to_string())),
+    );
+    lines
+}
+
+/// Pages are compared in squares of this many pixels, so the highlight is easy to see
+const CELL_SIZE: i32 = 8;
+/// How different a colour channel has to be to count, so that smoothing of text and images doesn't
+/// show up as a change
+const CHANNEL_TOLERANCE: u8 = 24;
+
+/// Compare two snapshots of a page, leaving out the `ignored` areas. Returns how much of the page
+/// changed in percent and `new` with the changes highlighted.
+fn diff_pixels(
+    old: &gdk::Texture,
+    new: &gdk::Texture,
+    ignored: &[graphene::Rect],
+) -> (f64, gdk::Texture) {
+    let download = |texture: &gdk::Texture| {
+        let mut texture_downloader = gdk::TextureDownloader::new(texture);
+        texture_downloader.set_format(gdk::MemoryFormat::R8g8b8a8);
+        texture_downloader.download_bytes()
+    };
+    let (old_pixels, old_stride) = download(old);
+    let (new_pixels, new_stride) = download(new);
+    let mut highlighted = new_pixels.to_vec();
+    // Anything outside of one of the snapshots is there because the page got longer or shorter
+    let width = old.width().max(new.width());
+    let height = old.height().max(new.height());
+    let (mut changed_cells, mut compared_cells) = (0usize, 0usize);
+    for cell_y in (0..height).step_by(CELL_SIZE as usize) {
+        for cell_x in (0..width).step_by(CELL_SIZE as usize) {
+            let cell_width = CELL_SIZE.min(width - cell_x);
+            let cell_height = CELL_SIZE.min(height - cell_y);
+            let cell_center = graphene::Point::new(
+                (cell_x + cell_width / 2) as f32,
+                (cell_y + cell_height / 2) as f32,
+            );
+            let is_ignored = ignored.iter().any(|area| area.contains_point(&cell_center));
+            let is_changed = !is_ignored
+                && (cell_x + cell_width > old.width().min(new.width())
+                    || cell_y + cell_height > old.height().min(new.height())
+                    || (cell_y..cell_y + cell_height).any(|y| {
+                        let old_row = y as usize * old_stride + cell_x as usize * 4;
+                        let new_row = y as usize * new_stride + cell_x as usize * 4;
+                        let row_length = cell_width as usize * 4;
+                        old_pixels[old_row..old_row + row_length]
+                            .iter()
+                            .zip(&new_pixels[new_row..new_row + row_length])
+                            .any(|(old, new)| old.abs_diff(*new) > CHANNEL_TOLERANCE)
+                    }));
+            if !is_ignored {
+                compared_cells += 1;
+            }
+            if is_changed {
+                changed_cells += 1;
+            }
+            // Tint changes red and ignored parts grey, where they are on the new snapshot
+            let tint = match (is_changed, is_ignored) {
+                (true, _) => Some([255u8, 40, 40]),
+                (false, true) => Some([128u8, 128, 128]),
+                (false, false) => None,
+            };
+            let Some(tint) = tint else {
+                continue;
+            };
+            for y in cell_y..(cell_y + cell_height).min(new.height()) {
+                for x in cell_x..(cell_x + cell_width).min(new.width()) {
+                    let pixel = y as usize * new_stride + x as usize * 4;
+                    for channel in 0..3 {
+                        highlighted[pixel + channel] = ((highlighted[pixel + channel] as u16
+                            + tint[channel] as u16)
+                            / 2) as u8;
+                    }
+                }
+            }
+        }
+    }
+    let percent = changed_cells as f64 * 100.0 / compared_cells.max(1) as f64;
+    let highlighted = gdk::MemoryTexture::new(
+        new.width(),
+        new.height(),
+        gdk::MemoryFormat::R8g8b8a8,
+        &glib::Bytes::from_owned(highlighted),
+        new_stride,
+    )
+    .upcast();
+    (percent, highlighted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn same(line: &str) -> DiffLine {
+        DiffLine::Same(line.to_string())
+    }
+
+    fn added(line: &str) -> DiffLine {
+        DiffLine::Added(line.to_string())
+    }
+
+    fn removed(line: &str) -> DiffLine {
+        DiffLine::Removed(line.to_string())
+    }
+
+    #[test]
+    fn identical_texts_are_all_same() {
+        assert_eq!(
+            diff_lines("a\nb\nc", "a\nb\nc"),
+            vec![same("a"), same("b"), same("c")]
+        );
+    }
+
+    #[test]
+    fn blank_lines_and_indentation_are_ignored() {
+        assert_eq!(
+            diff_lines("a\n\n  b\n", "  a\nb\n\n\n"),
+            vec![same("a"), same("b")]
+        );
+    }
+
+    #[test]
+    fn added_and_removed_lines_keep_their_place() {
+        assert_eq!(
+            diff_lines("a\nb\nc\nd", "a\nc\nx\nd"),
+            vec![same("a"), removed("b"), same("c"), added("x"), same("d")]
+        );
+    }
+
+    #[test]
+    fn changed_line_is_removed_then_added() {
+        assert_eq!(
+            diff_lines("price: 10\ntotal", "price: 12\ntotal"),
+            vec![removed("price: 10"), added("price: 12"), same("total")]
+        );
+    }
+
+    #[test]
+    fn empty_texts() {
+        assert_eq!(diff_lines("", ""), vec![]);
+        assert_eq!(diff_lines("", "a\nb"), vec![added("a"), added("b")]);
+        assert_eq!(diff_lines("a\nb", ""), vec![removed("a"), removed("b")]);
+    }
+
+    #[test]
+    fn very_different_texts_skip_the_common_subsequence() {
+        let old = (0..2001)
+            .map(|line| format!("old {line}\n"))
+            .collect::<String>();
+        let new = (0..2001)
+            .map(|line| format!("new {line}\n"))
+            .collect::<String>();
+        let lines = diff_lines(&old, &new);
+        assert_eq!(lines.len(), 4002);
+        assert!(lines[..2001]
+            .iter()
+            .all(|line| matches!(line, DiffLine::Removed(_))));
+        assert!(lines[2001..]
+            .iter()
+            .all(|line| matches!(line, DiffLine::Added(_))));
+    }
+}
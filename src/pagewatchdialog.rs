@@ -0,0 +1,389 @@
+#![allow(unused_imports)]
+#![allow(unused_variables)]
+use relm4::{
+    adw::prelude::*,
+    gtk::{glib::clone, prelude::*},
+    prelude::*,
+};
+
+use crate::pagewatch::{set_page_watch, DiffLine, PageChange, PageWatch, WatchMode};
+use crate::whoops::{attempt, Catch, IntoWhoops, Whoops};
+
+/// Lines kept around a changed line so it can be read in context
+const DIFF_CONTEXT_LINES: usize = 2;
+
+pub struct PageWatchDialog {
+    page_watch: PageWatch,
+    /// Whether the page was already being watched when the dialog was opened
+    watching: bool,
+    on_changed: Box<dyn Fn(Option<PageWatch>)>,
+}
+
+#[derive(Debug)]
+pub enum PageWatchInput {
+    SetInterval(u32),
+    SetMode(u32),
+    SetThreshold(f64),
+    AddIgnored(String),
+    RemoveIgnored(String),
+    Save,
+    Stop,
+}
+
+/// The page to watch, how it is watched now if it is, and what to do when that changes
+pub type PageWatchDialogInit = (String, Option<PageWatch>, Box<dyn Fn(Option<PageWatch>)>);
+
+#[relm4::component(pub)]
+impl Component for PageWatchDialog {
+    type Init = PageWatchDialogInit;
+    type Input = PageWatchInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        #[name(page_watch_dialog)]
+        adw::Dialog {
+            set_title: "Watch for Changes",
+            set_content_width: 450,
+
+            #[wrap(Some)]
+            set_child = &adw::ToolbarView {
+                add_top_bar = &adw::HeaderBar {},
+
+                #[wrap(Some)]
+                set_content = &adw::PreferencesPage {
+                    set_description: "Spidey loads the page in the background every so often and tells you when it looks different",
+
+                    add = &adw::PreferencesGroup {
+                        adw::SpinRow {
+                            set_title: "Check Every",
+                            set_subtitle: "Minutes",
+                            set_adjustment: Some(&gtk::Adjustment::new(60.0, 1.0, 1440.0, 1.0, 10.0, 0.0)),
+                            set_value: model.page_watch.interval_minutes as f64,
+                            connect_value_notify[sender] => move |this_row| {
+                                sender.input(PageWatchInput::SetInterval(this_row.value() as u32));
+                            },
+                        },
+
+                        adw::ComboRow {
+                            set_title: "Compare",
+                            set_subtitle: "Appearance compares snapshots of the whole page, Text only the words on it",
+                            set_model: Some(&gtk::StringList::new(
+                                &WatchMode::ALL.map(|watch_mode| watch_mode.label()),
+                            )),
+                            set_selected: WatchMode::ALL
+                                .iter()
+                                .position(|watch_mode| *watch_mode == model.page_watch.mode)
+                                .unwrap_or(0) as u32,
+                            connect_selected_notify[sender] => move |this_row| {
+                                sender.input(PageWatchInput::SetMode(this_row.selected()));
+                            },
+                        },
+
+                        adw::SpinRow {
+                            set_title: "Threshold",
+                            set_subtitle: "Percent of the page that has to change before you are told",
+                            set_adjustment: Some(&gtk::Adjustment::new(1.0, 0.1, 100.0, 0.1, 1.0, 0.0)),
+                            set_digits: 1,
+                            set_value: model.page_watch.threshold,
+                            connect_value_notify[sender] => move |this_row| {
+                                sender.input(PageWatchInput::SetThreshold(this_row.value()));
+                            },
+                        },
+                    },
+
+                    add = &adw::PreferencesGroup {
+                        set_title: "Ignored Parts",
+                        set_description: Some("CSS selectors for parts of the page that change all the time, e.g. .clock or #adverts"),
+
+                        #[name(ignored_list)]
+                        gtk::ListBox {
+                            add_css_class: "boxed-list",
+                            set_selection_mode: gtk::SelectionMode::None,
+
+                            adw::EntryRow {
+                                set_title: "Add Selector",
+                                set_show_apply_button: true,
+                                connect_apply[sender] => move |this_row| {
+                                    sender.input(PageWatchInput::AddIgnored(this_row.text().to_string()));
+                                    this_row.set_text("");
+                                },
+                            },
+                        },
+                    },
+
+                    add = &adw::PreferencesGroup {
+                        gtk::Box {
+                            set_orientation: gtk::Orientation::Horizontal,
+                            set_halign: gtk::Align::Center,
+                            set_spacing: 12,
+
+                            gtk::Button {
+                                set_label: "Stop Watching",
+                                add_css_class: "pill",
+                                add_css_class: "destructive-action",
+                                set_visible: model.watching,
+                                connect_clicked => PageWatchInput::Stop,
+                            },
+
+                            gtk::Button {
+                                set_label: if model.watching { "Save" } else { "Start Watching" },
+                                add_css_class: "pill",
+                                add_css_class: "suggested-action",
+                                connect_clicked => PageWatchInput::Save,
+                            },
+                        },
+                    },
+                }
+            }
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let (url, page_watch, on_changed) = init;
+        let model = PageWatchDialog {
+            watching: page_watch.is_some(),
+            page_watch: page_watch.unwrap_or_else(|| PageWatch::new(&url)),
+            on_changed,
+        };
+        let widgets = view_output!();
+        populate_ignored(&widgets.ignored_list, &model.page_watch.ignored, &sender);
+        ComponentParts {
+            model: model,
+            widgets: widgets,
+        }
+    }
+
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::Input,
+        sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        attempt(|| {
+            match message {
+                PageWatchInput::SetInterval(interval_minutes) => {
+                    self.page_watch.interval_minutes = interval_minutes.max(1)
+                }
+                PageWatchInput::SetMode(index) => {
+                    self.page_watch.mode = *WatchMode::ALL.get(index as usize)?
+                }
+                PageWatchInput::SetThreshold(threshold) => self.page_watch.threshold = threshold,
+                PageWatchInput::AddIgnored(selector) => {
+                    let selector = selector.trim().to_string();
+                    if !selector.is_empty() && !self.page_watch.ignored.contains(&selector) {
+                        self.page_watch.ignored.push(selector);
+                        populate_ignored(&widgets.ignored_list, &self.page_watch.ignored, &sender);
+                    }
+                }
+                PageWatchInput::RemoveIgnored(selector) => {
+                    self.page_watch
+                        .ignored
+                        .retain(|ignored| *ignored != selector);
+                    populate_ignored(&widgets.ignored_list, &self.page_watch.ignored, &sender);
+                }
+                PageWatchInput::Save => {
+                    set_page_watch(&self.page_watch.url, Some(self.page_watch.clone()));
+                    (self.on_changed)(Some(self.page_watch.clone()));
+                    root.close();
+                }
+                PageWatchInput::Stop => {
+                    set_page_watch(&self.page_watch.url, None);
+                    (self.on_changed)(None);
+                    root.close();
+                }
+            }
+            self.update_view(widgets, sender.clone());
+            Some(())
+        })
+        .catch(|error| eprintln!("{error}"));
+    }
+}
+
+/// Show the ignored selectors after the row for adding one
+fn populate_ignored(
+    ignored_list: &gtk::ListBox,
+    ignored: &[String],
+    sender: &ComponentSender<PageWatchDialog>,
+) {
+    while let Some(ignored_row) = ignored_list.row_at_index(1) {
+        ignored_list.remove(&ignored_row);
+    }
+    for selector in ignored {
+        let ignored_row = adw::ActionRow::builder()
+            .title(selector.as_str())
+            .use_markup(false)
+            .build();
+        let remove_button = gtk::Button::builder()
+            .icon_name("user-trash-symbolic")
+            .tooltip_text("Remove")
+            .valign(gtk::Align::Center)
+            .css_classes(["flat", "circular"])
+            .build();
+        remove_button.connect_clicked(clone!(@strong sender, @strong selector => move |_| {
+            sender.input(PageWatchInput::RemoveIgnored(selector.clone()));
+        }));
+        ignored_row.add_suffix(&remove_button);
+        ignored_list.append(&ignored_row);
+    }
+}
+
+/// Open the settings for watching `url` on top of `parent`. `on_changed` gets the new settings, or None
+/// if the user stopped watching the page.
+pub fn present_page_watch_dialog(
+    url: String,
+    page_watch: Option<PageWatch>,
+    on_changed: impl Fn(Option<PageWatch>) + 'static,
+    parent: &impl IsA<gtk::Widget>,
+) {
+    let mut page_watch_dialog = PageWatchDialog::builder()
+        .launch((url, page_watch, Box::new(on_changed)))
+        .detach();
+    page_watch_dialog.detach_runtime();
+    page_watch_dialog
+        .widgets()
+        .page_watch_dialog
+        .present(Some(parent));
+}
+
+pub struct PageChangesDialog {
+    title: String,
+    change: PageChange,
+}
+
+#[relm4::component(pub)]
+impl SimpleComponent for PageChangesDialog {
+    type Init = (String, PageChange);
+    type Input = ();
+    type Output = ();
+
+    view! {
+        #[name(page_changes_dialog)]
+        adw::Dialog {
+            set_title: "Page Changed",
+            set_content_width: 700,
+            set_content_height: 600,
+
+            #[wrap(Some)]
+            set_child = &adw::ToolbarView {
+                add_top_bar = &adw::HeaderBar {
+                    #[wrap(Some)]
+                    set_title_widget = &adw::WindowTitle {
+                        set_title: &model.title,
+                        set_subtitle: &format!("{:.1}% of the page changed", model.change.percent()),
+                    },
+                },
+
+                #[wrap(Some)]
+                #[name(changes_scrolled_window)]
+                set_content = &gtk::ScrolledWindow {
+                    set_vexpand: true,
+                },
+            }
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let (title, change) = init;
+        let model = PageChangesDialog { title, change };
+        let widgets = view_output!();
+        match &model.change {
+            PageChange::Appearance { highlighted, .. } => {
+                let picture = gtk::Picture::for_paintable(highlighted);
+                // Show the page at its real size, so small changes are not shrunk out of sight
+                picture.set_can_shrink(false);
+                picture.set_halign(gtk::Align::Center);
+                picture.set_valign(gtk::Align::Start);
+                widgets.changes_scrolled_window.set_child(Some(&picture));
+            }
+            PageChange::Text { lines, .. } => {
+                widgets
+                    .changes_scrolled_window
+                    .set_child(Some(&text_diff_view(lines)));
+            }
+        }
+        ComponentParts {
+            model: model,
+            widgets: widgets,
+        }
+    }
+}
+
+/// Added lines in green and removed ones struck out in red, with the unchanged lines around them
+fn text_diff_view(lines: &[DiffLine]) -> gtk::TextView {
+    let text_view = gtk::TextView::builder()
+        .editable(false)
+        .cursor_visible(false)
+        .monospace(true)
+        .wrap_mode(gtk::WrapMode::WordChar)
+        .top_margin(12)
+        .bottom_margin(12)
+        .left_margin(12)
+        .right_margin(12)
+        .build();
+    let buffer = text_view.buffer();
+    let added_tag = buffer
+        .create_tag(
+            Some("added"),
+            &[("paragraph-background", &"rgba(46, 194, 126, 0.25)")],
+        )
+        .expect("Could not create text tag for added lines");
+    let removed_tag = buffer
+        .create_tag(
+            Some("removed"),
+            &[
+                ("paragraph-background", &"rgba(224, 27, 36, 0.25)"),
+                ("strikethrough", &true),
+            ],
+        )
+        .expect("Could not create text tag for removed lines");
+    let is_changed = |index: usize| !matches!(lines.get(index), Some(DiffLine::Same(_)) | None);
+    let mut skipped = false;
+    for (index, line) in lines.iter().enumerate() {
+        let near_change =
+            (index.saturating_sub(DIFF_CONTEXT_LINES)..=index + DIFF_CONTEXT_LINES).any(is_changed);
+        if !near_change {
+            skipped = true;
+            continue;
+        }
+        let mut end = buffer.end_iter();
+        if skipped {
+            buffer.insert(&mut end, "⋯\n");
+            skipped = false;
+        }
+        match line {
+            DiffLine::Same(text) => buffer.insert(&mut end, &format!("  {text}\n")),
+            DiffLine::Added(text) => {
+                buffer.insert_with_tags(&mut end, &format!("+ {text}\n"), &[&added_tag])
+            }
+            DiffLine::Removed(text) => {
+                buffer.insert_with_tags(&mut end, &format!("- {text}\n"), &[&removed_tag])
+            }
+        }
+    }
+    if skipped {
+        buffer.insert(&mut buffer.end_iter(), "⋯\n");
+    }
+    text_view
+}
+
+/// Show what changed on the watched page titled `title` on top of `parent`
+pub fn present_page_changes(title: String, change: PageChange, parent: &impl IsA<gtk::Widget>) {
+    let mut page_changes_dialog = PageChangesDialog::builder()
+        .launch((title, change))
+        .detach();
+    page_changes_dialog.detach_runtime();
+    page_changes_dialog
+        .widgets()
+        .page_changes_dialog
+        .present(Some(parent));
+}
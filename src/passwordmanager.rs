@@ -0,0 +1,390 @@
+#![allow(unused_imports)]
+#![allow(unused_variables)]
+use relm4::{
+    adw::prelude::*,
+    gtk::{gdk::ContentProvider, gio, glib::clone, prelude::*},
+    prelude::*,
+};
+use webkit6::prelude::*;
+
+use crate::passwords::{
+    credentials_from_csv, credentials_to_csv, load_credentials, merge_credentials,
+    update_credentials, Credential,
+};
+use crate::whoops::{attempt, Catch, IntoWhoops, Whoops};
+
+pub struct PasswordManagerDialog {
+    credentials: Vec<Credential>,
+    search_query: String,
+    loading: bool,
+    load_failed: bool,
+}
+
+#[derive(Debug)]
+pub enum PasswordManagerInput {
+    Reload,
+    CredentialsLoaded(Result<Vec<Credential>, String>),
+    Search(String),
+    CopyUsername(usize),
+    CopyPassword(usize),
+    Delete(usize),
+    Import,
+    ImportFrom(gio::File),
+    Export,
+    ExportTo(gio::File),
+    Save(CredentialChange, String),
+    ShowToast(String),
+}
+
+/// A change to the saved credentials, applied to what is saved at the time rather than to the list shown
+#[derive(Debug)]
+pub enum CredentialChange {
+    Delete(Credential),
+    Import(Vec<Credential>),
+}
+
+#[relm4::component(pub)]
+impl Component for PasswordManagerDialog {
+    type Init = ();
+    type Input = PasswordManagerInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        #[name(password_manager_dialog)]
+        adw::Dialog {
+            set_title: "Passwords",
+            set_content_width: 500,
+            set_content_height: 600,
+
+            #[wrap(Some)]
+            set_child = &adw::ToolbarView {
+                add_top_bar = &adw::HeaderBar {
+                    pack_start = &gtk::Button {
+                        set_icon_name: "document-open-symbolic",
+                        set_tooltip_text: Some("Import passwords from a CSV file"),
+                        #[watch]
+                        set_sensitive: !model.loading && !model.load_failed,
+                        connect_clicked => PasswordManagerInput::Import,
+                    },
+
+                    pack_start = &gtk::Button {
+                        set_icon_name: "document-save-symbolic",
+                        set_tooltip_text: Some("Export passwords to an unencrypted CSV file"),
+                        #[watch]
+                        set_sensitive: !model.credentials.is_empty(),
+                        connect_clicked => PasswordManagerInput::Export,
+                    },
+                },
+
+                add_top_bar = &gtk::SearchEntry {
+                    set_margin_all: 6,
+                    set_placeholder_text: Some("Search sites and usernames"),
+                    connect_search_changed[sender] => move |this_entry| {
+                        sender.input(PasswordManagerInput::Search(this_entry.text().to_string()));
+                    },
+                },
+
+                #[wrap(Some)]
+                #[name(toast_overlay)]
+                set_content = &adw::ToastOverlay {
+                    gtk::Stack {
+                        #[watch]
+                        set_visible_child_name: if model.loading {
+                            "loading"
+                        } else if model.load_failed {
+                            "failed"
+                        } else if model.credentials.is_empty() {
+                            "empty"
+                        } else {
+                            "passwords"
+                        },
+
+                        add_named[Some("loading")] = &gtk::Spinner {
+                            set_halign: gtk::Align::Center,
+                            set_valign: gtk::Align::Center,
+                            #[watch]
+                            set_spinning: model.loading,
+                        },
+
+                        add_named[Some("failed")] = &adw::StatusPage {
+                            set_icon_name: Some("padlock2"),
+                            set_title: "Passwords Unavailable",
+                            set_description: Some("Saved passwords could not be unlocked"),
+                        },
+
+                        add_named[Some("empty")] = &adw::StatusPage {
+                            set_icon_name: Some("padlock2"),
+                            set_title: "No Passwords",
+                            set_description: Some("Passwords you save when logging in to sites will appear here"),
+                        },
+
+                        add_named[Some("passwords")] = &gtk::ScrolledWindow {
+                            set_vexpand: true,
+
+                            adw::Clamp {
+                                set_margin_all: 12,
+
+                                #[name(credential_list)]
+                                gtk::ListBox {
+                                    add_css_class: "boxed-list",
+                                    set_valign: gtk::Align::Start,
+                                    set_selection_mode: gtk::SelectionMode::None,
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = PasswordManagerDialog {
+            credentials: vec![],
+            search_query: String::new(),
+            loading: true,
+            load_failed: false,
+        };
+        let widgets = view_output!();
+        sender.input(PasswordManagerInput::Reload);
+        ComponentParts {
+            model: model,
+            widgets: widgets,
+        }
+    }
+
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::Input,
+        sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        attempt(|| {
+            match message {
+                PasswordManagerInput::Reload => {
+                    self.loading = true;
+                    relm4::spawn_local(clone!(@strong sender => async move {
+                        sender.input(PasswordManagerInput::CredentialsLoaded(
+                            load_credentials().await.map_err(|error| error.to_string()),
+                        ));
+                    }));
+                }
+                PasswordManagerInput::CredentialsLoaded(credentials_result) => {
+                    self.loading = false;
+                    match credentials_result {
+                        Ok(mut credentials) => {
+                            credentials.sort_by(|a, b| {
+                                (&a.origin, &a.username).cmp(&(&b.origin, &b.username))
+                            });
+                            self.load_failed = false;
+                            self.credentials = credentials;
+                        }
+                        Err(error) => {
+                            eprintln!("Could not load saved passwords: {error}");
+                            self.load_failed = true;
+                            self.credentials = vec![];
+                        }
+                    }
+                    self.populate(&widgets.credential_list, &sender);
+                }
+                PasswordManagerInput::Search(query) => {
+                    self.search_query = query.to_lowercase();
+                    self.populate(&widgets.credential_list, &sender);
+                }
+                PasswordManagerInput::CopyUsername(index) => {
+                    root.clipboard().set_content(Some(&ContentProvider::for_value(
+                        &self.credentials.get(index)?.username.to_value(),
+                    )))
+                    .ok()?;
+                    sender.input(PasswordManagerInput::ShowToast(String::from("Copied username")));
+                }
+                PasswordManagerInput::CopyPassword(index) => {
+                    root.clipboard().set_content(Some(&ContentProvider::for_value(
+                        &self.credentials.get(index)?.password.to_value(),
+                    )))
+                    .ok()?;
+                    sender.input(PasswordManagerInput::ShowToast(String::from("Copied password")));
+                }
+                PasswordManagerInput::Delete(index) => {
+                    let credential = self.credentials.remove(index);
+                    self.populate(&widgets.credential_list, &sender);
+                    let success_message = format!("Deleted password for {}", credential.origin);
+                    sender.input(PasswordManagerInput::Save(
+                        CredentialChange::Delete(credential),
+                        success_message,
+                    ));
+                }
+                PasswordManagerInput::Import => {
+                    let parent_window = root.root().and_downcast::<gtk::Window>();
+                    gtk::FileDialog::builder()
+                        .title("Import Passwords")
+                        .modal(true)
+                        .build()
+                        .open(
+                            parent_window.as_ref(),
+                            gio::Cancellable::NONE,
+                            clone!(@strong sender => move |file_result| {
+                                if let Ok(file) = file_result {
+                                    sender.input(PasswordManagerInput::ImportFrom(file));
+                                }
+                            }),
+                        );
+                }
+                PasswordManagerInput::ImportFrom(file) => {
+                    let (contents, _) = match file.load_contents(gio::Cancellable::NONE) {
+                        Ok(contents) => contents,
+                        Err(error) => {
+                            sender.input(PasswordManagerInput::ShowToast(format!("Could not read file: {error}")));
+                            return Some(());
+                        }
+                    };
+                    let imported = credentials_from_csv(&String::from_utf8_lossy(&contents));
+                    if imported.is_empty() {
+                        sender.input(PasswordManagerInput::ShowToast(String::from(
+                            "No passwords found. The file needs url, username and password columns.",
+                        )));
+                        return Some(());
+                    }
+                    let imported_count = imported.len();
+                    merge_credentials(&mut self.credentials, imported.clone());
+                    self.populate(&widgets.credential_list, &sender);
+                    sender.input(PasswordManagerInput::Save(
+                        CredentialChange::Import(imported),
+                        if imported_count == 1 {
+                            String::from("Imported 1 password")
+                        } else {
+                            format!("Imported {imported_count} passwords")
+                        },
+                    ));
+                }
+                PasswordManagerInput::Export => {
+                    let parent_window = root.root().and_downcast::<gtk::Window>();
+                    gtk::FileDialog::builder()
+                        .title("Export Passwords")
+                        .initial_name("passwords.csv")
+                        .modal(true)
+                        .build()
+                        .save(
+                            parent_window.as_ref(),
+                            gio::Cancellable::NONE,
+                            clone!(@strong sender => move |file_result| {
+                                if let Ok(file) = file_result {
+                                    sender.input(PasswordManagerInput::ExportTo(file));
+                                }
+                            }),
+                        );
+                }
+                PasswordManagerInput::ExportTo(file) => {
+                    match file.replace_contents(
+                        credentials_to_csv(&self.credentials).as_bytes(),
+                        None,
+                        false,
+                        gio::FileCreateFlags::PRIVATE | gio::FileCreateFlags::REPLACE_DESTINATION,
+                        gio::Cancellable::NONE,
+                    ) {
+                        Ok(_) => sender.input(PasswordManagerInput::ShowToast(String::from(
+                            "Exported passwords. Keep the file safe, it is not encrypted.",
+                        ))),
+                        Err(error) => sender.input(PasswordManagerInput::ShowToast(format!(
+                            "Could not export passwords: {error}"
+                        ))),
+                    }
+                }
+                PasswordManagerInput::Save(change, success_message) => {
+                    relm4::spawn_local(clone!(@strong sender => async move {
+                        let result = update_credentials(|credentials| match change {
+                            CredentialChange::Delete(deleted) => {
+                                credentials.retain(|credential| *credential != deleted)
+                            }
+                            CredentialChange::Import(imported) => merge_credentials(credentials, imported),
+                        })
+                        .await;
+                        match result {
+                            Ok(_) => sender.input(PasswordManagerInput::ShowToast(success_message)),
+                            Err(error) => sender.input(PasswordManagerInput::ShowToast(format!(
+                                "Could not save passwords: {error}"
+                            ))),
+                        }
+                        // Show what is saved now, including passwords saved by other windows
+                        sender.input(PasswordManagerInput::Reload);
+                    }));
+                }
+                PasswordManagerInput::ShowToast(message) => {
+                    widgets.toast_overlay.add_toast(adw::Toast::new(&message))
+                }
+            }
+            self.update_view(widgets, sender.clone());
+            Some(())
+        })
+        .catch(|error| eprintln!("{error}"));
+    }
+}
+
+impl PasswordManagerDialog {
+    fn populate(&self, credential_list: &gtk::ListBox, sender: &ComponentSender<Self>) {
+        credential_list.remove_all();
+        for (index, credential) in self.credentials.iter().enumerate() {
+            if !credential
+                .origin
+                .to_lowercase()
+                .contains(&self.search_query)
+                && !credential
+                    .username
+                    .to_lowercase()
+                    .contains(&self.search_query)
+            {
+                continue;
+            }
+            let credential_row = adw::ActionRow::builder()
+                .title(credential.origin.as_str())
+                .subtitle(match (credential.username.as_str(), &credential.realm) {
+                    ("", None) => String::from("No username"),
+                    (username, None) => username.to_string(),
+                    (username, Some(realm)) => format!("{username} · {realm}"),
+                })
+                .use_markup(false)
+                .build();
+            for (icon_name, tooltip_text, input) in [
+                (
+                    "avatar-default-symbolic",
+                    "Copy username",
+                    PasswordManagerInput::CopyUsername as fn(usize) -> PasswordManagerInput,
+                ),
+                ("copy", "Copy password", PasswordManagerInput::CopyPassword),
+                (
+                    "user-trash-symbolic",
+                    "Delete password",
+                    PasswordManagerInput::Delete,
+                ),
+            ] {
+                let button = gtk::Button::builder()
+                    .icon_name(icon_name)
+                    .tooltip_text(tooltip_text)
+                    .valign(gtk::Align::Center)
+                    .css_classes(["flat", "circular"])
+                    .build();
+                button.connect_clicked(clone!(@strong sender => move |_| {
+                    sender.input(input(index));
+                }));
+                credential_row.add_suffix(&button);
+            }
+            credential_list.append(&credential_row);
+        }
+    }
+}
+
+/// Open the saved passwords on top of `parent`
+pub fn present_password_manager(parent: &impl IsA<gtk::Widget>) {
+    let mut password_manager_dialog = PasswordManagerDialog::builder().launch(()).detach();
+    password_manager_dialog.detach_runtime();
+    password_manager_dialog
+        .widgets()
+        .password_manager_dialog
+        .present(Some(parent));
+}
@@ -0,0 +1,576 @@
+use std::{error::Error, fs, io::Write, os::unix::fs::OpenOptionsExt, path::Path, sync::OnceLock};
+
+use documents::prelude::*;
+use relm4::{adw::prelude::*, prelude::*};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    digest,
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+use webkit6::{javascriptcore, prelude::*};
+
+use crate::sitesettings::{origin_of, set_site_exception, site_exception, SiteException};
+
+/// A username and password saved for an origin
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Credential {
+    pub origin: String,
+    pub username: String,
+    pub password: String,
+    /// The HTTP authentication realm this is for, or None if it was saved from a login form
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub realm: Option<String>,
+}
+
+impl Credential {
+    /// Whether this and `other` are the same account, so one should replace the other
+    pub fn is_same_account(&self, other: &Credential) -> bool {
+        self.origin == other.origin && self.username == other.username && self.realm == other.realm
+    }
+}
+
+/// What the autofill script asks for
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum PasswordMessage {
+    Save { username: String, password: String },
+    Fill,
+}
+
+const PASSWORDS_WORLD: &str = "spidey-passwords";
+const PASSWORDS_MESSAGE_HANDLER: &str = "spideyPasswords";
+
+/// Fills in saved credentials when the user clicks a login field and offers to save the ones the user submits.
+/// It runs in its own script world so the page can't talk to the message handler and ask for another site's passwords.
+/// Nothing is filled in without the user, so a page can't collect passwords from fields the user never sees.
+const AUTOFILL_SCRIPT: &str = r#"
+(() => {
+    const handler = window.webkit.messageHandlers.spideyPasswords;
+    const textFieldSelector = "input:not([type]), input[type=text], input[type=email], input[type=tel]";
+    const usernameFieldOf = (passwordField) =>
+        Array.from((passwordField.form || document).querySelectorAll(textFieldSelector))
+            .filter((field) => field.compareDocumentPosition(passwordField) & Node.DOCUMENT_POSITION_FOLLOWING)
+            .pop() || null;
+    const setValue = (field, value) => {
+        field.value = value;
+        field.dispatchEvent(new Event("input", { bubbles: true }));
+        field.dispatchEvent(new Event("change", { bubbles: true }));
+    };
+
+    let lastOffered = null;
+    const offerToSave = (container) => {
+        const passwordField = container && container.querySelector("input[type=password]");
+        if (!passwordField || !passwordField.value) {
+            return;
+        }
+        const usernameField = usernameFieldOf(passwordField);
+        const credential = { username: usernameField ? usernameField.value : "", password: passwordField.value };
+        if (lastOffered === JSON.stringify(credential)) {
+            return;
+        }
+        lastOffered = JSON.stringify(credential);
+        handler.postMessage({ type: "save", ...credential });
+    };
+    document.addEventListener("submit", (event) => offerToSave(event.target), true);
+    // Many sites log in with a script instead of submitting the form
+    document.addEventListener("click", (event) => {
+        const button = event.target.closest && event.target.closest("button, input[type=submit]");
+        if (button) {
+            offerToSave(button.form || button.closest("form") || document);
+        }
+    }, true);
+
+    let credentials = null;
+    const fill = (event) => {
+        const field = event.target;
+        if (!event.isTrusted || !(field instanceof HTMLInputElement)) {
+            return;
+        }
+        const passwordField = field.type === "password"
+            ? field
+            : Array.from((field.form || document).querySelectorAll("input[type=password]"))
+                .find((passwordField) => usernameFieldOf(passwordField) === field);
+        if (!passwordField || passwordField.value) {
+            return;
+        }
+        credentials = credentials || handler.postMessage({ type: "fill" });
+        credentials.then((saved) => {
+            if (!saved || saved.length === 0) {
+                return;
+            }
+            const usernameField = usernameFieldOf(passwordField);
+            const credential = saved.find((saved) => usernameField && saved.username === usernameField.value) || saved[0];
+            if (usernameField && !usernameField.value) {
+                setValue(usernameField, credential.username);
+            }
+            setValue(passwordField, credential.password);
+        });
+    };
+    document.addEventListener("pointerdown", fill, true);
+    document.addEventListener("keydown", (event) => event.key === "ArrowDown" && fill(event), true);
+})();
+"#;
+
+static ENCRYPTION_KEY: OnceLock<[u8; 32]> = OnceLock::new();
+
+fn data_file_path(file_name: &str) -> Option<String> {
+    let mut path = None;
+    with(
+        &[Document::at(
+            Project(Data(&[]).with_id("com", "github.kdwk", "Spidey")),
+            file_name,
+            Create::No,
+        )],
+        |d| {
+            path = Some(d[file_name].path());
+            Ok(())
+        },
+    );
+    path
+}
+
+/// Get the key passwords are encrypted with. It is derived from the secret the Secret portal keeps for Spidey in the
+/// user's keyring when there is one, otherwise it is generated once and kept in the data directory.
+/// A key file that already exists is always used so passwords saved without the portal can still be read.
+/// A key file is only created while no passwords are saved, as it would take priority over the secret they were
+/// encrypted with.
+async fn encryption_key() -> Result<[u8; 32], Box<dyn Error>> {
+    if let Some(key) = ENCRYPTION_KEY.get() {
+        return Ok(*key);
+    }
+    let mut key = [0u8; 32];
+    let key_path = data_file_path("passwords.key").ok_or("Could not find data directory")?;
+    match fs::read(&key_path) {
+        Ok(saved_key) if saved_key.len() == key.len() => key.copy_from_slice(&saved_key),
+        _ => match ashpd::desktop::secret::retrieve().await {
+            Ok(secret) => key.copy_from_slice(digest::digest(&digest::SHA256, &secret).as_ref()),
+            Err(error) => {
+                let passwords_path =
+                    data_file_path("passwords").ok_or("Could not find data directory")?;
+                if Path::new(&passwords_path).exists() {
+                    return Err(format!("Could not unlock saved passwords: {error}"))?;
+                }
+                eprintln!("Secret portal unavailable, keeping the password key in a file instead: {error}");
+                SystemRandom::new()
+                    .fill(&mut key)
+                    .map_err(|_| "Could not generate password encryption key")?;
+                fs::OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .mode(0o600)
+                    .open(&key_path)?
+                    .write_all(&key)?;
+            }
+        },
+    }
+    Ok(*ENCRYPTION_KEY.get_or_init(|| key))
+}
+
+fn encrypt(key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).map_err(|_| "Invalid key")?);
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| "Could not generate nonce")?;
+    let mut ciphertext = plaintext.to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::empty(),
+        &mut ciphertext,
+    )
+    .map_err(|_| "Could not encrypt passwords")?;
+    Ok([nonce.as_slice(), &ciphertext].concat())
+}
+
+fn decrypt(key: &[u8; 32], encrypted: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    if encrypted.len() < NONCE_LEN {
+        return Err("Saved passwords are corrupted")?;
+    }
+    let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).map_err(|_| "Invalid key")?);
+    let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+    let mut ciphertext = ciphertext.to_vec();
+    let plaintext = key
+        .open_in_place(
+            Nonce::try_assume_unique_for_key(nonce).map_err(|_| "Invalid nonce")?,
+            Aad::empty(),
+            &mut ciphertext,
+        )
+        .map_err(|_| "Could not decrypt saved passwords")?;
+    Ok(plaintext.to_vec())
+}
+
+/// Get every saved credential
+pub async fn load_credentials() -> Result<Vec<Credential>, Box<dyn Error>> {
+    let key = encryption_key().await?;
+    let passwords_path = data_file_path("passwords").ok_or("Could not find data directory")?;
+    let encrypted = match fs::read(passwords_path) {
+        Ok(encrypted) => encrypted,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(error) => return Err(error)?,
+    };
+    Ok(serde_json::from_slice(&decrypt(&key, &encrypted)?)?)
+}
+
+/// Replace all saved credentials with `credentials`
+async fn save_credentials(credentials: &[Credential]) -> Result<(), Box<dyn Error>> {
+    let key = encryption_key().await?;
+    let passwords_path = data_file_path("passwords").ok_or("Could not find data directory")?;
+    let encrypted = encrypt(&key, &serde_json::to_vec(credentials)?)?;
+    fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(passwords_path)?
+        .write_all(&encrypted)?;
+    Ok(())
+}
+
+/// Change the saved credentials with `change`. They are read again right before writing so credentials saved by
+/// another window in the meantime are kept. Once the key is known nothing here waits, so no other save can come between.
+pub async fn update_credentials(
+    change: impl FnOnce(&mut Vec<Credential>),
+) -> Result<(), Box<dyn Error>> {
+    let mut credentials = load_credentials().await?;
+    change(&mut credentials);
+    save_credentials(&credentials).await
+}
+
+/// Add `new_credentials` to `credentials`, replacing the passwords of ones with the same origin and username
+pub fn merge_credentials(credentials: &mut Vec<Credential>, new_credentials: Vec<Credential>) {
+    for new_credential in new_credentials {
+        match credentials
+            .iter_mut()
+            .find(|credential| credential.is_same_account(&new_credential))
+        {
+            Some(credential) => credential.password = new_credential.password,
+            None => credentials.push(new_credential),
+        }
+    }
+}
+
+/// Fill in saved passwords and offer to save new ones in every page loaded with `user_content_manager`.
+/// `offer_to_save` is called with credentials the user submits that are not saved yet.
+pub fn set_up_password_autofill(
+    user_content_manager: &webkit6::UserContentManager,
+    offer_to_save: impl Fn(Credential) + 'static,
+) {
+    user_content_manager.add_script(&webkit6::UserScript::for_world(
+        AUTOFILL_SCRIPT,
+        webkit6::UserContentInjectedFrames::TopFrame,
+        webkit6::UserScriptInjectionTime::End,
+        PASSWORDS_WORLD,
+        &[],
+        &[],
+    ));
+    user_content_manager
+        .register_script_message_handler_with_reply(PASSWORDS_MESSAGE_HANDLER, PASSWORDS_WORLD);
+    user_content_manager.connect_script_message_with_reply_received(
+        Some(PASSWORDS_MESSAGE_HANDLER),
+        move |_, value, reply| {
+            let Some(context) = value.context() else {
+                return false;
+            };
+            let message = value
+                .to_json(0)
+                .and_then(|json| serde_json::from_str::<PasswordMessage>(&json).ok());
+            // Taken from the script world's own globals rather than the message, which the page can't touch
+            let frame_origin = context
+                .global_object()
+                .and_then(|global| global.object_get_property("location"))
+                .and_then(|location| location.object_get_property("origin"))
+                .map(|origin| origin.to_str().to_string());
+            let Some(frame_origin) = frame_origin.filter(|origin| origin_of(origin).is_some())
+            else {
+                reply.return_value(&javascriptcore::Value::new_null(&context));
+                return true;
+            };
+            match message {
+                Some(PasswordMessage::Save { username, password }) => {
+                    offer_to_save(Credential {
+                        origin: frame_origin,
+                        username,
+                        password,
+                        realm: None,
+                    });
+                    reply.return_value(&javascriptcore::Value::new_null(&context));
+                }
+                Some(PasswordMessage::Fill) => {
+                    let reply = reply.clone();
+                    relm4::spawn_local(async move {
+                        let credentials = load_credentials()
+                            .await
+                            .unwrap_or_else(|error| {
+                                eprintln!("Could not load saved passwords: {error}");
+                                vec![]
+                            })
+                            .into_iter()
+                            .filter(|credential| {
+                                credential.origin == frame_origin && credential.realm.is_none()
+                            })
+                            .collect::<Vec<Credential>>();
+                        let json =
+                            serde_json::to_string(&credentials).unwrap_or(String::from("[]"));
+                        reply.return_value(&javascriptcore::Value::from_json(&context, &json));
+                    });
+                }
+                None => reply.return_value(&javascriptcore::Value::new_null(&context)),
+            }
+            true
+        },
+    );
+}
+
+/// Ask the user whether to save `credential`, unless it is already saved or its site is never saved
+pub fn offer_to_save_credential(credential: Credential, parent: &impl IsA<gtk::Widget>) {
+    if site_exception("password-exceptions", &credential.origin) == SiteException::Block {
+        return;
+    }
+    let parent = parent.clone().upcast::<gtk::Widget>();
+    relm4::spawn_local(async move {
+        let credentials = match load_credentials().await {
+            Ok(credentials) => credentials,
+            Err(error) => {
+                eprintln!("Could not load saved passwords: {error}");
+                return;
+            }
+        };
+        if credentials.contains(&credential) {
+            return;
+        }
+        let updating = credentials
+            .iter()
+            .any(|saved| saved.is_same_account(&credential));
+        let save_dialog = adw::AlertDialog::builder()
+            .heading(if updating {
+                "Update Password?"
+            } else {
+                "Save Password?"
+            })
+            .body(if credential.username.is_empty() {
+                format!(
+                    "Spidey can fill in this password the next time you log in to {}",
+                    credential.origin
+                )
+            } else {
+                format!(
+                    "Spidey can fill in the password for {} the next time you log in to {}",
+                    credential.username, credential.origin
+                )
+            })
+            .close_response("not-now")
+            .default_response("save")
+            .build();
+        save_dialog.add_responses(&[
+            ("never", "Never for This Site"),
+            ("not-now", "Not Now"),
+            ("save", if updating { "Update" } else { "Save" }),
+        ]);
+        save_dialog.set_response_appearance("save", adw::ResponseAppearance::Suggested);
+        save_dialog.connect_response(None, move |_, response| match response {
+            "save" => {
+                let credential = credential.clone();
+                relm4::spawn_local(async move {
+                    if let Err(error) = update_credentials(|credentials| {
+                        merge_credentials(credentials, vec![credential])
+                    })
+                    .await
+                    {
+                        eprintln!("Could not save password: {error}");
+                    }
+                });
+            }
+            "never" => set_site_exception(
+                "password-exceptions",
+                &credential.origin,
+                SiteException::Block,
+            ),
+            _ => {}
+        });
+        save_dialog.present(Some(&parent));
+    });
+}
+
+/// Read credentials from a CSV file exported by Spidey or another browser, which has url, username and password columns.
+/// Spidey also writes a realm column for HTTP authentication passwords.
+pub fn credentials_from_csv(csv: &str) -> Vec<Credential> {
+    let mut rows = parse_csv(csv).into_iter();
+    let Some(header) = rows.next() else {
+        return vec![];
+    };
+    let column = |names: &[&str]| {
+        header
+            .iter()
+            .position(|column| names.contains(&column.trim().to_lowercase().as_str()))
+    };
+    let (Some(url_column), Some(username_column), Some(password_column)) = (
+        column(&["url", "origin", "login_uri"]),
+        column(&["username", "login_username"]),
+        column(&["password", "login_password"]),
+    ) else {
+        return vec![];
+    };
+    let realm_column = column(&["realm"]);
+    rows.filter_map(|row| {
+        Some(Credential {
+            origin: origin_of(row.get(url_column)?)?,
+            username: row.get(username_column)?.clone(),
+            password: row
+                .get(password_column)
+                .filter(|password| !password.is_empty())?
+                .clone(),
+            realm: realm_column
+                .and_then(|realm_column| row.get(realm_column))
+                .filter(|realm| !realm.is_empty())
+                .cloned(),
+        })
+    })
+    .collect()
+}
+
+/// Write credentials as CSV that other browsers can import. Other browsers ignore the realm column.
+pub fn credentials_to_csv(credentials: &[Credential]) -> String {
+    let field = |value: &str| {
+        if value.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    };
+    let mut csv = String::from("url,username,password,realm\n");
+    for credential in credentials {
+        csv.push_str(&format!(
+            "{},{},{},{}\n",
+            field(&credential.origin),
+            field(&credential.username),
+            field(&credential.password),
+            field(credential.realm.as_deref().unwrap_or_default())
+        ));
+    }
+    csv
+}
+
+/// Split CSV into rows of fields. A field is quoted only if it starts with a quote, in which case `""` stands for a
+/// quote and commas and line breaks are part of the field; quotes anywhere else are kept as they are.
+fn parse_csv(csv: &str) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut field_started = false;
+    let mut in_quotes = false;
+    let mut chars = csv.chars().peekable();
+    while let Some(char) = chars.next() {
+        match (char, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => in_quotes = false,
+            ('"', false) if !field_started => {
+                in_quotes = true;
+                field_started = true;
+            }
+            (',', false) => {
+                row.push(std::mem::take(&mut field));
+                field_started = false;
+            }
+            ('\r', false) => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+                field_started = false;
+            }
+            (char, _) => {
+                field.push(char);
+                field_started = true;
+            }
+        }
+    }
+    if field_started || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credential(origin: &str, username: &str, password: &str) -> Credential {
+        Credential {
+            origin: origin.to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+            realm: None,
+        }
+    }
+
+    #[test]
+    fn csv_round_trips_commas_quotes_and_line_breaks() {
+        let credentials = vec![
+            credential("https://example.com", "a,b", "pa\"ss"),
+            credential(
+                "https://example.org",
+                "\"quoted\"",
+                "line\nbreak,and \"more\"",
+            ),
+            credential("http://localhost:8080", "plain", "crlf\r\nline"),
+        ];
+        assert_eq!(
+            credentials_from_csv(&credentials_to_csv(&credentials)),
+            credentials
+        );
+    }
+
+    #[test]
+    fn quotes_inside_unquoted_fields_are_kept() {
+        let csv = "url,username,password\nhttps://example.com,me,pa\"ss\nhttps://example.org,you,secret\n";
+        assert_eq!(
+            credentials_from_csv(csv),
+            vec![
+                credential("https://example.com", "me", "pa\"ss"),
+                credential("https://example.org", "you", "secret"),
+            ]
+        );
+    }
+
+    #[test]
+    fn escaped_quotes_in_quoted_fields() {
+        assert_eq!(
+            parse_csv("\"a \"\"b\"\" c\",d\n"),
+            vec![vec![String::from("a \"b\" c"), String::from("d")]]
+        );
+    }
+
+    #[test]
+    fn rows_without_a_password_or_url_are_skipped() {
+        let csv = "url,username,password\nhttps://example.com,me,\nnot a url,me,secret\nhttps://example.org,you,secret";
+        assert_eq!(
+            credentials_from_csv(csv),
+            vec![credential("https://example.org", "you", "secret")]
+        );
+    }
+
+    #[test]
+    fn csv_without_a_realm_column_is_imported() {
+        let csv = "name,url,username,password\nExample,https://example.com/login,me,secret\n";
+        assert_eq!(
+            credentials_from_csv(csv),
+            vec![credential("https://example.com", "me", "secret")]
+        );
+    }
+
+    #[test]
+    fn realm_with_commas_round_trips() {
+        let credentials = vec![Credential {
+            realm: Some(String::from("Staff, \"internal\" area")),
+            ..credential("https://intranet.example.com", "me", "secret")
+        }];
+        let csv = credentials_to_csv(&credentials);
+        assert!(csv.starts_with("url,username,password,realm\n"));
+        assert_eq!(credentials_from_csv(&csv), credentials);
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    cell::Cell,
+    fs,
+    path::PathBuf,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use chrono::offset::Utc;
+use relm4::gtk::{gio, glib, glib::clone, prelude::*};
+use url::{form_urlencoded, Url};
+use webkit6::prelude::*;
+
+use crate::downloads::{available_path, download_folder_for, DownloadRecord, DownloadState};
+
+const PDF_VIEWER_SCHEME: &str = "spidey-pdf";
+const PDFJS_RESOURCE_PATH: &str = "/com/github/kdwk/Spidey/pdfjs";
+
+static PDF_VIEWER_SCHEME_REGISTERED: AtomicBool = AtomicBool::new(false);
+
+/// A PDF WebKit downloaded to show in the viewer
+struct ViewerPdf {
+    id: String,
+    url: String,
+    path: PathBuf,
+}
+
+/// Addresses of PDFs WebKit is downloading to show in the viewer rather than to save
+static PDFS_TO_VIEW: Mutex<Vec<String>> = Mutex::new(vec![]);
+/// The PDFs the viewer can show. It can't open anything else, so pages can't use it to fetch other addresses.
+static VIEWER_PDFS: Mutex<Vec<ViewerPdf>> = Mutex::new(vec![]);
+
+/// Whether pdf.js was bundled when Spidey was built
+pub fn pdf_viewer_available() -> bool {
+    gio::resources_get_info(
+        &format!("{PDFJS_RESOURCE_PATH}/web/viewer.html"),
+        gio::ResourceLookupFlags::NONE,
+    )
+    .is_ok()
+}
+
+/// The page that shows the PDF downloaded for the viewer as `id` in pdf.js
+fn pdf_viewer_uri(id: &str) -> String {
+    let file_url = format!("{PDF_VIEWER_SCHEME}://viewer/pdf?id={id}");
+    format!(
+        "{PDF_VIEWER_SCHEME}://viewer/web/viewer.html?file={}",
+        form_urlencoded::byte_serialize(file_url.as_bytes()).collect::<String>()
+    )
+}
+
+/// The id of the PDF a request to the viewer's scheme asks for
+fn pdf_id_of(file_url: &str) -> Option<String> {
+    Url::parse(file_url)
+        .ok()?
+        .query_pairs()
+        .find(|(key, _)| key == "id")
+        .map(|(_, id)| id.to_string())
+}
+
+/// The id of the PDF being shown, if `uri` is the PDF viewer
+fn viewer_pdf_id_of(uri: &str) -> Option<String> {
+    let file_url = Url::parse(uri)
+        .ok()
+        .filter(|url| url.scheme() == PDF_VIEWER_SCHEME)?
+        .query_pairs()
+        .find(|(key, _)| key == "file")?
+        .1
+        .to_string();
+    pdf_id_of(&file_url)
+}
+
+/// The address of the PDF being shown, if `uri` is the PDF viewer
+pub fn pdf_url_of_viewer(uri: &str) -> Option<String> {
+    let id = viewer_pdf_id_of(uri)?;
+    VIEWER_PDFS
+        .lock()
+        .ok()?
+        .iter()
+        .find(|viewer_pdf| viewer_pdf.id == id)
+        .map(|viewer_pdf| viewer_pdf.url.clone())
+}
+
+/// Delete the PDF downloaded for the viewer as `id`, once the viewer showing it is closed or left
+fn forget_viewer_pdf(id: &str) {
+    let Ok(mut viewer_pdfs) = VIEWER_PDFS.lock() else {
+        return;
+    };
+    if let Some(index) = viewer_pdfs
+        .iter()
+        .position(|viewer_pdf| viewer_pdf.id == id)
+    {
+        let viewer_pdf = viewer_pdfs.remove(index);
+        if let Err(error) = fs::remove_file(&viewer_pdf.path) {
+            eprintln!("Could not delete PDF downloaded for the viewer: {error}");
+        }
+    }
+}
+
+/// Show the PDF `response_decision` is for in the viewer. WebKit downloads the response it already has, so this
+/// works for PDFs made by submitting a form and keeps everything the session sent with the request.
+pub fn view_pdf_response(response_decision: &webkit6::ResponsePolicyDecision) {
+    let urls = [
+        response_decision
+            .request()
+            .and_then(|request| request.uri()),
+        response_decision
+            .response()
+            .and_then(|response| response.uri()),
+    ];
+    if let Ok(mut pdfs_to_view) = PDFS_TO_VIEW.lock() {
+        pdfs_to_view.extend(urls.into_iter().flatten().map(|url| url.to_string()));
+    }
+    response_decision.download();
+}
+
+fn urls_of_download(download: &webkit6::Download) -> Vec<String> {
+    [
+        download.request().and_then(|request| request.uri()),
+        download.response().and_then(|response| response.uri()),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|url| url.to_string())
+    .collect()
+}
+
+fn forget_pdf_to_view(urls: &[String]) {
+    if let Ok(mut pdfs_to_view) = PDFS_TO_VIEW.lock() {
+        pdfs_to_view.retain(|pdf_url| !urls.contains(pdf_url));
+    }
+}
+
+/// Whether `download` is a PDF being downloaded for the viewer, which isn't listed with other downloads
+pub fn is_pdf_viewer_download(download: &webkit6::Download) -> bool {
+    let urls = urls_of_download(download);
+    PDFS_TO_VIEW
+        .lock()
+        .is_ok_and(|pdfs_to_view| pdfs_to_view.iter().any(|pdf_url| urls.contains(pdf_url)))
+}
+
+/// Save `download` where only the viewer reads it, then show it in the Web Window it came from
+pub fn show_download_in_pdf_viewer(download: &webkit6::Download) {
+    let urls = urls_of_download(download);
+    let id = glib::uuid_string_random().to_string();
+    let path = std::env::temp_dir().join(format!("spidey-{id}.pdf"));
+    download.set_allow_overwrite(true);
+    download.connect_decide_destination(clone!(@strong path => move |download, _| {
+        download.set_destination(&path.to_string_lossy());
+        true
+    }));
+    // "finished" is also emitted after "failed", so only show the PDF when nothing went wrong
+    let failed = Rc::new(Cell::new(false));
+    download.connect_failed(clone!(@strong failed, @strong urls => move |_, error| {
+        failed.set(true);
+        forget_pdf_to_view(&urls);
+        eprintln!("Could not download PDF: {error}");
+    }));
+    download.connect_finished(move |download| {
+        if failed.get() {
+            return;
+        }
+        forget_pdf_to_view(&urls);
+        if let Ok(mut viewer_pdfs) = VIEWER_PDFS.lock() {
+            viewer_pdfs.push(ViewerPdf {
+                id: id.clone(),
+                url: urls.last().cloned().unwrap_or_default(),
+                path: path.clone(),
+            });
+        }
+        let Some(web_view) = download.web_view() else {
+            forget_viewer_pdf(&id);
+            return;
+        };
+        web_view.load_uri(&pdf_viewer_uri(&id));
+        web_view.connect_uri_notify(clone!(@strong id => move |web_view| {
+            if web_view.uri().and_then(|uri| viewer_pdf_id_of(&uri)).as_deref() != Some(id.as_str()) {
+                forget_viewer_pdf(&id);
+            }
+        }));
+        web_view.connect_destroy(clone!(@strong id => move |_| forget_viewer_pdf(&id)));
+    });
+}
+
+/// Serve pdf.js from the bundled resources. The viewer only opens files from its own origin,
+/// so PDFs WebKit downloaded for it are passed through the same scheme.
+pub fn register_pdf_viewer_scheme(web_context: &webkit6::WebContext) {
+    if PDF_VIEWER_SCHEME_REGISTERED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    if let Some(security_manager) = web_context.security_manager() {
+        security_manager.register_uri_scheme_as_secure(PDF_VIEWER_SCHEME);
+    }
+    web_context.register_uri_scheme(PDF_VIEWER_SCHEME, |request| {
+        let path = request
+            .path()
+            .map(|path| path.to_string())
+            .unwrap_or_default();
+        if path == "/pdf" {
+            serve_pdf(request.clone());
+            return;
+        }
+        match gio::resources_lookup_data(
+            &format!("{PDFJS_RESOURCE_PATH}{path}"),
+            gio::ResourceLookupFlags::NONE,
+        ) {
+            Ok(data) => {
+                let stream = gio::MemoryInputStream::from_bytes(&data);
+                request.finish(&stream, data.len() as i64, Some(mime_type_of(&path)));
+            }
+            Err(mut error) => request.finish_error(&mut error),
+        }
+    });
+}
+
+/// pdf.js saves what it has loaded through a blob URL. Save the PDF WebKit downloaded for the viewer instead, so it
+/// is listed with the address it came from without fetching it again. Returns the saved download if it was replaced.
+pub fn replace_pdf_viewer_download(download: &webkit6::Download) -> Option<DownloadRecord> {
+    let web_view = download.web_view()?;
+    let id = web_view.uri().and_then(|uri| viewer_pdf_id_of(&uri))?;
+    let is_blob = download
+        .request()
+        .and_then(|request| request.uri())
+        .is_some_and(|uri| uri.starts_with("blob:") || uri.starts_with("data:"));
+    if !is_blob {
+        return None;
+    }
+    let (url, path) = VIEWER_PDFS
+        .lock()
+        .ok()?
+        .iter()
+        .find(|viewer_pdf| viewer_pdf.id == id)
+        .map(|viewer_pdf| (viewer_pdf.url.clone(), viewer_pdf.path.clone()))?;
+    download.cancel();
+    let mut record = DownloadRecord {
+        url,
+        source_page: None,
+        destination: None,
+        state: DownloadState::Finished,
+        started: Utc::now().timestamp(),
+        total_size: 0,
+        error: None,
+        partial: None,
+        validator: None,
+    };
+    let mut file_name = record.file_name();
+    if !file_name.to_lowercase().ends_with(".pdf") {
+        file_name.push_str(".pdf");
+    }
+    let folder = download_folder_for(&file_name, Some("application/pdf"));
+    let destination = available_path(&folder, &file_name);
+    match fs::create_dir_all(&folder).and_then(|_| fs::copy(&path, &destination)) {
+        Ok(total_size) => record.total_size = total_size,
+        Err(error) => {
+            record.state = DownloadState::Failed;
+            record.error = Some(format!("Could not save file: {error}"));
+        }
+    }
+    record.destination = Some(destination.to_string_lossy().to_string());
+    Some(record)
+}
+
+fn serve_pdf(request: webkit6::URISchemeRequest) {
+    let path = request
+        .uri()
+        .and_then(|uri| pdf_id_of(&uri))
+        .and_then(|id| {
+            VIEWER_PDFS
+                .lock()
+                .ok()?
+                .iter()
+                .find(|viewer_pdf| viewer_pdf.id == id)
+                .map(|viewer_pdf| viewer_pdf.path.clone())
+        });
+    let Some(path) = path else {
+        request.finish_error(&mut glib::Error::new(
+            gio::IOErrorEnum::NotFound,
+            "This PDF is no longer available. Open it again from the page it came from.",
+        ));
+        return;
+    };
+    let file = gio::File::for_path(&path);
+    match file.read(gio::Cancellable::NONE) {
+        Ok(stream) => {
+            let size = fs::metadata(&path)
+                .map(|metadata| metadata.len() as i64)
+                .unwrap_or(-1);
+            request.finish(&stream, size, Some("application/pdf"));
+        }
+        Err(mut error) => request.finish_error(&mut error),
+    }
+}
+
+fn mime_type_of(path: &str) -> &'static str {
+    match path.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("html") => "text/html",
+        Some("js") | Some("mjs") => "text/javascript",
+        Some("css") => "text/css",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("json") => "application/json",
+        Some("wasm") => "application/wasm",
+        Some("ftl") | Some("properties") => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
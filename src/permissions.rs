@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+
+use relm4::{adw::prelude::*, prelude::*};
+use webkit6::{glib::ToVariant, prelude::*};
+
+use crate::gsettings::gsettings;
+
+/// A kind of permission a website can ask for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PermissionKind {
+    Geolocation,
+    Notifications,
+    Camera,
+    Microphone,
+    Clipboard,
+    StorageAccess,
+}
+
+impl PermissionKind {
+    pub const ALL: [PermissionKind; 6] = [
+        PermissionKind::Geolocation,
+        PermissionKind::Notifications,
+        PermissionKind::Camera,
+        PermissionKind::Microphone,
+        PermissionKind::Clipboard,
+        PermissionKind::StorageAccess,
+    ];
+    pub fn id(&self) -> &'static str {
+        match self {
+            PermissionKind::Geolocation => "geolocation",
+            PermissionKind::Notifications => "notifications",
+            PermissionKind::Camera => "camera",
+            PermissionKind::Microphone => "microphone",
+            PermissionKind::Clipboard => "clipboard",
+            PermissionKind::StorageAccess => "storage-access",
+        }
+    }
+    pub fn from_id(id: &str) -> Option<Self> {
+        PermissionKind::ALL
+            .into_iter()
+            .find(|permission_kind| permission_kind.id() == id)
+    }
+    pub fn title(&self) -> &'static str {
+        match self {
+            PermissionKind::Geolocation => "Location",
+            PermissionKind::Notifications => "Notifications",
+            PermissionKind::Camera => "Camera",
+            PermissionKind::Microphone => "Microphone",
+            PermissionKind::Clipboard => "Clipboard",
+            PermissionKind::StorageAccess => "Cookies in Other Sites",
+        }
+    }
+    fn description(&self) -> &'static str {
+        match self {
+            PermissionKind::Geolocation => "know your location",
+            PermissionKind::Notifications => "show notifications",
+            PermissionKind::Camera => "use your camera",
+            PermissionKind::Microphone => "use your microphone",
+            PermissionKind::Clipboard => "see what you have copied",
+            PermissionKind::StorageAccess => "use its cookies while you visit other sites",
+        }
+    }
+}
+
+/// Work out which permissions a request is asking for. Requests Spidey doesn't know about are left to WebKit.
+pub fn permission_kinds(request: &webkit6::PermissionRequest) -> Vec<PermissionKind> {
+    if request.is::<webkit6::GeolocationPermissionRequest>() {
+        vec![PermissionKind::Geolocation]
+    } else if request.is::<webkit6::NotificationPermissionRequest>() {
+        vec![PermissionKind::Notifications]
+    } else if let Some(user_media_request) =
+        request.downcast_ref::<webkit6::UserMediaPermissionRequest>()
+    {
+        let mut permission_kinds = vec![];
+        if user_media_request.is_for_video_device() {
+            permission_kinds.push(PermissionKind::Camera);
+        }
+        if user_media_request.is_for_audio_device() {
+            permission_kinds.push(PermissionKind::Microphone);
+        }
+        permission_kinds
+    } else if request.is::<webkit6::ClipboardPermissionRequest>() {
+        vec![PermissionKind::Clipboard]
+    } else if request.is::<webkit6::WebsiteDataAccessPermissionRequest>() {
+        vec![PermissionKind::StorageAccess]
+    } else {
+        vec![]
+    }
+}
+
+/// Get every stored decision, keyed by origin and then by permission id
+pub fn permission_decisions() -> HashMap<String, HashMap<String, bool>> {
+    gsettings()
+        .get::<HashMap<String, HashMap<String, String>>>("permission-decisions")
+        .into_iter()
+        .map(|(origin, decisions)| {
+            (
+                origin,
+                decisions
+                    .into_iter()
+                    .map(|(permission_id, decision)| (permission_id, decision == "allow"))
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+/// Get the stored decision for all of `permission_kinds` at `origin`.
+/// Returns None if the user has to be asked, i.e. one of them has no decision and none of them is blocked.
+pub fn permission_decision(origin: &str, permission_kinds: &[PermissionKind]) -> Option<bool> {
+    let decisions = permission_decisions().remove(origin).unwrap_or_default();
+    let mut allowed = true;
+    for permission_kind in permission_kinds {
+        match decisions.get(permission_kind.id()) {
+            Some(false) => return Some(false),
+            Some(true) => {}
+            None => allowed = false,
+        }
+    }
+    if allowed {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// Remember a decision for `permission_kind` at `origin`, or forget it if `decision` is None
+pub fn set_permission_decision(
+    origin: &str,
+    permission_kind: PermissionKind,
+    decision: Option<bool>,
+) {
+    let gsettings = gsettings();
+    let mut decisions =
+        gsettings.get::<HashMap<String, HashMap<String, String>>>("permission-decisions");
+    let origin_decisions = decisions.entry(origin.to_string()).or_default();
+    match decision {
+        Some(allowed) => origin_decisions.insert(
+            permission_kind.id().to_string(),
+            String::from(if allowed { "allow" } else { "block" }),
+        ),
+        None => origin_decisions.remove(permission_kind.id()),
+    };
+    if origin_decisions.is_empty() {
+        decisions.remove(origin);
+    }
+    if let Err(error) = gsettings.set("permission-decisions", decisions.to_variant()) {
+        eprintln!("Could not update GSettings value 'permission-decisions': {error}");
+    }
+}
+
+/// Ask the user whether `origin` may have `permission_kinds`, then allow or deny `request` accordingly
+pub fn present_permission_dialog(
+    request: webkit6::PermissionRequest,
+    permission_kinds: Vec<PermissionKind>,
+    origin: String,
+    parent: &impl IsA<gtk::Widget>,
+) {
+    let description = permission_kinds
+        .iter()
+        .map(|permission_kind| permission_kind.description())
+        .collect::<Vec<&str>>()
+        .join(" and ");
+    let heading = match &request.downcast_ref::<webkit6::WebsiteDataAccessPermissionRequest>() {
+        Some(storage_access_request) => format!(
+            "Allow {} to {} such as {}?",
+            storage_access_request
+                .requesting_domain()
+                .unwrap_or_default(),
+            description,
+            storage_access_request.current_domain().unwrap_or_default()
+        ),
+        None => format!("Allow {origin} to {description}?"),
+    };
+    let permission_dialog = adw::AlertDialog::builder()
+        .heading(heading)
+        .close_response("deny")
+        .default_response("once")
+        .build();
+    permission_dialog.add_responses(&[
+        ("never", "Never Allow"),
+        ("deny", "Don't Allow"),
+        ("once", "Allow Once"),
+        ("always", "Always Allow"),
+    ]);
+    permission_dialog.set_response_appearance("never", adw::ResponseAppearance::Destructive);
+    permission_dialog.set_response_appearance("always", adw::ResponseAppearance::Suggested);
+    permission_dialog.connect_response(None, move |_, response| {
+        match response {
+            "once" | "always" => request.allow(),
+            _ => request.deny(),
+        }
+        let decision = match response {
+            "always" => Some(true),
+            "never" => Some(false),
+            _ => None,
+        };
+        if decision.is_some() {
+            for permission_kind in &permission_kinds {
+                set_permission_decision(&origin, *permission_kind, decision);
+            }
+        }
+    });
+    permission_dialog.present(Some(parent));
+}
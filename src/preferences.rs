@@ -0,0 +1,694 @@
+#![allow(unused_imports)]
+#![allow(unused_variables)]
+use relm4::{
+    adw::prelude::*,
+    gtk::{gio, glib::clone, prelude::*},
+    prelude::*,
+};
+
+use crate::cleardata::present_clear_data;
+use crate::cookiemanager::{apply_cookie_policy, present_cookie_manager};
+use crate::downloads::{
+    default_download_folder, download_rules, normalize_download_rule, set_download_rule,
+};
+use crate::gsettings::gsettings;
+use crate::mimepolicy::{
+    chosen_mime_action, chosen_mime_types, mime_action_from_index, mime_action_index,
+    mime_action_labels, set_mime_action, MimeAction, COMMON_MIME_TYPES,
+};
+use crate::passwordmanager::present_password_manager;
+use crate::permissions::{permission_decisions, set_permission_decision, PermissionKind};
+use crate::proxy::{apply_proxy_settings, is_valid_proxy_url, PROXY_MODES};
+use crate::screenshots::{screenshot_folder, SCREENSHOT_FORMATS};
+use crate::trackers::present_tracking_prevention;
+
+const COOKIE_ACCEPT_POLICIES: [(&str, &str); 3] = [
+    ("always", "Always"),
+    ("no-third-party", "Only From Sites You Visit"),
+    ("never", "Never"),
+];
+
+pub struct Preferences;
+
+#[derive(Debug)]
+pub enum PreferencesInput {
+    ShowCookieManager,
+    ShowClearData,
+    ShowTrackers,
+    ShowPasswordManager,
+    SetCookieAcceptPolicy(u32),
+    SetProxyMode(u32),
+    SetProxyUrl(String),
+    SetProxyIgnoreHosts(String),
+    ChooseDownloadFolder,
+    SetDownloadFolder(gio::File),
+    AddDownloadRule(String),
+    SetDownloadRule(String, gio::File),
+    RemoveDownloadRule(String),
+    SetMimeAction(String, u32),
+    AddMimeType(String),
+    ChooseScreenshotFolder,
+    SetScreenshotFolder(gio::File),
+    SetScreenshotFilenameTemplate(String),
+    SetScreenshotFormat(u32),
+}
+
+#[relm4::component(pub)]
+impl Component for Preferences {
+    type Init = ();
+    type Input = PreferencesInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        #[name(preferences_dialog)]
+        adw::PreferencesDialog {
+            set_title: "Preferences",
+
+            add = &adw::PreferencesPage {
+                set_title: "Privacy & Security",
+                set_icon_name: Some("padlock2"),
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Connections",
+
+                    #[name(https_only_row)]
+                    adw::SwitchRow {
+                        set_title: "HTTPS-Only Mode",
+                        set_subtitle: "Upgrade connections to HTTPS and warn before loading sites that don't support it",
+                    },
+
+                    #[name(mixed_content_blocking_row)]
+                    adw::SwitchRow {
+                        set_title: "Block Insecure Content",
+                        set_subtitle: "Don't load images, scripts and other resources over HTTP on secure pages",
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Content",
+
+                    #[name(javascript_row)]
+                    adw::SwitchRow {
+                        set_title: "JavaScript",
+                        set_subtitle: "Let sites run scripts. Individual sites can be allowed or blocked from the header bar of a Web Window.",
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Cookies",
+                    set_description: Some("Exceptions for individual sites can be set from the padlock in a Web Window"),
+
+                    adw::ComboRow {
+                        set_title: "Accept Cookies",
+                        set_model: Some(&gtk::StringList::new(
+                            &COOKIE_ACCEPT_POLICIES.map(|(_, label)| label),
+                        )),
+                        set_selected: COOKIE_ACCEPT_POLICIES
+                            .iter()
+                            .position(|(policy, _)| *policy == gsettings().string("cookie-accept-policy").as_str())
+                            .unwrap_or(1) as u32,
+                        connect_selected_notify[sender] => move |this_row| {
+                            sender.input(PreferencesInput::SetCookieAcceptPolicy(this_row.selected()));
+                        },
+                    },
+
+                    adw::ActionRow {
+                        set_title: "Manage Cookies",
+                        set_subtitle: "See and delete cookies stored by websites",
+                        set_activatable: true,
+                        add_suffix = &gtk::Image {
+                            set_icon_name: Some("go-next-symbolic"),
+                        },
+                        connect_activated => PreferencesInput::ShowCookieManager,
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Passwords",
+
+                    adw::ActionRow {
+                        set_title: "Saved Passwords",
+                        set_subtitle: "See, import and export the passwords Spidey fills in for you",
+                        set_activatable: true,
+                        add_suffix = &gtk::Image {
+                            set_icon_name: Some("go-next-symbolic"),
+                        },
+                        connect_activated => PreferencesInput::ShowPasswordManager,
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Tracking Prevention",
+
+                    adw::ActionRow {
+                        set_title: "Trackers",
+                        set_subtitle: "See which sites were found following you across the web",
+                        set_activatable: true,
+                        add_suffix = &gtk::Image {
+                            set_icon_name: Some("go-next-symbolic"),
+                        },
+                        connect_activated => PreferencesInput::ShowTrackers,
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Browsing Data",
+
+                    adw::ActionRow {
+                        set_title: "Clear Browsing Data",
+                        set_subtitle: "Remove cookies, cached files and other website data",
+                        set_activatable: true,
+                        add_suffix = &gtk::Image {
+                            set_icon_name: Some("go-next-symbolic"),
+                        },
+                        connect_activated => PreferencesInput::ShowClearData,
+                    },
+                },
+            },
+
+            add = &adw::PreferencesPage {
+                set_title: "Network",
+                set_icon_name: Some("network-wired-symbolic"),
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Proxy",
+                    set_description: Some("Custom proxies can be HTTP, HTTPS or SOCKS, e.g. socks5://localhost:1080"),
+
+                    adw::ComboRow {
+                        set_title: "Proxy",
+                        set_model: Some(&gtk::StringList::new(&PROXY_MODES.map(|(_, label)| label))),
+                        set_selected: PROXY_MODES
+                            .iter()
+                            .position(|(mode, _)| *mode == gsettings().string("proxy-mode").as_str())
+                            .unwrap_or(0) as u32,
+                        connect_selected_notify[sender] => move |this_row| {
+                            sender.input(PreferencesInput::SetProxyMode(this_row.selected()));
+                        },
+                    },
+
+                    #[name(proxy_url_row)]
+                    adw::EntryRow {
+                        set_title: "Proxy Server",
+                        set_show_apply_button: true,
+                        set_input_purpose: gtk::InputPurpose::Url,
+                        set_text: &gsettings().string("proxy-url"),
+                        connect_apply[sender] => move |this_row| {
+                            sender.input(PreferencesInput::SetProxyUrl(this_row.text().to_string()));
+                        },
+                    },
+
+                    #[name(proxy_ignore_hosts_row)]
+                    adw::EntryRow {
+                        set_title: "Hosts Without Proxy, Separated by Commas",
+                        set_show_apply_button: true,
+                        set_text: &gsettings().strv("proxy-ignore-hosts").join(", "),
+                        connect_apply[sender] => move |this_row| {
+                            sender.input(PreferencesInput::SetProxyIgnoreHosts(this_row.text().to_string()));
+                        },
+                    },
+                },
+            },
+
+            add = &adw::PreferencesPage {
+                set_title: "Downloads",
+                set_icon_name: Some("folder-download-symbolic"),
+
+                add = &adw::PreferencesGroup {
+                    #[name(download_folder_row)]
+                    adw::ActionRow {
+                        set_title: "Download Folder",
+                        set_activatable: true,
+                        add_suffix = &gtk::Image {
+                            set_icon_name: Some("folder-open-symbolic"),
+                        },
+                        connect_activated => PreferencesInput::ChooseDownloadFolder,
+                    },
+
+                    #[name(download_ask_row)]
+                    adw::SwitchRow {
+                        set_title: "Ask Where to Save Each File",
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Folders for File Types",
+                    set_description: Some("Save some kinds of files somewhere else, e.g. pdf, application/pdf or image/*"),
+
+                    #[name(download_rules_list)]
+                    gtk::ListBox {
+                        add_css_class: "boxed-list",
+                        set_selection_mode: gtk::SelectionMode::None,
+
+                        adw::EntryRow {
+                            set_title: "Add File Type",
+                            set_show_apply_button: true,
+                            connect_apply[sender] => move |this_row| {
+                                sender.input(PreferencesInput::AddDownloadRule(this_row.text().to_string()));
+                                this_row.set_text("");
+                            },
+                        },
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Opening Files",
+                    set_description: Some("What to do when a link leads to a file rather than a web page"),
+
+                    #[name(mime_policies_list)]
+                    gtk::ListBox {
+                        add_css_class: "boxed-list",
+                        set_selection_mode: gtk::SelectionMode::None,
+
+                        adw::EntryRow {
+                            set_title: "Add MIME Type, e.g. application/epub+zip",
+                            set_show_apply_button: true,
+                            connect_apply[sender] => move |this_row| {
+                                sender.input(PreferencesInput::AddMimeType(this_row.text().to_string()));
+                                this_row.set_text("");
+                            },
+                        },
+                    },
+                },
+            },
+
+            add = &adw::PreferencesPage {
+                set_title: "Screenshots",
+                set_icon_name: Some("screenshooter"),
+
+                add = &adw::PreferencesGroup {
+                    #[name(screenshot_save_row)]
+                    adw::SwitchRow {
+                        set_title: "Save to Folder",
+                    },
+
+                    #[name(screenshot_folder_row)]
+                    adw::ActionRow {
+                        set_title: "Folder",
+                        set_activatable: true,
+                        add_suffix = &gtk::Image {
+                            set_icon_name: Some("folder-open-symbolic"),
+                        },
+                        connect_activated => PreferencesInput::ChooseScreenshotFolder,
+                    },
+
+                    #[name(screenshot_copy_row)]
+                    adw::SwitchRow {
+                        set_title: "Copy to Clipboard",
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Files",
+                    set_description: Some("{title}, {domain}, {date} and {time} in the file name are replaced with the page's title, its domain and when the screenshot was taken"),
+
+                    #[name(screenshot_filename_template_row)]
+                    adw::EntryRow {
+                        set_title: "File Name",
+                        set_show_apply_button: true,
+                        set_text: &gsettings().string("screenshot-filename-template"),
+                        connect_apply[sender] => move |this_row| {
+                            sender.input(PreferencesInput::SetScreenshotFilenameTemplate(this_row.text().to_string()));
+                        },
+                    },
+
+                    adw::ComboRow {
+                        set_title: "Format",
+                        set_model: Some(&gtk::StringList::new(&SCREENSHOT_FORMATS.map(|(_, label)| label))),
+                        set_selected: SCREENSHOT_FORMATS
+                            .iter()
+                            .position(|(format, _)| *format == gsettings().string("screenshot-format").as_str())
+                            .unwrap_or(0) as u32,
+                        connect_selected_notify[sender] => move |this_row| {
+                            sender.input(PreferencesInput::SetScreenshotFormat(this_row.selected()));
+                        },
+                    },
+
+                    #[name(screenshot_quality_row)]
+                    adw::SpinRow {
+                        set_title: "Quality",
+                        set_subtitle: "Higher quality JPEG and WebP files are bigger",
+                        set_adjustment: Some(&gtk::Adjustment::new(90.0, 1.0, 100.0, 1.0, 10.0, 0.0)),
+                        set_value: gsettings().int("screenshot-quality") as f64,
+                        connect_value_notify => move |this_row| {
+                            _ = gsettings().set_int("screenshot-quality", this_row.value() as i32);
+                        },
+                    },
+                },
+            },
+
+            add = &adw::PreferencesPage {
+                set_title: "Sites",
+                set_icon_name: Some("globe-symbolic"),
+
+                #[name(site_permissions_group)]
+                add = &adw::PreferencesGroup {
+                    set_title: "Permissions",
+                    set_description: Some("What sites have been always or never allowed to do"),
+                },
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = Preferences;
+        let widgets = view_output!();
+        gsettings()
+            .bind("https-only", &widgets.https_only_row, "active")
+            .build();
+        gsettings()
+            .bind(
+                "mixed-content-blocking",
+                &widgets.mixed_content_blocking_row,
+                "active",
+            )
+            .build();
+        gsettings()
+            .bind("javascript-enabled", &widgets.javascript_row, "active")
+            .build();
+        for proxy_row in [&widgets.proxy_url_row, &widgets.proxy_ignore_hosts_row] {
+            gsettings()
+                .bind("proxy-mode", proxy_row, "sensitive")
+                .mapping(|mode, _| Some((mode.str()? == "custom").to_value()))
+                .get_only()
+                .build();
+        }
+        gsettings()
+            .bind("download-ask", &widgets.download_ask_row, "active")
+            .build();
+        widgets
+            .download_folder_row
+            .set_subtitle(&default_download_folder().to_string_lossy());
+        gsettings()
+            .bind("screenshot-save", &widgets.screenshot_save_row, "active")
+            .build();
+        gsettings()
+            .bind(
+                "screenshot-save",
+                &widgets.screenshot_folder_row,
+                "sensitive",
+            )
+            .get_only()
+            .build();
+        gsettings()
+            .bind("screenshot-copy", &widgets.screenshot_copy_row, "active")
+            .build();
+        gsettings()
+            .bind(
+                "screenshot-format",
+                &widgets.screenshot_quality_row,
+                "sensitive",
+            )
+            .mapping(|format, _| Some((format.str()? != "png").to_value()))
+            .get_only()
+            .build();
+        widgets
+            .screenshot_folder_row
+            .set_subtitle(&screenshot_folder().to_string_lossy());
+        populate_download_rules(&widgets.download_rules_list, &sender);
+        populate_mime_policies(&widgets.mime_policies_list, &sender);
+        populate_site_permissions(&widgets.site_permissions_group);
+        ComponentParts {
+            model: model,
+            widgets: widgets,
+        }
+    }
+
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::Input,
+        sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        match message {
+            PreferencesInput::ShowCookieManager => {
+                present_cookie_manager(webkit6::NetworkSession::default(), root)
+            }
+            PreferencesInput::ShowClearData => {
+                present_clear_data(webkit6::NetworkSession::default(), root)
+            }
+            PreferencesInput::ShowTrackers => {
+                present_tracking_prevention(webkit6::NetworkSession::default(), None, root)
+            }
+            PreferencesInput::ShowPasswordManager => present_password_manager(root),
+            PreferencesInput::SetCookieAcceptPolicy(index) => {
+                if let Some((policy, _)) = COOKIE_ACCEPT_POLICIES.get(index as usize) {
+                    _ = gsettings().set_string("cookie-accept-policy", policy);
+                    apply_cookie_policy(&webkit6::NetworkSession::default());
+                }
+            }
+            PreferencesInput::SetProxyMode(index) => {
+                if let Some((mode, _)) = PROXY_MODES.get(index as usize) {
+                    _ = gsettings().set_string("proxy-mode", mode);
+                    apply_proxy_settings(&webkit6::NetworkSession::default());
+                }
+            }
+            PreferencesInput::SetProxyUrl(proxy_url) => {
+                let proxy_url = proxy_url.trim();
+                if !is_valid_proxy_url(proxy_url) {
+                    root.add_toast(adw::Toast::new(
+                        "Enter a proxy like http://proxy.example.com:8080",
+                    ));
+                    return;
+                }
+                _ = gsettings().set_string("proxy-url", proxy_url);
+                apply_proxy_settings(&webkit6::NetworkSession::default());
+            }
+            PreferencesInput::SetProxyIgnoreHosts(ignore_hosts) => {
+                let ignore_hosts = ignore_hosts
+                    .split(',')
+                    .map(|host| host.trim())
+                    .filter(|host| !host.is_empty())
+                    .collect::<Vec<&str>>();
+                _ = gsettings().set_strv("proxy-ignore-hosts", ignore_hosts.as_slice());
+                apply_proxy_settings(&webkit6::NetworkSession::default());
+            }
+            PreferencesInput::ChooseDownloadFolder => {
+                choose_folder(
+                    root,
+                    "Download Folder",
+                    clone!(@strong sender => move |folder| {
+                        sender.input(PreferencesInput::SetDownloadFolder(folder));
+                    }),
+                );
+            }
+            PreferencesInput::SetDownloadFolder(folder) => {
+                let Some(path) = folder.path() else {
+                    return;
+                };
+                _ = gsettings().set_string("download-folder", &path.to_string_lossy());
+                widgets
+                    .download_folder_row
+                    .set_subtitle(&default_download_folder().to_string_lossy());
+            }
+            PreferencesInput::AddDownloadRule(file_type) => {
+                let Some(file_type) = normalize_download_rule(&file_type) else {
+                    root.add_toast(adw::Toast::new("Enter a file extension or MIME type"));
+                    return;
+                };
+                choose_folder(
+                    root,
+                    &format!("Folder for {file_type}"),
+                    clone!(@strong sender => move |folder| {
+                        sender.input(PreferencesInput::SetDownloadRule(file_type.clone(), folder));
+                    }),
+                );
+            }
+            PreferencesInput::SetDownloadRule(file_type, folder) => {
+                let Some(path) = folder.path() else {
+                    return;
+                };
+                set_download_rule(&file_type, Some(&path.to_string_lossy()));
+                populate_download_rules(&widgets.download_rules_list, &sender);
+            }
+            PreferencesInput::RemoveDownloadRule(file_type) => {
+                set_download_rule(&file_type, None);
+                populate_download_rules(&widgets.download_rules_list, &sender);
+            }
+            PreferencesInput::SetMimeAction(mime_type, index) => {
+                set_mime_action(&mime_type, mime_action_from_index(index));
+            }
+            PreferencesInput::AddMimeType(mime_type) => {
+                let mime_type = mime_type.trim().to_lowercase();
+                if !mime_type.contains('/') || mime_type.contains(char::is_whitespace) {
+                    root.add_toast(adw::Toast::new(
+                        "Enter a MIME type like application/epub+zip or video/*",
+                    ));
+                    return;
+                }
+                set_mime_action(&mime_type, Some(MimeAction::Download));
+                populate_mime_policies(&widgets.mime_policies_list, &sender);
+            }
+            PreferencesInput::ChooseScreenshotFolder => {
+                choose_folder(
+                    root,
+                    "Screenshot Folder",
+                    clone!(@strong sender => move |folder| {
+                        sender.input(PreferencesInput::SetScreenshotFolder(folder));
+                    }),
+                );
+            }
+            PreferencesInput::SetScreenshotFolder(folder) => {
+                let Some(path) = folder.path() else {
+                    return;
+                };
+                _ = gsettings().set_string("screenshot-folder", &path.to_string_lossy());
+                widgets
+                    .screenshot_folder_row
+                    .set_subtitle(&screenshot_folder().to_string_lossy());
+            }
+            PreferencesInput::SetScreenshotFilenameTemplate(template) => {
+                if template.trim().is_empty() {
+                    root.add_toast(adw::Toast::new(
+                        "Enter a file name, e.g. Screenshot from {domain} {date} {time}",
+                    ));
+                    return;
+                }
+                _ = gsettings().set_string("screenshot-filename-template", template.trim());
+            }
+            PreferencesInput::SetScreenshotFormat(index) => {
+                if let Some((format, _)) = SCREENSHOT_FORMATS.get(index as usize) {
+                    _ = gsettings().set_string("screenshot-format", format);
+                }
+            }
+        }
+    }
+}
+
+fn choose_folder(
+    root: &adw::PreferencesDialog,
+    title: &str,
+    then: impl FnOnce(gio::File) + 'static,
+) {
+    let parent_window = root.root().and_downcast::<gtk::Window>();
+    gtk::FileDialog::builder()
+        .title(title)
+        .modal(true)
+        .build()
+        .select_folder(
+            parent_window.as_ref(),
+            gio::Cancellable::NONE,
+            move |folder_result| {
+                if let Ok(folder) = folder_result {
+                    then(folder);
+                }
+            },
+        );
+}
+
+/// Show the rules after the row for adding a new one, which is always first
+fn populate_download_rules(
+    download_rules_list: &gtk::ListBox,
+    sender: &ComponentSender<Preferences>,
+) {
+    while let Some(rule_row) = download_rules_list.row_at_index(1) {
+        download_rules_list.remove(&rule_row);
+    }
+    for (file_type, folder) in download_rules() {
+        let rule_row = adw::ActionRow::builder()
+            .title(file_type.as_str())
+            .subtitle(folder.as_str())
+            .use_markup(false)
+            .build();
+        let remove_button = gtk::Button::builder()
+            .icon_name("user-trash-symbolic")
+            .tooltip_text("Remove")
+            .valign(gtk::Align::Center)
+            .css_classes(["flat", "circular"])
+            .build();
+        remove_button.connect_clicked(clone!(@strong sender => move |_| {
+            sender.input(PreferencesInput::RemoveDownloadRule(file_type.clone()));
+        }));
+        rule_row.add_suffix(&remove_button);
+        download_rules_list.append(&rule_row);
+    }
+}
+
+/// Open the preferences dialog on top of `parent`
+pub fn present_preferences(parent: &impl IsA<gtk::Widget>) {
+    let mut preferences = Preferences::builder().launch(()).detach();
+    preferences.detach_runtime();
+    preferences
+        .widgets()
+        .preferences_dialog
+        .present(Some(parent));
+}
+
+/// Show the common types and any others the user has added, after the row for adding one
+fn populate_mime_policies(
+    mime_policies_list: &gtk::ListBox,
+    sender: &ComponentSender<Preferences>,
+) {
+    while let Some(mime_policy_row) = mime_policies_list.row_at_index(1) {
+        mime_policies_list.remove(&mime_policy_row);
+    }
+    let mut mime_types = COMMON_MIME_TYPES
+        .iter()
+        .map(|(mime_type, title)| (mime_type.to_string(), title.to_string()))
+        .collect::<Vec<(String, String)>>();
+    for mime_type in chosen_mime_types() {
+        if !mime_types
+            .iter()
+            .any(|(common_mime_type, _)| *common_mime_type == mime_type)
+        {
+            mime_types.push((mime_type.clone(), mime_type));
+        }
+    }
+    for (mime_type, title) in mime_types {
+        let mime_policy_row = adw::ComboRow::builder()
+            .title(title.as_str())
+            .model(&gtk::StringList::new(&mime_action_labels()))
+            .selected(mime_action_index(chosen_mime_action(&mime_type)))
+            .build();
+        if title != mime_type {
+            mime_policy_row.set_subtitle(&mime_type);
+        }
+        mime_policy_row.connect_selected_notify(clone!(@strong sender => move |this_row| {
+            sender.input(PreferencesInput::SetMimeAction(mime_type.clone(), this_row.selected()));
+        }));
+        mime_policies_list.append(&mime_policy_row);
+    }
+}
+
+fn populate_site_permissions(site_permissions_group: &adw::PreferencesGroup) {
+    let mut decisions = permission_decisions().into_iter().collect::<Vec<_>>();
+    decisions.sort_by(|(a, _), (b, _)| a.cmp(b));
+    if decisions.is_empty() {
+        site_permissions_group.add(
+            &adw::ActionRow::builder()
+                .title("No sites have been allowed or blocked yet")
+                .build(),
+        );
+    }
+    for (origin, origin_decisions) in decisions {
+        let origin_row = adw::ExpanderRow::builder().title(origin.as_str()).build();
+        for (permission_id, allowed) in origin_decisions {
+            let Some(permission_kind) = PermissionKind::from_id(&permission_id) else {
+                continue;
+            };
+            let decision_row = adw::ComboRow::builder()
+                .title(permission_kind.title())
+                .model(&gtk::StringList::new(&["Ask", "Allow", "Block"]))
+                .selected(if allowed { 1 } else { 2 })
+                .build();
+            decision_row.connect_selected_notify(clone!(@strong origin => move |this_row| {
+                set_permission_decision(
+                    &origin,
+                    permission_kind,
+                    match this_row.selected() {
+                        1 => Some(true),
+                        2 => Some(false),
+                        _ => None,
+                    },
+                );
+            }));
+            origin_row.add_row(&decision_row);
+        }
+        site_permissions_group.add(&origin_row);
+    }
+}
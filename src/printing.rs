@@ -0,0 +1,103 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use relm4::gtk::{gio, glib, glib::clone, prelude::*};
+use webkit6::prelude::*;
+
+/// Show the GTK print dialog for the page in `web_view`
+pub fn print_page(web_view: &webkit6::WebView, parent: &impl IsA<gtk::Window>) {
+    let print_operation = webkit6::PrintOperation::new(web_view);
+    print_operation.connect_failed(|_, error| eprintln!("Could not print page: {error}"));
+    print_operation.run_dialog(Some(parent));
+}
+
+/// Calls `on_found` with the printer that writes to a file instead of paper once printers have been listed, or with
+/// None if there isn't one. Printers are listed in the background, as waiting for every print backend freezes Spidey.
+fn find_print_to_file_printer(on_found: impl FnOnce(Option<gtk::Printer>) + 'static) {
+    /// Calls `on_found` with None when GTK is done listing printers and drops the closure holding this
+    struct Search<F: FnOnce(Option<gtk::Printer>)>(Option<F>);
+    impl<F: FnOnce(Option<gtk::Printer>)> Drop for Search<F> {
+        fn drop(&mut self) {
+            if let Some(on_found) = self.0.take() {
+                on_found(None);
+            }
+        }
+    }
+    // The file backend names its printer "Print to File", translated like the rest of GTK
+    let print_to_file_names = [
+        glib::GString::from("Print to File"),
+        glib::dgettext(Some("gtk40"), "Print to File"),
+    ];
+    let search = RefCell::new(Search(Some(on_found)));
+    gtk::enumerate_printers(
+        move |printer| {
+            if !printer.is_virtual() || !print_to_file_names.contains(&printer.name()) {
+                return false;
+            }
+            if let Some(on_found) = search.borrow_mut().0.take() {
+                on_found(Some(printer.clone()));
+            }
+            true
+        },
+        false,
+    );
+}
+
+/// Ask where to save the page in `web_view` as a PDF, then write it there without the print dialog.
+/// `on_done` gets the file that was written, or why it couldn't be.
+pub fn save_page_as_pdf(
+    web_view: &webkit6::WebView,
+    title: &str,
+    parent: &impl IsA<gtk::Window>,
+    on_done: impl Fn(Result<gio::File, String>) + 'static,
+) {
+    let file_name = match title.replace(['/', '\\', ':', '\0'], "-").trim() {
+        "" => String::from("Page.pdf"),
+        title => format!("{title}.pdf"),
+    };
+    let pdf_filter = gtk::FileFilter::new();
+    pdf_filter.set_name(Some("PDF Documents"));
+    pdf_filter.add_mime_type("application/pdf");
+    let file_dialog = gtk::FileDialog::builder()
+        .title("Save as PDF")
+        .initial_name(file_name)
+        .default_filter(&pdf_filter)
+        .modal(true)
+        .build();
+    file_dialog.save(
+        Some(parent),
+        gio::Cancellable::NONE,
+        clone!(@strong web_view => move |file_result| {
+            let Ok(file) = file_result else {
+                return;
+            };
+            find_print_to_file_printer(clone!(@strong web_view => move |printer| {
+                let Some(printer) = printer else {
+                    on_done(Err(String::from("Printing to a file is not available")));
+                    return;
+                };
+                let print_settings = gtk::PrintSettings::new();
+                print_settings.set_printer(&printer.name());
+                print_settings.set(gtk::PRINT_SETTINGS_OUTPUT_FILE_FORMAT, Some("pdf"));
+                print_settings.set(gtk::PRINT_SETTINGS_OUTPUT_URI, Some(&file.uri()));
+                let print_operation = webkit6::PrintOperation::new(&web_view);
+                print_operation.set_print_settings(&print_settings);
+                let on_done = Rc::new(on_done);
+                // "finished" is also emitted after "failed", so only report success when nothing went wrong
+                let failed = Rc::new(Cell::new(false));
+                print_operation.connect_failed(clone!(@strong on_done, @strong failed => move |_, error| {
+                    failed.set(true);
+                    on_done(Err(error.to_string()));
+                }));
+                print_operation.connect_finished(clone!(@strong on_done, @strong file => move |_| {
+                    if !failed.get() {
+                        on_done(Ok(file.clone()));
+                    }
+                }));
+                print_operation.print();
+            }));
+        }),
+    );
+}
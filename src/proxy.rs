@@ -0,0 +1,86 @@
+use url::Url;
+use webkit6::{gio, prelude::*};
+
+use crate::gsettings::gsettings;
+
+/// Ways Spidey can connect through a proxy, as (value of the "proxy-mode" GSettings key, label)
+pub const PROXY_MODES: [(&str, &str); 3] = [
+    ("system", "System Default"),
+    ("none", "No Proxy"),
+    ("custom", "Custom"),
+];
+
+/// Check that `proxy_url` is something WebKit can connect through, e.g. `http://proxy.example.com:8080`
+pub fn is_valid_proxy_url(proxy_url: &str) -> bool {
+    Url::parse(proxy_url).is_ok_and(|url| {
+        url.host_str().is_some()
+            && ["http", "https", "socks", "socks4", "socks4a", "socks5"].contains(&url.scheme())
+    })
+}
+
+/// Make `session` connect through the proxy chosen in the preferences
+pub fn apply_proxy_settings(session: &webkit6::NetworkSession) {
+    let gsettings = gsettings();
+    match gsettings.string("proxy-mode").as_str() {
+        "none" => session.set_proxy_settings(webkit6::NetworkProxyMode::NoProxy, None),
+        "custom" => {
+            let proxy_url = gsettings.string("proxy-url");
+            if !is_valid_proxy_url(&proxy_url) {
+                eprintln!("Not using invalid proxy '{proxy_url}'");
+                session.set_proxy_settings(webkit6::NetworkProxyMode::Default, None);
+                return;
+            }
+            let ignore_hosts = gsettings.strv("proxy-ignore-hosts");
+            let ignore_hosts = ignore_hosts
+                .iter()
+                .map(|host| host.as_str())
+                .collect::<Vec<&str>>();
+            let mut proxy_settings =
+                webkit6::NetworkProxySettings::new(Some(&proxy_url), &ignore_hosts);
+            session
+                .set_proxy_settings(webkit6::NetworkProxyMode::Custom, Some(&mut proxy_settings));
+        }
+        _ => session.set_proxy_settings(webkit6::NetworkProxyMode::Default, None),
+    }
+}
+
+/// Make a client for requests Spidey sends itself, e.g. to resume a download, that connects through the same proxy as
+/// Web Windows. The system proxy is looked up for `url`, which can block, so call this off the main thread.
+/// Fails rather than connecting directly if the proxy can't be used.
+pub fn proxied_client(url: &str) -> Result<reqwest::blocking::Client, String> {
+    let client_builder = reqwest::blocking::Client::builder();
+    let gsettings = gsettings();
+    let client_builder = match gsettings.string("proxy-mode").as_str() {
+        "none" => client_builder.no_proxy(),
+        "custom" => {
+            let proxy_url = gsettings.string("proxy-url");
+            if !is_valid_proxy_url(&proxy_url) {
+                return Err(format!("Invalid proxy '{proxy_url}'"));
+            }
+            let ignore_hosts = gsettings
+                .strv("proxy-ignore-hosts")
+                .iter()
+                .map(|host| host.to_string())
+                .collect::<Vec<String>>()
+                .join(",");
+            let proxy = reqwest::Proxy::all(proxy_url.as_str())
+                .map_err(|error| format!("Could not use proxy: {error}"))?
+                .no_proxy(reqwest::NoProxy::from_string(&ignore_hosts));
+            client_builder.proxy(proxy)
+        }
+        // GIO follows the desktop's proxy settings, like WebKit does
+        _ => match gio::ProxyResolver::default()
+            .lookup(url, gio::Cancellable::NONE)
+            .map_err(|error| format!("Could not look up proxy: {error}"))?
+            .first()
+            .filter(|proxy| proxy.as_str() != "direct://")
+        {
+            Some(proxy) => client_builder.proxy(
+                reqwest::Proxy::all(proxy.as_str())
+                    .map_err(|error| format!("Could not use proxy: {error}"))?,
+            ),
+            None => client_builder.no_proxy(),
+        },
+    };
+    client_builder.build().map_err(|error| error.to_string())
+}
@@ -0,0 +1,261 @@
+use std::{fs, path::Path};
+
+use chrono::{offset::Utc, DateTime, Local};
+use documents::prelude::*;
+use relm4::{
+    gtk::{gio, glib::clone, prelude::*},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use webkit6::prelude::*;
+
+use crate::app::{AppInput, APP_BROKER};
+use crate::downloads::default_download_folder;
+use crate::sitesettings::host_of;
+
+/// A page saved as an MHTML archive, so it can be opened again without a connection
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedPage {
+    pub title: String,
+    pub url: String,
+    pub path: String,
+    /// Unix timestamp of when the page was saved
+    pub saved: i64,
+}
+
+impl SavedPage {
+    pub fn exists(&self) -> bool {
+        Path::new(&self.path).is_file()
+    }
+}
+
+/// Ask where to save the page in `web_view`, then write it there as a self-contained MHTML archive.
+/// The saved page is added to the list in the main window.
+pub fn save_page(
+    web_view: &webkit6::WebView,
+    title: &str,
+    parent: &impl IsA<gtk::Window>,
+    on_done: impl Fn(Result<SavedPage, String>) + 'static,
+) {
+    let file_name = match title.replace(['/', '\\', ':', '\0'], "-").trim() {
+        "" => String::from("Page.mhtml"),
+        title => format!("{title}.mhtml"),
+    };
+    let mhtml_filter = gtk::FileFilter::new();
+    mhtml_filter.set_name(Some("Web Archives"));
+    mhtml_filter.add_suffix("mhtml");
+    mhtml_filter.add_suffix("mht");
+    let file_dialog = gtk::FileDialog::builder()
+        .title("Save Page")
+        .initial_name(file_name)
+        .default_filter(&mhtml_filter)
+        .modal(true)
+        .build();
+    let folder = default_download_folder();
+    if folder.is_dir() {
+        file_dialog.set_initial_folder(Some(&gio::File::for_path(&folder)));
+    }
+    let title = title.to_string();
+    let url = web_view
+        .uri()
+        .map(|uri| uri.to_string())
+        .unwrap_or_default();
+    file_dialog.save(
+        Some(parent),
+        gio::Cancellable::NONE,
+        clone!(@strong web_view => move |file_result| {
+            let Some(path) = file_result.ok().and_then(|file| file.path()) else {
+                return;
+            };
+            web_view.save_to_file(
+                &gio::File::for_path(&path),
+                webkit6::SaveMode::Mhtml,
+                gio::Cancellable::NONE,
+                move |save_result| match save_result {
+                    Ok(_) => {
+                        let saved_page = SavedPage {
+                            title,
+                            url,
+                            path: path.to_string_lossy().to_string(),
+                            saved: Utc::now().timestamp(),
+                        };
+                        APP_BROKER.send(AppInput::PageSaved(saved_page.clone()));
+                        on_done(Ok(saved_page));
+                    }
+                    Err(error) => on_done(Err(error.to_string())),
+                },
+            );
+        }),
+    );
+}
+
+fn with_saved_pages_json(closure: impl FnOnce(String)) {
+    let mut closure = Some(closure);
+    with(
+        &[Document::at(
+            Project(Data(&[]).with_id("com", "github.kdwk", "Spidey")),
+            "saved-pages.json",
+            Create::No,
+        )],
+        |d| {
+            if let Some(closure) = closure.take() {
+                closure(d["saved-pages.json"].path());
+            }
+            Ok(())
+        },
+    );
+}
+
+pub fn load_saved_pages() -> Vec<SavedPage> {
+    let mut saved_pages: Vec<SavedPage> = vec![];
+    with_saved_pages_json(|path| {
+        if let Ok(json) = fs::read_to_string(path) {
+            saved_pages = serde_json::from_str(&json).unwrap_or_default();
+        }
+    });
+    saved_pages
+}
+
+pub fn save_saved_pages(saved_pages: &[SavedPage]) {
+    with_saved_pages_json(|path| {
+        let result = serde_json::to_string(saved_pages)
+            .map_err(|error| error.to_string())
+            .and_then(|json| fs::write(path, json).map_err(|error| error.to_string()));
+        if let Err(error) = result {
+            eprintln!("Could not save the list of saved pages: {error}");
+        }
+    });
+}
+
+pub struct SavedPageRow {
+    id: DynamicIndex,
+    pub saved_page: SavedPage,
+}
+
+#[derive(Debug)]
+pub enum SavedPageRowInput {
+    Open,
+    ShowInFolder,
+    Remove,
+}
+
+#[derive(Debug)]
+pub enum SavedPageRowOutput {
+    Open(gio::File),
+    Remove(DynamicIndex),
+}
+
+#[relm4::factory(pub)]
+impl FactoryComponent for SavedPageRow {
+    type Init = SavedPage;
+    type Input = SavedPageRowInput;
+    type Output = SavedPageRowOutput;
+    type CommandOutput = ();
+    type ParentWidget = gtk::ListBox;
+
+    view! {
+        gtk::Box {
+            set_orientation: gtk::Orientation::Horizontal,
+            set_spacing: 6,
+            set_margin_all: 6,
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_hexpand: true,
+                set_valign: gtk::Align::Center,
+
+                gtk::Label {
+                    set_xalign: 0.0,
+                    set_ellipsize: gtk::pango::EllipsizeMode::End,
+                    set_label: &self.saved_page.title,
+                },
+
+                gtk::Label {
+                    add_css_class: "caption",
+                    add_css_class: "dim-label",
+                    set_xalign: 0.0,
+                    set_ellipsize: gtk::pango::EllipsizeMode::End,
+                    set_label: &self.describe(),
+                },
+            },
+
+            gtk::Button {
+                set_icon_name: "document-open-symbolic",
+                set_tooltip_text: Some("Open"),
+                set_valign: gtk::Align::Center,
+                add_css_class: "flat",
+                add_css_class: "circular",
+                set_sensitive: self.saved_page.exists(),
+                connect_clicked => SavedPageRowInput::Open,
+            },
+
+            gtk::Button {
+                set_icon_name: "folder-open-symbolic",
+                set_tooltip_text: Some("Show in folder"),
+                set_valign: gtk::Align::Center,
+                add_css_class: "flat",
+                add_css_class: "circular",
+                set_sensitive: self.saved_page.exists(),
+                connect_clicked => SavedPageRowInput::ShowInFolder,
+            },
+
+            gtk::Button {
+                set_icon_name: "cross",
+                set_tooltip_text: Some("Remove from list"),
+                set_valign: gtk::Align::Center,
+                add_css_class: "flat",
+                add_css_class: "circular",
+                connect_clicked => SavedPageRowInput::Remove,
+            },
+        }
+    }
+
+    fn init_model(init: Self::Init, index: &Self::Index, _sender: FactorySender<Self>) -> Self {
+        Self {
+            id: index.clone(),
+            saved_page: init,
+        }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: FactorySender<Self>) {
+        match message {
+            SavedPageRowInput::Open => {
+                _ = sender.output(SavedPageRowOutput::Open(gio::File::for_path(
+                    &self.saved_page.path,
+                )));
+            }
+            SavedPageRowInput::ShowInFolder => {
+                gtk::FileLauncher::new(Some(&gio::File::for_path(&self.saved_page.path)))
+                    .open_containing_folder(gtk::Window::NONE, gio::Cancellable::NONE, |result| {
+                        if let Err(error) = result {
+                            eprintln!("Could not show saved page in folder: {error}");
+                        }
+                    });
+            }
+            SavedPageRowInput::Remove => {
+                _ = sender.output(SavedPageRowOutput::Remove(self.id.clone()));
+            }
+        }
+    }
+}
+
+impl SavedPageRow {
+    fn describe(&self) -> String {
+        if !self.saved_page.exists() {
+            return String::from("File was moved or deleted");
+        }
+        let mut description = vec![];
+        if let Some(host) = host_of(&self.saved_page.url) {
+            description.push(host);
+        }
+        if let Some(saved) = DateTime::from_timestamp(self.saved_page.saved, 0) {
+            description.push(
+                saved
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string(),
+            );
+        }
+        description.join(" · ")
+    }
+}
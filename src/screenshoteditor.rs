@@ -0,0 +1,577 @@
+#![allow(unused_imports)]
+#![allow(unused_variables)]
+use std::{cell::RefCell, f64::consts::PI, rc::Rc};
+
+use relm4::{
+    adw::prelude::*,
+    gtk::{cairo, gdk, gdk_pixbuf, glib, glib::clone, prelude::*},
+    prelude::*,
+};
+
+use crate::screenshots::{deliver_screenshot, describe_delivered_screenshot};
+use crate::whoops::{attempt, Catch, IntoWhoops, Whoops};
+
+/// How many pixels wide each block of the pixelate tool is
+const PIXELATE_BLOCK_SIZE: f64 = 12.0;
+/// Cairo can't make images wider or taller than this
+const MAX_IMAGE_SIZE: i32 = 32767;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnnotationTool {
+    Arrow,
+    Rectangle,
+    Text,
+    Highlighter,
+    Pixelate,
+}
+
+impl AnnotationTool {
+    const ALL: [AnnotationTool; 5] = [
+        AnnotationTool::Arrow,
+        AnnotationTool::Rectangle,
+        AnnotationTool::Text,
+        AnnotationTool::Highlighter,
+        AnnotationTool::Pixelate,
+    ];
+    fn icon_name(&self) -> &'static str {
+        match self {
+            AnnotationTool::Arrow => "arrow3-right",
+            AnnotationTool::Rectangle => "checkbox-symbolic",
+            AnnotationTool::Text => "insert-text-symbolic",
+            AnnotationTool::Highlighter => "document-edit-symbolic",
+            AnnotationTool::Pixelate => "view-grid-symbolic",
+        }
+    }
+    fn title(&self) -> &'static str {
+        match self {
+            AnnotationTool::Arrow => "Arrow",
+            AnnotationTool::Rectangle => "Rectangle",
+            AnnotationTool::Text => "Text",
+            AnnotationTool::Highlighter => "Highlighter",
+            AnnotationTool::Pixelate => "Pixelate",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Annotation {
+    Arrow {
+        from: (f64, f64),
+        to: (f64, f64),
+        color: gdk::RGBA,
+    },
+    Rectangle {
+        from: (f64, f64),
+        to: (f64, f64),
+        color: gdk::RGBA,
+    },
+    Text {
+        at: (f64, f64),
+        text: String,
+        color: gdk::RGBA,
+    },
+    Highlight {
+        points: Vec<(f64, f64)>,
+        color: gdk::RGBA,
+    },
+    Pixelate {
+        from: (f64, f64),
+        to: (f64, f64),
+    },
+}
+
+/// The screenshot and everything drawn over it, shared with the drawing area's draw function
+pub struct Canvas {
+    image: cairo::ImageSurface,
+    /// The screenshot shrunk by `PIXELATE_BLOCK_SIZE`, which is blown back up to pixelate part of it
+    pixelated: cairo::ImageSurface,
+    annotations: Vec<Annotation>,
+    annotation_in_progress: Option<Annotation>,
+    /// How the screenshot was last fitted into the drawing area
+    scale: f64,
+    offset: (f64, f64),
+}
+
+/// Shrink `texture` to a size cairo can draw on, e.g. a screenshot of a very long page
+fn fit_image_size(texture: &gdk::Texture) -> Option<gdk::Texture> {
+    let scale = MAX_IMAGE_SIZE as f64 / texture.width().max(texture.height()) as f64;
+    if scale >= 1.0 {
+        return Some(texture.clone());
+    }
+    let mut texture_downloader = gdk::TextureDownloader::new(texture);
+    texture_downloader.set_format(gdk::MemoryFormat::R8g8b8a8);
+    let (pixels, stride) = texture_downloader.download_bytes();
+    let pixbuf = gdk_pixbuf::Pixbuf::from_bytes(
+        &pixels,
+        gdk_pixbuf::Colorspace::Rgb,
+        true,
+        8,
+        texture.width(),
+        texture.height(),
+        stride as i32,
+    )
+    .scale_simple(
+        ((texture.width() as f64 * scale) as i32).max(1),
+        ((texture.height() as f64 * scale) as i32).max(1),
+        gdk_pixbuf::InterpType::Bilinear,
+    )?;
+    Some(
+        gdk::MemoryTexture::new(
+            pixbuf.width(),
+            pixbuf.height(),
+            gdk::MemoryFormat::R8g8b8a8,
+            &pixbuf.read_pixel_bytes(),
+            pixbuf.rowstride() as usize,
+        )
+        .upcast(),
+    )
+}
+
+impl Canvas {
+    fn new(texture: &gdk::Texture) -> Option<Self> {
+        let texture = &fit_image_size(texture)?;
+        let mut texture_downloader = gdk::TextureDownloader::new(texture);
+        // The same layout as cairo's ARGB32 on little-endian machines
+        texture_downloader.set_format(gdk::MemoryFormat::B8g8r8a8Premultiplied);
+        let (pixels, stride) = texture_downloader.download_bytes();
+        let image = cairo::ImageSurface::create_for_data(
+            pixels.to_vec(),
+            cairo::Format::ARgb32,
+            texture.width(),
+            texture.height(),
+            stride as i32,
+        )
+        .ok()?;
+        let pixelated = cairo::ImageSurface::create(
+            cairo::Format::ARgb32,
+            (texture.width() as f64 / PIXELATE_BLOCK_SIZE).ceil() as i32,
+            (texture.height() as f64 / PIXELATE_BLOCK_SIZE).ceil() as i32,
+        )
+        .ok()?;
+        {
+            let cairo_context = cairo::Context::new(&pixelated).ok()?;
+            cairo_context.scale(1.0 / PIXELATE_BLOCK_SIZE, 1.0 / PIXELATE_BLOCK_SIZE);
+            cairo_context.set_source_surface(&image, 0.0, 0.0).ok()?;
+            cairo_context.source().set_filter(cairo::Filter::Good);
+            cairo_context.paint().ok()?;
+        }
+        Some(Self {
+            image,
+            pixelated,
+            annotations: vec![],
+            annotation_in_progress: None,
+            scale: 1.0,
+            offset: (0.0, 0.0),
+        })
+    }
+    /// Turn a point in the drawing area into a point on the screenshot
+    fn to_image(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            (x - self.offset.0) / self.scale,
+            (y - self.offset.1) / self.scale,
+        )
+    }
+    /// Lines get thicker on bigger screenshots so they look the same when fitted to the window
+    fn line_width(&self) -> f64 {
+        (self.image.width().max(self.image.height()) as f64 / 250.0).max(3.0)
+    }
+    fn draw(&self, cairo_context: &cairo::Context) -> Result<(), cairo::Error> {
+        cairo_context.set_source_surface(&self.image, 0.0, 0.0)?;
+        cairo_context.paint()?;
+        let line_width = self.line_width();
+        for annotation in self.annotations.iter().chain(&self.annotation_in_progress) {
+            cairo_context.save()?;
+            cairo_context.set_line_width(line_width);
+            cairo_context.set_line_cap(cairo::LineCap::Round);
+            cairo_context.set_line_join(cairo::LineJoin::Round);
+            match annotation {
+                Annotation::Arrow { from, to, color } => {
+                    set_source_color(cairo_context, color, 1.0);
+                    let angle = (to.1 - from.1).atan2(to.0 - from.0);
+                    let head_length = line_width * 5.0;
+                    cairo_context.move_to(from.0, from.1);
+                    cairo_context.line_to(to.0, to.1);
+                    for side in [-1.0, 1.0] {
+                        cairo_context.move_to(to.0, to.1);
+                        cairo_context.line_to(
+                            to.0 - head_length * (angle + side * PI / 7.0).cos(),
+                            to.1 - head_length * (angle + side * PI / 7.0).sin(),
+                        );
+                    }
+                    cairo_context.stroke()?;
+                }
+                Annotation::Rectangle { from, to, color } => {
+                    set_source_color(cairo_context, color, 1.0);
+                    let (x, y, width, height) = rectangle_between(*from, *to);
+                    cairo_context.rectangle(x, y, width, height);
+                    cairo_context.stroke()?;
+                }
+                Annotation::Text { at, text, color } => {
+                    set_source_color(cairo_context, color, 1.0);
+                    cairo_context.select_font_face(
+                        "Sans",
+                        cairo::FontSlant::Normal,
+                        cairo::FontWeight::Bold,
+                    );
+                    cairo_context.set_font_size(line_width * 8.0);
+                    cairo_context.move_to(at.0, at.1);
+                    cairo_context.show_text(text)?;
+                }
+                Annotation::Highlight { points, color } => {
+                    set_source_color(cairo_context, color, 0.4);
+                    cairo_context.set_line_width(line_width * 6.0);
+                    for (index, (x, y)) in points.iter().enumerate() {
+                        if index == 0 {
+                            cairo_context.move_to(*x, *y);
+                        } else {
+                            cairo_context.line_to(*x, *y);
+                        }
+                    }
+                    cairo_context.stroke()?;
+                }
+                Annotation::Pixelate { from, to } => {
+                    let (x, y, width, height) = rectangle_between(*from, *to);
+                    cairo_context.rectangle(x, y, width, height);
+                    cairo_context.clip();
+                    cairo_context.scale(PIXELATE_BLOCK_SIZE, PIXELATE_BLOCK_SIZE);
+                    cairo_context.set_source_surface(&self.pixelated, 0.0, 0.0)?;
+                    cairo_context.source().set_filter(cairo::Filter::Nearest);
+                    cairo_context.paint()?;
+                }
+            }
+            cairo_context.restore()?;
+        }
+        Ok(())
+    }
+    /// The screenshot with the annotations drawn in, at its full size
+    fn render(&self) -> Option<gdk::Texture> {
+        let surface = cairo::ImageSurface::create(
+            cairo::Format::ARgb32,
+            self.image.width(),
+            self.image.height(),
+        )
+        .ok()?;
+        {
+            let cairo_context = cairo::Context::new(&surface).ok()?;
+            self.draw(&cairo_context).ok()?;
+        }
+        surface.flush();
+        let (width, height, stride) = (surface.width(), surface.height(), surface.stride());
+        let pixels = surface.take_data().ok()?.to_vec();
+        Some(
+            gdk::MemoryTexture::new(
+                width,
+                height,
+                gdk::MemoryFormat::B8g8r8a8Premultiplied,
+                &glib::Bytes::from_owned(pixels),
+                stride as usize,
+            )
+            .upcast(),
+        )
+    }
+}
+
+fn set_source_color(cairo_context: &cairo::Context, color: &gdk::RGBA, alpha: f64) {
+    cairo_context.set_source_rgba(
+        color.red() as f64,
+        color.green() as f64,
+        color.blue() as f64,
+        color.alpha() as f64 * alpha,
+    );
+}
+
+fn rectangle_between(from: (f64, f64), to: (f64, f64)) -> (f64, f64, f64, f64) {
+    (
+        from.0.min(to.0),
+        from.1.min(to.1),
+        (to.0 - from.0).abs(),
+        (to.1 - from.1).abs(),
+    )
+}
+
+pub struct ScreenshotEditor {
+    canvas: Rc<RefCell<Canvas>>,
+    tool: AnnotationTool,
+    color: gdk::RGBA,
+    title: String,
+    url: String,
+}
+
+#[derive(Debug)]
+pub enum ScreenshotEditorInput {
+    SetTool(AnnotationTool),
+    SetColor(gdk::RGBA),
+    DragBegin(f64, f64),
+    DragUpdate(f64, f64),
+    DragEnd,
+    AddText((f64, f64), String),
+    Undo,
+    Save,
+}
+
+#[relm4::component(pub)]
+impl Component for ScreenshotEditor {
+    type Init = (Canvas, String, String);
+    type Input = ScreenshotEditorInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        #[name(screenshot_editor_dialog)]
+        adw::Dialog {
+            set_title: "Edit Screenshot",
+            set_content_width: 900,
+            set_content_height: 650,
+
+            #[wrap(Some)]
+            set_child = &adw::ToolbarView {
+                add_top_bar = &adw::HeaderBar {
+                    #[name(tools_box)]
+                    pack_start = &gtk::Box {
+                        add_css_class: "linked",
+                    },
+
+                    pack_start = &gtk::ColorDialogButton {
+                        set_tooltip_text: Some("Colour"),
+                        set_dialog: &gtk::ColorDialog::new(),
+                        set_rgba: &model.color,
+                        connect_rgba_notify[sender] => move |this_button| {
+                            sender.input(ScreenshotEditorInput::SetColor(this_button.rgba()));
+                        },
+                    },
+
+                    pack_start = &gtk::Button {
+                        set_icon_name: "edit-undo-symbolic",
+                        set_tooltip_text: Some("Undo"),
+                        connect_clicked => ScreenshotEditorInput::Undo,
+                    },
+
+                    pack_end = &gtk::Button {
+                        set_label: "Save",
+                        add_css_class: "suggested-action",
+                        connect_clicked => ScreenshotEditorInput::Save,
+                    },
+                },
+
+                #[wrap(Some)]
+                #[name(toast_overlay)]
+                set_content = &adw::ToastOverlay {
+                    #[name(drawing_area)]
+                    gtk::DrawingArea {
+                        set_hexpand: true,
+                        set_vexpand: true,
+                        set_margin_all: 12,
+                        set_cursor_from_name: Some("crosshair"),
+                    }
+                }
+            }
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let (canvas, title, url) = init;
+        let model = ScreenshotEditor {
+            canvas: Rc::new(RefCell::new(canvas)),
+            tool: AnnotationTool::Arrow,
+            color: gdk::RGBA::new(0.88, 0.11, 0.14, 1.0),
+            title,
+            url,
+        };
+        let widgets = view_output!();
+
+        let mut first_tool_button: Option<gtk::ToggleButton> = None;
+        for tool in AnnotationTool::ALL {
+            let tool_button = gtk::ToggleButton::builder()
+                .icon_name(tool.icon_name())
+                .tooltip_text(tool.title())
+                .active(tool == model.tool)
+                .build();
+            tool_button.set_group(first_tool_button.as_ref());
+            tool_button.connect_toggled(clone!(@strong sender => move |this_button| {
+                if this_button.is_active() {
+                    sender.input(ScreenshotEditorInput::SetTool(tool));
+                }
+            }));
+            widgets.tools_box.append(&tool_button);
+            first_tool_button.get_or_insert(tool_button);
+        }
+
+        widgets.drawing_area.set_draw_func(
+            clone!(@strong model.canvas as canvas => move |_, cairo_context, width, height| {
+                let mut canvas = canvas.borrow_mut();
+                // Fit the screenshot in the middle of the drawing area without enlarging it
+                let (image_width, image_height) = (canvas.image.width() as f64, canvas.image.height() as f64);
+                canvas.scale = (width as f64 / image_width).min(height as f64 / image_height).min(1.0);
+                canvas.offset = (
+                    (width as f64 - image_width * canvas.scale) / 2.0,
+                    (height as f64 - image_height * canvas.scale) / 2.0,
+                );
+                cairo_context.translate(canvas.offset.0, canvas.offset.1);
+                cairo_context.scale(canvas.scale, canvas.scale);
+                if let Err(error) = canvas.draw(cairo_context) {
+                    eprintln!("Could not draw screenshot: {error}");
+                }
+            }),
+        );
+        let drag_gesture = gtk::GestureDrag::new();
+        drag_gesture.connect_drag_begin(clone!(@strong sender => move |_, x, y| {
+            sender.input(ScreenshotEditorInput::DragBegin(x, y));
+        }));
+        drag_gesture.connect_drag_update(clone!(@strong sender => move |this_gesture, offset_x, offset_y| {
+            if let Some((start_x, start_y)) = this_gesture.start_point() {
+                sender.input(ScreenshotEditorInput::DragUpdate(start_x + offset_x, start_y + offset_y));
+            }
+        }));
+        drag_gesture.connect_drag_end(clone!(@strong sender => move |_, _, _| {
+            sender.input(ScreenshotEditorInput::DragEnd);
+        }));
+        widgets.drawing_area.add_controller(drag_gesture);
+
+        ComponentParts {
+            model: model,
+            widgets: widgets,
+        }
+    }
+
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::Input,
+        sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        attempt(|| {
+            match message {
+                ScreenshotEditorInput::SetTool(tool) => self.tool = tool,
+                ScreenshotEditorInput::SetColor(color) => self.color = color,
+                ScreenshotEditorInput::DragBegin(x, y) => {
+                    let mut canvas = self.canvas.borrow_mut();
+                    let point = canvas.to_image(x, y);
+                    let color = self.color;
+                    canvas.annotation_in_progress = match self.tool {
+                        AnnotationTool::Arrow => Some(Annotation::Arrow {
+                            from: point,
+                            to: point,
+                            color,
+                        }),
+                        AnnotationTool::Rectangle => Some(Annotation::Rectangle {
+                            from: point,
+                            to: point,
+                            color,
+                        }),
+                        AnnotationTool::Highlighter => Some(Annotation::Highlight {
+                            points: vec![point],
+                            color,
+                        }),
+                        AnnotationTool::Pixelate => Some(Annotation::Pixelate {
+                            from: point,
+                            to: point,
+                        }),
+                        AnnotationTool::Text => {
+                            ask_for_text(
+                                root,
+                                clone!(@strong sender => move |text| {
+                                    sender.input(ScreenshotEditorInput::AddText(point, text));
+                                }),
+                            );
+                            None
+                        }
+                    };
+                }
+                ScreenshotEditorInput::DragUpdate(x, y) => {
+                    let mut canvas = self.canvas.borrow_mut();
+                    let point = canvas.to_image(x, y);
+                    match &mut canvas.annotation_in_progress {
+                        Some(Annotation::Arrow { to, .. })
+                        | Some(Annotation::Rectangle { to, .. })
+                        | Some(Annotation::Pixelate { to, .. }) => *to = point,
+                        Some(Annotation::Highlight { points, .. }) => points.push(point),
+                        Some(Annotation::Text { .. }) | None => {}
+                    }
+                }
+                ScreenshotEditorInput::DragEnd => {
+                    let mut canvas = self.canvas.borrow_mut();
+                    if let Some(annotation) = canvas.annotation_in_progress.take() {
+                        canvas.annotations.push(annotation);
+                    }
+                }
+                ScreenshotEditorInput::AddText(at, text) => {
+                    let color = self.color;
+                    self.canvas
+                        .borrow_mut()
+                        .annotations
+                        .push(Annotation::Text { at, text, color });
+                }
+                ScreenshotEditorInput::Undo => {
+                    self.canvas.borrow_mut().annotations.pop();
+                }
+                ScreenshotEditorInput::Save => {
+                    let texture = self.canvas.borrow().render()?;
+                    match deliver_screenshot(&texture, &self.title, &self.url, root) {
+                        Ok(path) => {
+                            if let Some(description) = describe_delivered_screenshot(path.as_ref())
+                            {
+                                widgets
+                                    .toast_overlay
+                                    .add_toast(adw::Toast::new(&description));
+                            }
+                        }
+                        Err(error) => widgets.toast_overlay.add_toast(adw::Toast::new(&format!(
+                            "Could not save screenshot: {error}"
+                        ))),
+                    }
+                }
+            }
+            widgets.drawing_area.queue_draw();
+            Some(())
+        })
+        .catch(|error| eprintln!("{error}"));
+    }
+}
+
+fn ask_for_text(parent: &adw::Dialog, then: impl Fn(String) + 'static) {
+    let text_row = adw::EntryRow::builder().title("Text").build();
+    let text_list = gtk::ListBox::builder()
+        .css_classes(["boxed-list"])
+        .selection_mode(gtk::SelectionMode::None)
+        .build();
+    text_list.append(&text_row);
+    let text_dialog = adw::AlertDialog::builder()
+        .heading("Add Text")
+        .extra_child(&text_list)
+        .close_response("cancel")
+        .default_response("add")
+        .build();
+    text_dialog.add_responses(&[("cancel", "Cancel"), ("add", "Add")]);
+    text_dialog.set_response_appearance("add", adw::ResponseAppearance::Suggested);
+    text_dialog.connect_response(None, move |_, response| {
+        let text = text_row.text().trim().to_string();
+        if response == "add" && !text.is_empty() {
+            then(text);
+        }
+    });
+    text_dialog.present(Some(parent));
+}
+
+/// Open the annotation editor for a screenshot of the page titled `title` at `url`, unless the screenshot can't be loaded
+pub fn present_screenshot_editor(
+    texture: gdk::Texture,
+    title: String,
+    url: String,
+    parent: &impl IsA<gtk::Widget>,
+) -> Result<(), String> {
+    let canvas = Canvas::new(&texture).ok_or("The screenshot is too big to edit")?;
+    let mut screenshot_editor = ScreenshotEditor::builder()
+        .launch((canvas, title, url))
+        .detach();
+    screenshot_editor.detach_runtime();
+    screenshot_editor
+        .widgets()
+        .screenshot_editor_dialog
+        .present(Some(parent));
+    Ok(())
+}
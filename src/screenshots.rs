@@ -0,0 +1,397 @@
+use std::{cell::Cell, fs, path::PathBuf, rc::Rc};
+
+use relm4::gtk::{gdk, gdk_pixbuf, gio, glib, graphene, prelude::*};
+use serde::Deserialize;
+use webkit6::prelude::*;
+
+use crate::downloads::available_path;
+use crate::gsettings::gsettings;
+use crate::sitesettings::host_of;
+
+const ELEMENT_SCREENSHOT_WORLD: &str = "spidey-element-screenshots";
+
+/// Remembers the element that was last right-clicked and where, for "Screenshot this element"
+const CONTEXT_MENU_TARGET_SCRIPT: &str = r#"
+document.addEventListener("contextmenu", (event) => {
+    window.spideyContextMenuTarget = {
+        element: event.target instanceof Element ? event.target : event.target.parentElement,
+        x: event.clientX,
+        y: event.clientY,
+    };
+}, true);
+"#;
+
+/// Where the right-clicked element is on the whole page, in CSS pixels
+const ELEMENT_BOUNDS_SCRIPT: &str = r#"
+(() => {
+    const element = window.spideyContextMenuElement;
+    if (!element || !element.isConnected) {
+        return null;
+    }
+    const bounds = element.getBoundingClientRect();
+    return {
+        x: bounds.left + window.scrollX,
+        y: bounds.top + window.scrollY,
+        width: bounds.width,
+        height: bounds.height,
+        documentWidth: Math.max(document.documentElement.scrollWidth, document.documentElement.clientWidth),
+    };
+})()
+"#;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ElementBounds {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    document_width: f32,
+}
+
+pub const SCREENSHOT_FORMATS: [(&str, &str); 3] =
+    [("png", "PNG"), ("jpeg", "JPEG"), ("webp", "WebP")];
+
+/// How long a screenshot's file name can be in bytes, leaving room under the usual 255 byte limit
+/// for the extension and the number added when the name is taken
+const MAX_FILE_NAME_LENGTH: usize = 240;
+
+/// The folder screenshots are saved to
+pub fn screenshot_folder() -> PathBuf {
+    match gsettings().string("screenshot-folder").as_str() {
+        "" => glib::user_special_dir(glib::UserDirectory::Pictures)
+            .unwrap_or_else(|| glib::home_dir().join("Pictures"))
+            .join("Screenshots"),
+        screenshot_folder => PathBuf::from(screenshot_folder),
+    }
+}
+
+/// Fill in the file name template for a screenshot of the page at `url`, without the extension
+pub fn screenshot_file_name(template: &str, title: &str, url: &str) -> String {
+    let now = chrono::Local::now();
+    let file_name = template
+        .replace("{title}", title)
+        .replace("{domain}", &host_of(url).unwrap_or_default())
+        .replace("{date}", &now.format("%Y-%m-%d").to_string())
+        .replace("{time}", &now.format("%H-%M-%S").to_string())
+        .replace(['/', '\\', ':', '\0'], "-");
+    let mut file_name = file_name.trim().to_string();
+    if file_name.len() > MAX_FILE_NAME_LENGTH {
+        let mut end = MAX_FILE_NAME_LENGTH;
+        while !file_name.is_char_boundary(end) {
+            end -= 1;
+        }
+        file_name.truncate(end);
+    }
+    match file_name.trim() {
+        "" => String::from("Screenshot"),
+        file_name => file_name.to_string(),
+    }
+}
+
+/// Encode `texture` in the format chosen in the preferences. Returns the data and the file extension.
+fn encode_screenshot(texture: &gdk::Texture) -> Result<(Vec<u8>, &'static str), String> {
+    let format = gsettings().string("screenshot-format");
+    if format != "jpeg" && format != "webp" {
+        return Ok((texture.save_to_png_bytes().to_vec(), "png"));
+    }
+    let quality = gsettings().int("screenshot-quality");
+    let mut texture_downloader = gdk::TextureDownloader::new(texture);
+    texture_downloader.set_format(gdk::MemoryFormat::R8g8b8a8);
+    let (pixels, stride) = texture_downloader.download_bytes();
+    if format == "webp" {
+        // The encoder wants rows without padding
+        let row_length = texture.width() as usize * 4;
+        let pixels = pixels
+            .chunks(stride)
+            .take(texture.height() as usize)
+            .flat_map(|row| &row[..row_length])
+            .copied()
+            .collect::<Vec<u8>>();
+        let data =
+            webp::Encoder::from_rgba(&pixels, texture.width() as u32, texture.height() as u32)
+                .encode(quality as f32);
+        return Ok((data.to_vec(), "webp"));
+    }
+    let pixbuf = gdk_pixbuf::Pixbuf::from_bytes(
+        &pixels,
+        gdk_pixbuf::Colorspace::Rgb,
+        true,
+        8,
+        texture.width(),
+        texture.height(),
+        stride as i32,
+    );
+    // JPEG has no transparency, and pages are drawn on an opaque background anyway
+    let pixbuf = pixbuf
+        .composite_color_simple(
+            texture.width(),
+            texture.height(),
+            gdk_pixbuf::InterpType::Nearest,
+            255,
+            1,
+            0xffffff,
+            0xffffff,
+        )
+        .unwrap_or(pixbuf);
+    pixbuf
+        .save_to_bufferv("jpeg", &[("quality", quality.to_string().as_str())])
+        .map(|data| (data, "jpg"))
+        .map_err(|error| error.to_string())
+}
+
+/// Save a screenshot of the page titled `title` at `url` as the preferences say. Returns where it was saved, if it was.
+pub fn save_screenshot(
+    texture: &gdk::Texture,
+    title: &str,
+    url: &str,
+) -> Result<Option<PathBuf>, String> {
+    if !gsettings().boolean("screenshot-save") {
+        return Ok(None);
+    }
+    let (data, extension) = encode_screenshot(texture)?;
+    let folder = screenshot_folder();
+    fs::create_dir_all(&folder).map_err(|error| error.to_string())?;
+    let file_name = screenshot_file_name(
+        &gsettings().string("screenshot-filename-template"),
+        title,
+        url,
+    );
+    let path = available_path(&folder, &format!("{file_name}.{extension}"));
+    fs::write(&path, data).map_err(|error| error.to_string())?;
+    Ok(Some(path))
+}
+
+/// A layer to put over the page that lets the user drag out the part of it to capture.
+/// `on_done` gets the rectangle in the layer's coordinates, or None if the user pressed Escape.
+pub fn region_selector(on_done: impl Fn(Option<graphene::Rect>) + 'static) -> gtk::DrawingArea {
+    let region_selector = gtk::DrawingArea::builder()
+        .halign(gtk::Align::Fill)
+        .valign(gtk::Align::Fill)
+        .focusable(true)
+        .can_focus(true)
+        .build();
+    region_selector.set_cursor_from_name(Some("crosshair"));
+    let on_done = Rc::new(on_done);
+    // Where the drag started and how far it has gone
+    let selection: Rc<Cell<Option<(f64, f64, f64, f64)>>> = Rc::new(Cell::new(None));
+
+    region_selector.set_draw_func(
+        glib::clone!(@strong selection => move |_, cairo_context, width, height| {
+            cairo_context.set_fill_rule(gtk::cairo::FillRule::EvenOdd);
+            cairo_context.set_source_rgba(0.0, 0.0, 0.0, 0.4);
+            cairo_context.rectangle(0.0, 0.0, width as f64, height as f64);
+            if let Some((x, y, width, height)) = selection.get() {
+                cairo_context.rectangle(x, y, width, height);
+            }
+            _ = cairo_context.fill();
+            if let Some((x, y, width, height)) = selection.get() {
+                cairo_context.set_source_rgba(1.0, 1.0, 1.0, 0.9);
+                cairo_context.set_line_width(1.0);
+                cairo_context.rectangle(x + 0.5, y + 0.5, width - 1.0, height - 1.0);
+                _ = cairo_context.stroke();
+            }
+        }),
+    );
+
+    let drag_gesture = gtk::GestureDrag::new();
+    drag_gesture.connect_drag_begin(
+        glib::clone!(@strong selection, @weak region_selector => move |_, x, y| {
+            selection.set(Some((x, y, 0.0, 0.0)));
+            region_selector.queue_draw();
+        }),
+    );
+    drag_gesture.connect_drag_update(glib::clone!(@strong selection, @weak region_selector => move |this_gesture, offset_x, offset_y| {
+        if let Some((start_x, start_y)) = this_gesture.start_point() {
+            // Dragging up or left still gives a rectangle with a positive size
+            selection.set(Some((
+                start_x.min(start_x + offset_x),
+                start_y.min(start_y + offset_y),
+                offset_x.abs(),
+                offset_y.abs(),
+            )));
+            region_selector.queue_draw();
+        }
+    }));
+    drag_gesture.connect_drag_end(glib::clone!(@strong selection, @strong on_done => move |_, _, _| {
+        match selection.take() {
+            // A click without a drag doesn't select anything, so keep waiting
+            Some((_, _, width, height)) if width < 2.0 || height < 2.0 => {}
+            Some((x, y, width, height)) => {
+                on_done(Some(graphene::Rect::new(x as f32, y as f32, width as f32, height as f32)))
+            }
+            None => {}
+        }
+    }));
+    region_selector.add_controller(drag_gesture);
+
+    let key_controller = gtk::EventControllerKey::new();
+    key_controller.connect_key_pressed(glib::clone!(@strong on_done => move |_, key, _, _| {
+        if key == gdk::Key::Escape {
+            on_done(None);
+            glib::Propagation::Stop
+        } else {
+            glib::Propagation::Proceed
+        }
+    }));
+    region_selector.add_controller(key_controller);
+
+    region_selector
+}
+
+/// Cut `region` out of `texture`. `scale` is how many texture pixels there are per point of `region`.
+pub fn crop_texture(
+    texture: &gdk::Texture,
+    region: &graphene::Rect,
+    scale: f64,
+) -> Option<gdk::Texture> {
+    let x = ((region.x() as f64 * scale).round() as i32).clamp(0, texture.width());
+    let y = ((region.y() as f64 * scale).round() as i32).clamp(0, texture.height());
+    let width = ((region.width() as f64 * scale).round() as i32).min(texture.width() - x);
+    let height = ((region.height() as f64 * scale).round() as i32).min(texture.height() - y);
+    if width <= 0 || height <= 0 {
+        return None;
+    }
+    let mut texture_downloader = gdk::TextureDownloader::new(texture);
+    texture_downloader.set_format(gdk::MemoryFormat::R8g8b8a8Premultiplied);
+    let (pixels, stride) = texture_downloader.download_bytes();
+    let mut cropped_pixels = Vec::with_capacity(width as usize * height as usize * 4);
+    for row in y..y + height {
+        let row_start = row as usize * stride + x as usize * 4;
+        cropped_pixels.extend_from_slice(&pixels[row_start..row_start + width as usize * 4]);
+    }
+    Some(
+        gdk::MemoryTexture::new(
+            width,
+            height,
+            gdk::MemoryFormat::R8g8b8a8Premultiplied,
+            &glib::Bytes::from_owned(cropped_pixels),
+            width as usize * 4,
+        )
+        .upcast(),
+    )
+}
+
+/// Copy and save a screenshot as the preferences say, for taking one or editing it.
+/// `widget` is anything on the screen, which gives the clipboard.
+pub fn deliver_screenshot(
+    texture: &gdk::Texture,
+    title: &str,
+    url: &str,
+    widget: &impl IsA<gtk::Widget>,
+) -> Result<Option<PathBuf>, String> {
+    if gsettings().boolean("screenshot-copy") {
+        widget.clipboard().set_texture(texture);
+    }
+    save_screenshot(texture, title, url)
+}
+
+/// What to tell the user after `deliver_screenshot`
+pub fn describe_delivered_screenshot(path: Option<&PathBuf>) -> Option<String> {
+    let copied = gsettings().boolean("screenshot-copy");
+    let folder_name = path
+        .and_then(|path| path.parent())
+        .and_then(|folder| folder.file_name())
+        .map(|folder_name| folder_name.to_string_lossy().to_string());
+    match (folder_name, copied) {
+        (Some(folder_name), true) => Some(format!("Screenshot saved to {folder_name} and copied")),
+        (Some(folder_name), false) => Some(format!("Screenshot saved to {folder_name}")),
+        (None, true) => Some(String::from("Screenshot copied")),
+        (None, false) => None,
+    }
+}
+
+/// Keep track of which element is right-clicked in pages loaded with `user_content_manager`
+pub fn set_up_element_screenshots(user_content_manager: &webkit6::UserContentManager) {
+    user_content_manager.add_script(&webkit6::UserScript::for_world(
+        CONTEXT_MENU_TARGET_SCRIPT,
+        webkit6::UserContentInjectedFrames::TopFrame,
+        webkit6::UserScriptInjectionTime::Start,
+        ELEMENT_SCREENSHOT_WORLD,
+        &[],
+        &[],
+    ));
+}
+
+/// Take the element the context menu that is opening in `web_view` was opened on, so that
+/// `find_context_menu_element` finds it. Right-clicks inside frames and pages that cancel the
+/// context menu leave an old element behind, so the element is only taken if it was right-clicked
+/// where the menu opened, and it is cleared either way.
+pub fn take_context_menu_element(web_view: &webkit6::WebView, context_menu: &webkit6::ContextMenu) {
+    // Menus opened with the keyboard have no position to check, but the element was still cleared when the last menu opened
+    let position = context_menu
+        .event()
+        .and_then(|event| event.position())
+        .and_then(|(x, y)| {
+            let native = web_view.native()?;
+            let (surface_x, surface_y) = native.surface_transform();
+            native.compute_point(
+                web_view,
+                &graphene::Point::new((x - surface_x) as f32, (y - surface_y) as f32),
+            )
+        });
+    let is_fresh = match position {
+        Some(position) => {
+            let zoom_level = web_view.zoom_level();
+            format!(
+                "Math.abs(target.x - {}) <= 2 && Math.abs(target.y - {}) <= 2",
+                position.x() as f64 / zoom_level,
+                position.y() as f64 / zoom_level,
+            )
+        }
+        None => String::from("true"),
+    };
+    web_view.evaluate_javascript(
+        &format!(
+            "(() => {{
+                const target = window.spideyContextMenuTarget;
+                window.spideyContextMenuTarget = null;
+                window.spideyContextMenuElement = target && ({is_fresh}) ? target.element : null;
+            }})()"
+        ),
+        Some(ELEMENT_SCREENSHOT_WORLD),
+        None,
+        gio::Cancellable::NONE,
+        |result| {
+            if let Err(error) = result {
+                eprintln!("Could not find right-clicked element: {error}");
+            }
+        },
+    );
+}
+
+/// Find where the element that was last right-clicked in `web_view` is on a full-page snapshot.
+/// `on_found` gets the area in the same units as the width of `web_view`, which is what `crop_texture` expects.
+pub fn find_context_menu_element(
+    web_view: &webkit6::WebView,
+    on_found: impl FnOnce(Option<graphene::Rect>) + 'static,
+) {
+    let web_view_width = web_view.width().max(1) as f32;
+    web_view.evaluate_javascript(
+        ELEMENT_BOUNDS_SCRIPT,
+        Some(ELEMENT_SCREENSHOT_WORLD),
+        None,
+        gio::Cancellable::NONE,
+        move |result| {
+            let element_bounds = result
+                .map_err(|error| eprintln!("Could not find element to capture: {error}"))
+                .ok()
+                .and_then(|value| value.to_json(0))
+                .and_then(|json| serde_json::from_str::<Option<ElementBounds>>(&json).ok())
+                .flatten()
+                .filter(|element_bounds| {
+                    element_bounds.width >= 1.0 && element_bounds.height >= 1.0
+                });
+            // A full-page snapshot is as wide as the page, which is drawn at the width of the web view
+            on_found(element_bounds.map(|element_bounds| {
+                let scale = web_view_width / element_bounds.document_width.max(1.0);
+                graphene::Rect::new(
+                    element_bounds.x * scale,
+                    element_bounds.y * scale,
+                    element_bounds.width * scale,
+                    element_bounds.height * scale,
+                )
+            }));
+        },
+    );
+}
@@ -0,0 +1,202 @@
+use documents::prelude::*;
+use webkit6::{gio, glib, prelude::*};
+
+use crate::gsettings::gsettings;
+use crate::sitesettings::{host_of, site_exception, site_values, SiteException};
+
+/// A page shown over the WebView instead of a page that could not be loaded safely
+#[derive(Debug, Clone, PartialEq)]
+pub enum Interstitial {
+    HttpsUnavailable {
+        http_url: String,
+        failing_url: String,
+    },
+    TlsError {
+        failing_url: String,
+        host: String,
+        certificate: gio::TlsCertificate,
+        errors: gio::TlsCertificateFlags,
+    },
+}
+
+impl Interstitial {
+    pub fn failing_url(&self) -> &str {
+        match self {
+            Interstitial::HttpsUnavailable { failing_url, .. }
+            | Interstitial::TlsError { failing_url, .. } => failing_url,
+        }
+    }
+    pub fn title(&self) -> &'static str {
+        match self {
+            Interstitial::HttpsUnavailable { .. } => "Secure Connection Unavailable",
+            Interstitial::TlsError { .. } => "Connection Is Not Secure",
+        }
+    }
+    pub fn description(&self) -> String {
+        match self {
+            Interstitial::HttpsUnavailable { http_url, .. } => format!(
+                "{} does not support HTTPS. If you continue, anyone on your network can see and change what you send to and receive from this site.",
+                host_of(http_url).unwrap_or_default()
+            ),
+            Interstitial::TlsError { host, errors, .. } => format!(
+                "The identity of {host} could not be verified because its certificate {}. Someone could be pretending to be this site to steal your information.",
+                describe_tls_errors(*errors, host).join(", ")
+            ),
+        }
+    }
+    pub fn proceed_label(&self) -> &'static str {
+        match self {
+            Interstitial::HttpsUnavailable { .. } => "Continue to HTTP Site",
+            Interstitial::TlsError { .. } => "Accept Risk and Continue",
+        }
+    }
+    pub fn certificate(&self) -> Option<(gio::TlsCertificate, gio::TlsCertificateFlags)> {
+        match self {
+            Interstitial::TlsError {
+                certificate,
+                errors,
+                ..
+            } => Some((certificate.clone(), *errors)),
+            _ => None,
+        }
+    }
+}
+
+/// Get the HTTPS version of `url` if HTTPS-Only Mode is on and the site is not exempt from it
+pub fn https_upgrade_of(url: &str) -> Option<String> {
+    if !gsettings().boolean("https-only") || !url.starts_with("http://") {
+        return None;
+    }
+    let host = host_of(url)?;
+    if let SiteException::Allow = site_exception("https-only-exceptions", &host) {
+        return None;
+    }
+    Some(url.replacen("http://", "https://", 1))
+}
+
+/// Explain each problem with a certificate as the end of the sentence "The certificate …"
+pub fn describe_tls_errors(errors: gio::TlsCertificateFlags, host: &str) -> Vec<String> {
+    let mut descriptions = vec![];
+    if errors.contains(gio::TlsCertificateFlags::UNKNOWN_CA) {
+        descriptions.push(String::from(
+            "was issued by an authority that is not trusted",
+        ));
+    }
+    if errors.contains(gio::TlsCertificateFlags::BAD_IDENTITY) {
+        descriptions.push(format!("does not belong to {host}"));
+    }
+    if errors.contains(gio::TlsCertificateFlags::NOT_ACTIVATED) {
+        descriptions.push(String::from("is not valid yet"));
+    }
+    if errors.contains(gio::TlsCertificateFlags::EXPIRED) {
+        descriptions.push(String::from("has expired"));
+    }
+    if errors.contains(gio::TlsCertificateFlags::REVOKED) {
+        descriptions.push(String::from("has been revoked"));
+    }
+    if errors.contains(gio::TlsCertificateFlags::INSECURE) {
+        descriptions.push(String::from("uses an insecure algorithm"));
+    }
+    if descriptions.is_empty() {
+        descriptions.push(String::from("could not be validated"));
+    }
+    descriptions
+}
+
+/// Trust `certificate` for `host` in `session` until Spidey quits, so the exception doesn't outlive the visit
+pub fn add_tls_exception(
+    session: &webkit6::NetworkSession,
+    host: &str,
+    certificate: &gio::TlsCertificate,
+) {
+    session.allow_tls_certificate_for_host(certificate, host);
+}
+
+/// Content blocker rules that block insecure subresources on HTTPS pages, except on sites allowed to load them
+fn mixed_content_rules() -> String {
+    let mut rules = vec![String::from(
+        r#"{"trigger": {"url-filter": "^http://", "if-top-url": ["^https://"]}, "action": {"type": "block"}}"#,
+    )];
+    for host in site_values("mixed-content-exceptions").into_keys() {
+        rules.push(format!(
+            r#"{{"trigger": {{"url-filter": ".*", "if-top-url": ["^https://{}[:/]"]}}, "action": {{"type": "ignore-previous-rules"}}}}"#,
+            host.replace('.', r"\\.")
+        ));
+    }
+    format!("[{}]", rules.join(", "))
+}
+
+/// Add or remove the filter that blocks insecure subresources according to the current settings, then call `then`
+pub fn apply_mixed_content_blocking(
+    user_content_manager: &webkit6::UserContentManager,
+    then: impl FnOnce() + 'static,
+) {
+    user_content_manager.remove_filter_by_id("mixed-content");
+    if !gsettings().boolean("mixed-content-blocking") {
+        then();
+        return;
+    }
+    let mut user_content_filter_store_option = None;
+    with(
+        &[Document::at(
+            Project(Data(&["UserContentFilterStore"]).with_id("com", "github.kdwk", "Spidey")),
+            "",
+            Create::OnlyIfNotExists,
+        )
+        .alias("UserContentFilterStore")],
+        |d| {
+            user_content_filter_store_option = Some(webkit6::UserContentFilterStore::new(
+                d["UserContentFilterStore"].path().as_str(),
+            ));
+            Ok(())
+        },
+    );
+    if let Some(user_content_filter_store) = user_content_filter_store_option {
+        let user_content_manager = user_content_manager.clone();
+        user_content_filter_store.save(
+            "mixed-content",
+            &glib::Bytes::from_owned(mixed_content_rules()),
+            gio::Cancellable::NONE,
+            move |user_content_filter_result| {
+                match user_content_filter_result {
+                    Ok(user_content_filter) => {
+                        user_content_manager.add_filter(&user_content_filter)
+                    }
+                    Err(error) => eprintln!("Could not compile mixed content filter: {error}"),
+                }
+                then();
+            },
+        );
+    }
+}
+
+/// Summarise the insecure subresources of a page for the site information popover
+pub fn describe_insecure_resources(insecure_resources: &[String], blocked: bool) -> String {
+    let mut description = String::from(if blocked {
+        "Insecure content on this page was blocked:"
+    } else {
+        "Parts of this page were loaded over an insecure connection:"
+    });
+    for insecure_resource in insecure_resources.iter().take(5) {
+        description.push_str("\n");
+        description.push_str(insecure_resource);
+    }
+    if insecure_resources.len() > 5 {
+        description.push_str(&format!("\nand {} more", insecure_resources.len() - 5));
+    }
+    description
+}
+
+/// JavaScript that lists the insecure subresources a page refers to, one per line
+pub const INSECURE_RESOURCES_SCRIPT: &str = r#"
+Array.from(new Set([
+    ...performance.getEntriesByType("resource").map((entry) => entry.name),
+    ...Array.from(
+        // Other links, like canonical or alternate addresses, are never loaded
+        document.querySelectorAll(
+            'img[src], script[src], iframe[src], audio[src], video[src], source[src], link[rel~="stylesheet"][href], link[rel~="icon"][href], link[rel~="preload"][href]'
+        ),
+        (element) => element.src || element.href
+    ),
+].filter((url) => url.startsWith("http:")))).join("\n")
+"#;
@@ -3,7 +3,7 @@ use relm4::{gtk, RelmApp};
 use gettextrs::{gettext, LocaleCategory};
 use gtk::{gio, glib};
 
-use crate::config::{APP_ID, GETTEXT_PACKAGE, LOCALEDIR, RESOURCES_FILE};
+use crate::config::{APP_ID, GETTEXT_PACKAGE, LOCALEDIR, PDFJS_RESOURCES_FILE, RESOURCES_FILE};
 
 pub fn setup<Model: std::fmt::Debug>(app: &RelmApp<Model>) {
     // Initialize GTK
@@ -16,6 +16,11 @@ pub fn setup<Model: std::fmt::Debug>(app: &RelmApp<Model>) {
     let res = gio::Resource::load(RESOURCES_FILE).expect("Could not load gresource file");
     gio::resources_register(&res);
 
+    // The PDF viewer is only there if build-aux/fetch-pdfjs.sh was run before building
+    if let Ok(pdfjs_res) = gio::Resource::load(PDFJS_RESOURCES_FILE) {
+        gio::resources_register(&pdfjs_res);
+    }
+
     setup_css(&res, app);
 
     gtk::Window::set_default_icon_name(APP_ID);
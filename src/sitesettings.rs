@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+use url::Url;
+use webkit6::glib::ToVariant;
+
+use crate::gsettings::gsettings;
+
+/// Get the host of a URL, which is what per-site settings are keyed by
+pub fn host_of(url: &str) -> Option<String> {
+    Url::parse(url)
+        .ok()?
+        .host_str()
+        .map(|host| host.to_string())
+}
+
+/// Get the origin of a URL, e.g. `https://example.com:8080`, which permission decisions are keyed by
+pub fn origin_of(url: &str) -> Option<String> {
+    let origin = Url::parse(url).ok()?.origin();
+    if origin.is_tuple() {
+        Some(origin.ascii_serialization())
+    } else {
+        None
+    }
+}
+
+/// Whether a site overrides a global setting, and in which direction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SiteException {
+    Default,
+    Allow,
+    Block,
+}
+
+impl SiteException {
+    pub const LABELS: [&'static str; 3] = ["Default", "Allow", "Block"];
+    pub fn from_index(index: u32) -> Self {
+        match index {
+            1 => SiteException::Allow,
+            2 => SiteException::Block,
+            _ => SiteException::Default,
+        }
+    }
+    pub fn index(&self) -> u32 {
+        match self {
+            SiteException::Default => 0,
+            SiteException::Allow => 1,
+            SiteException::Block => 2,
+        }
+    }
+    pub fn from_value(value: Option<String>) -> Self {
+        match value.as_deref() {
+            Some("allow") => SiteException::Allow,
+            Some("block") => SiteException::Block,
+            _ => SiteException::Default,
+        }
+    }
+    fn value(&self) -> Option<&'static str> {
+        match self {
+            SiteException::Default => None,
+            SiteException::Allow => Some("allow"),
+            SiteException::Block => Some("block"),
+        }
+    }
+}
+
+/// Get all per-site values stored in the `a{ss}` GSettings key `key`, keyed by host
+pub fn site_values(key: &str) -> HashMap<String, String> {
+    gsettings().get::<HashMap<String, String>>(key)
+}
+
+/// Get the value stored for `host` in the `a{ss}` GSettings key `key`
+pub fn site_value(key: &str, host: &str) -> Option<String> {
+    site_values(key).remove(host)
+}
+
+/// Store a value for `host` in the `a{ss}` GSettings key `key`, or forget it if `value` is None
+pub fn set_site_value(key: &str, host: &str, value: Option<&str>) {
+    let mut values = site_values(key);
+    match value {
+        Some(value) => values.insert(host.to_string(), value.to_string()),
+        None => values.remove(host),
+    };
+    if let Err(error) = gsettings().set(key, values.to_variant()) {
+        eprintln!("Could not update GSettings value '{key}': {error}");
+    }
+}
+
+pub fn site_exception(key: &str, host: &str) -> SiteException {
+    SiteException::from_value(site_value(key, host))
+}
+
+pub fn set_site_exception(key: &str, host: &str, exception: SiteException) {
+    set_site_value(key, host, exception.value());
+}
+
+/// Whether JavaScript should run at `origin`, taking its exception into account
+pub fn javascript_enabled_for(origin: Option<&str>) -> bool {
+    let exception = match origin {
+        Some(origin) => site_exception("javascript-exceptions", origin),
+        None => SiteException::Default,
+    };
+    match exception {
+        SiteException::Default => gsettings().boolean("javascript-enabled"),
+        SiteException::Allow => true,
+        SiteException::Block => false,
+    }
+}
+
+/// Allow or block JavaScript at `origin`, storing an exception only if it differs from the global setting
+pub fn set_javascript_enabled_for(origin: &str, enabled: bool) {
+    let exception = if enabled == gsettings().boolean("javascript-enabled") {
+        SiteException::Default
+    } else if enabled {
+        SiteException::Allow
+    } else {
+        SiteException::Block
+    };
+    set_site_exception("javascript-exceptions", origin, exception);
+}
@@ -0,0 +1,296 @@
+#![allow(unused_imports)]
+#![allow(unused_variables)]
+use relm4::{
+    adw::prelude::*,
+    gtk::{glib::clone, prelude::*},
+    prelude::*,
+};
+use webkit6::{glib, prelude::*};
+
+use crate::whoops::{attempt, Catch, IntoWhoops, Whoops};
+
+/// A third-party domain that Intelligent Tracking Prevention has classified as a tracker
+#[derive(Debug, Clone)]
+pub struct Tracker {
+    pub domain: String,
+    pub first_parties: Vec<TrackedSite>,
+}
+
+/// A site a tracker was found on
+#[derive(Debug, Clone)]
+pub struct TrackedSite {
+    pub domain: String,
+    pub data_access_allowed: bool,
+    pub last_seen: Option<glib::DateTime>,
+}
+
+impl Tracker {
+    /// Whether this tracker was found on `host`, which may be a subdomain of the site ITP recorded
+    pub fn is_on(&self, host: &str) -> bool {
+        self.first_parties.iter().any(|first_party| {
+            host == first_party.domain || host.ends_with(&format!(".{}", first_party.domain))
+        })
+    }
+}
+
+/// Get the trackers Intelligent Tracking Prevention has found in `session`, most recently seen first
+pub fn load_trackers(
+    session: &webkit6::NetworkSession,
+    callback: impl FnOnce(Vec<Tracker>) + 'static,
+) {
+    session.itp_summary(gtk::gio::Cancellable::NONE, move |summary_result| {
+        let third_parties = match summary_result {
+            Ok(third_parties) => third_parties,
+            Err(error) => {
+                eprintln!("Could not fetch tracking prevention summary: {error}");
+                vec![]
+            }
+        };
+        let mut trackers = third_parties
+            .into_iter()
+            .map(|mut third_party| Tracker {
+                domain: third_party
+                    .domain()
+                    .map(|domain| domain.to_string())
+                    .unwrap_or_default(),
+                first_parties: third_party
+                    .first_parties()
+                    .into_iter()
+                    .map(|mut first_party| TrackedSite {
+                        domain: first_party
+                            .domain()
+                            .map(|domain| domain.to_string())
+                            .unwrap_or_default(),
+                        data_access_allowed: first_party.is_website_data_access_allowed(),
+                        last_seen: first_party.last_update_time(),
+                    })
+                    .collect(),
+            })
+            .collect::<Vec<Tracker>>();
+        for tracker in &mut trackers {
+            tracker
+                .first_parties
+                .sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
+        }
+        trackers.sort_by(|a, b| {
+            let last_seen = |tracker: &Tracker| {
+                tracker
+                    .first_parties
+                    .first()
+                    .and_then(|first_party| first_party.last_seen.clone())
+            };
+            last_seen(b).cmp(&last_seen(a))
+        });
+        callback(trackers);
+    });
+}
+
+pub struct TrackingPreventionDialog {
+    session: webkit6::NetworkSession,
+    host: Option<String>,
+    trackers: Vec<Tracker>,
+    loading: bool,
+}
+
+#[derive(Debug)]
+pub enum TrackingPreventionInput {
+    Reload,
+    TrackersLoaded(Vec<Tracker>),
+}
+
+#[relm4::component(pub)]
+impl Component for TrackingPreventionDialog {
+    type Init = (webkit6::NetworkSession, Option<String>);
+    type Input = TrackingPreventionInput;
+    type Output = ();
+    type CommandOutput = ();
+
+    view! {
+        #[name(tracking_prevention_dialog)]
+        adw::Dialog {
+            set_title: &match &model.host {
+                Some(host) => format!("Trackers on {host}"),
+                None => String::from("Tracking Prevention"),
+            },
+            set_content_width: 500,
+            set_content_height: 600,
+
+            #[wrap(Some)]
+            set_child = &adw::ToolbarView {
+                add_top_bar = &adw::HeaderBar {
+                    pack_start = &gtk::Button {
+                        set_icon_name: "arrow-circular-top-right",
+                        set_tooltip_text: Some("Refresh"),
+                        connect_clicked => TrackingPreventionInput::Reload,
+                    },
+                },
+
+                #[wrap(Some)]
+                set_content = &gtk::Stack {
+                    #[watch]
+                    set_visible_child_name: if model.loading {
+                        "loading"
+                    } else if model.trackers.is_empty() {
+                        "empty"
+                    } else {
+                        "trackers"
+                    },
+
+                    add_named[Some("loading")] = &gtk::Spinner {
+                        set_halign: gtk::Align::Center,
+                        set_valign: gtk::Align::Center,
+                        #[watch]
+                        set_spinning: model.loading,
+                    },
+
+                    add_named[Some("empty")] = &adw::StatusPage {
+                        set_icon_name: Some("padlock2"),
+                        set_title: "No Trackers Found",
+                        set_description: Some(if model.host.is_some() {
+                            "No trackers have been found on this site"
+                        } else {
+                            "No sites have been found tracking you across the web"
+                        }),
+                    },
+
+                    add_named[Some("trackers")] = &gtk::ScrolledWindow {
+                        set_vexpand: true,
+
+                        adw::Clamp {
+                            set_margin_all: 12,
+
+                            gtk::Box {
+                                set_orientation: gtk::Orientation::Vertical,
+                                set_spacing: 12,
+
+                                gtk::Label {
+                                    set_wrap: true,
+                                    set_xalign: 0.0,
+                                    add_css_class: "dim-label",
+                                    set_label: "These sites were found following you across the web. Spidey stops them from using their cookies and other data in other sites unless you interact with them directly.",
+                                },
+
+                                #[name(tracker_list)]
+                                gtk::ListBox {
+                                    add_css_class: "boxed-list",
+                                    set_valign: gtk::Align::Start,
+                                    set_selection_mode: gtk::SelectionMode::None,
+                                },
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = TrackingPreventionDialog {
+            session: init.0,
+            host: init.1,
+            trackers: vec![],
+            loading: true,
+        };
+        let widgets = view_output!();
+        sender.input(TrackingPreventionInput::Reload);
+        ComponentParts {
+            model: model,
+            widgets: widgets,
+        }
+    }
+
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        message: Self::Input,
+        sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        match message {
+            TrackingPreventionInput::Reload => {
+                self.loading = true;
+                load_trackers(
+                    &self.session,
+                    clone!(@strong sender => move |trackers| {
+                        sender.input(TrackingPreventionInput::TrackersLoaded(trackers));
+                    }),
+                );
+            }
+            TrackingPreventionInput::TrackersLoaded(trackers) => {
+                self.loading = false;
+                self.trackers = match &self.host {
+                    Some(host) => trackers
+                        .into_iter()
+                        .filter(|tracker| tracker.is_on(host))
+                        .collect(),
+                    None => trackers,
+                };
+                self.populate(&widgets.tracker_list);
+            }
+        }
+        self.update_view(widgets, sender);
+    }
+}
+
+impl TrackingPreventionDialog {
+    fn populate(&self, tracker_list: &gtk::ListBox) {
+        tracker_list.remove_all();
+        for tracker in &self.trackers {
+            let tracker_row = adw::ExpanderRow::builder()
+                .title(tracker.domain.as_str())
+                .subtitle(if tracker.first_parties.len() == 1 {
+                    String::from("Found on 1 site")
+                } else {
+                    format!("Found on {} sites", tracker.first_parties.len())
+                })
+                .build();
+            for first_party in &tracker.first_parties {
+                let first_party_row = adw::ActionRow::builder()
+                    .title(first_party.domain.as_str())
+                    .subtitle(describe_tracked_site(first_party))
+                    .use_markup(false)
+                    .build();
+                tracker_row.add_row(&first_party_row);
+            }
+            tracker_list.append(&tracker_row);
+        }
+    }
+}
+
+fn describe_tracked_site(tracked_site: &TrackedSite) -> String {
+    let last_seen = match tracked_site
+        .last_seen
+        .as_ref()
+        .and_then(|last_seen| last_seen.to_local().ok())
+        .and_then(|last_seen| last_seen.format("%x %X").ok())
+    {
+        Some(last_seen) => format!("Last seen {last_seen}"),
+        None => String::from("Last seen at an unknown time"),
+    };
+    let data_access = if tracked_site.data_access_allowed {
+        "Allowed to use its cookies here"
+    } else {
+        "Blocked from using its cookies here"
+    };
+    format!("{last_seen} · {data_access}")
+}
+
+/// Open the tracking prevention dashboard for `session` on top of `parent`, showing only trackers on `host` if given
+pub fn present_tracking_prevention(
+    session: webkit6::NetworkSession,
+    host: Option<String>,
+    parent: &impl IsA<gtk::Widget>,
+) {
+    let mut tracking_prevention_dialog = TrackingPreventionDialog::builder()
+        .launch((session, host))
+        .detach();
+    tracking_prevention_dialog.detach_runtime();
+    tracking_prevention_dialog
+        .widgets()
+        .tracking_prevention_dialog
+        .present(Some(parent));
+}
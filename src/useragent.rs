@@ -0,0 +1,63 @@
+use crate::sitesettings::{set_site_value, site_value};
+
+/// User agents sites can be shown instead of Spidey's own, as (label, user agent)
+pub const USER_AGENT_PRESETS: [(&str, &str); 3] = [
+    (
+        "Firefox (Desktop)",
+        "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0",
+    ),
+    (
+        "Safari (Mobile)",
+        "Mozilla/5.0 (iPhone; CPU iPhone OS 17_5 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Mobile/15E148 Safari/604.1",
+    ),
+    (
+        "Chrome",
+        "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36",
+    ),
+];
+
+/// The user agent used to request the mobile version of a site
+pub const MOBILE_USER_AGENT: &str = USER_AGENT_PRESETS[1].1;
+
+/// Labels for choosing a user agent: Spidey's own, then the presets, then a custom one
+pub fn user_agent_labels() -> Vec<&'static str> {
+    let mut labels = vec!["Default"];
+    labels.extend(USER_AGENT_PRESETS.iter().map(|(label, _)| *label));
+    labels.push("Custom");
+    labels
+}
+
+/// Get the position of `user_agent` in `user_agent_labels()`
+pub fn user_agent_index(user_agent: Option<&str>) -> u32 {
+    match user_agent {
+        None => 0,
+        Some(user_agent) => {
+            USER_AGENT_PRESETS
+                .iter()
+                .position(|(_, preset)| *preset == user_agent)
+                .unwrap_or(USER_AGENT_PRESETS.len()) as u32
+                + 1
+        }
+    }
+}
+
+/// Get the user agent at `index` in `user_agent_labels()`, or None for Spidey's own or a custom one
+pub fn user_agent_preset(index: u32) -> Option<&'static str> {
+    USER_AGENT_PRESETS
+        .get((index as usize).checked_sub(1)?)
+        .map(|(_, user_agent)| *user_agent)
+}
+
+/// Get the user agent `host` should be shown, if it overrides Spidey's own
+pub fn user_agent_for(host: Option<&str>) -> Option<String> {
+    site_value("user-agents", host?)
+}
+
+/// Show `host` a different user agent from now on, or Spidey's own if `user_agent` is None
+pub fn set_user_agent_for(host: &str, user_agent: Option<&str>) {
+    set_site_value(
+        "user-agents",
+        host,
+        user_agent.filter(|user_agent| !user_agent.is_empty()),
+    );
+}
@@ -3,8 +3,10 @@
 use core::fmt::Display;
 use documents::prelude::*;
 use std::{
+    cell::RefCell,
     error::Error,
     process::Command,
+    rc::Rc,
     str::FromStr,
     sync::{atomic::AtomicBool, Arc},
     time::Duration,
@@ -17,6 +19,7 @@ use relm4::{
     gtk::{
         gdk::ContentProvider,
         glib::clone,
+        graphene,
         prelude::{WidgetExt, *},
         EventControllerMotion,
     },
@@ -33,7 +36,45 @@ use webkit6::{
 };
 use webkit6_sys::webkit_web_view_get_settings;
 
+use crate::certificatedialog::present_certificate_dialog;
+use crate::cleardata::present_clear_data;
+use crate::cookiemanager::{
+    apply_cookie_exceptions, apply_cookie_policy, present_cookie_manager, set_up_cookie_exceptions,
+};
+use crate::downloads::change_download_destination;
+use crate::gsettings::gsettings;
+use crate::httpauth::handle_authentication;
+use crate::mimepolicy::decide_response_policy;
+use crate::pagewatch::{page_watch_for, PageChange, PageWatch};
+use crate::pagewatchdialog::{present_page_changes, present_page_watch_dialog};
+use crate::passwordmanager::present_password_manager;
+use crate::passwords::{offer_to_save_credential, set_up_password_autofill, Credential};
+use crate::pdfviewer::{is_pdf_viewer_download, register_pdf_viewer_scheme};
+use crate::permissions::{
+    permission_decision, permission_kinds, present_permission_dialog, PermissionKind,
+};
+use crate::printing::{print_page, save_page_as_pdf};
+use crate::proxy::apply_proxy_settings;
+use crate::savedpages::save_page;
+use crate::screenshoteditor::present_screenshot_editor;
+use crate::screenshots::{
+    crop_texture, deliver_screenshot, describe_delivered_screenshot, find_context_menu_element,
+    region_selector, set_up_element_screenshots, take_context_menu_element,
+};
+use crate::security::{
+    add_tls_exception, apply_mixed_content_blocking, describe_insecure_resources, https_upgrade_of,
+    Interstitial, INSECURE_RESOURCES_SCRIPT,
+};
+use crate::sitesettings::{
+    host_of, javascript_enabled_for, origin_of, set_javascript_enabled_for, set_site_exception,
+    site_exception, SiteException,
+};
 use crate::smallwebwindow::*;
+use crate::trackers::{load_trackers, present_tracking_prevention};
+use crate::useragent::{
+    set_user_agent_for, user_agent_for, user_agent_index, user_agent_labels, user_agent_preset,
+    MOBILE_USER_AGENT,
+};
 use crate::{
     app::process_url,
     config::{APP_ID, PROFILE},
@@ -56,6 +97,39 @@ fn match_style_with_rgb(main_app: adw::Application) -> RGBA {
     }
 }
 
+/// Use the site's user agent for the page about to be loaded from `url`, and load it over HTTPS instead if HTTPS-Only
+/// Mode asks for it. Returns whether `decision` was ignored to do so.
+fn prepare_page_load(
+    web_view: &WebView,
+    decision: &webkit6::PolicyDecision,
+    url: &str,
+    http_allowed_once: &RefCell<Option<String>>,
+    sender: &ComponentSender<WebWindow>,
+) -> bool {
+    let host = host_of(url);
+    if let Some(web_view_settings) = webkit6::prelude::WebViewExt::settings(web_view) {
+        web_view_settings.set_user_agent(user_agent_for(host.as_deref()).as_deref());
+    }
+    let allowed_once = host.is_some() && *http_allowed_once.borrow() == host;
+    match https_upgrade_of(url) {
+        Some(https_url) if !allowed_once => {
+            decision.ignore();
+            sender.input(WebWindowInput::UpgradedToHttps(url.to_string()));
+            web_view.load_uri(&https_url);
+            true
+        }
+        _ => false,
+    }
+}
+
+fn padlock_icon_name(url: &str) -> &'static str {
+    if url.starts_with("https://") || url.starts_with("webkit://") {
+        "padlock2"
+    } else {
+        "padlock2-open"
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 struct Inhibited {
     no_of_inhibitions: u32,
@@ -99,6 +173,34 @@ pub struct WebWindow {
     show_headerbar: bool,
     web_view: Option<WebView>,
     toast_overlay: Option<ToastOverlay>,
+    host: Option<String>,
+    cookie_exception: SiteException,
+    allow_http: bool,
+    interstitial: Option<Interstitial>,
+    insecure_resources: Vec<String>,
+    mixed_content_blocking: bool,
+    allow_insecure_content: bool,
+    javascript_enabled: bool,
+    site_user_agent: Option<String>,
+    trackers_on_site: usize,
+    #[do_not_track]
+    region_selector: Option<gtk::DrawingArea>,
+    /// The last screenshot taken, with the title and address of its page, so it can be edited
+    #[do_not_track]
+    last_screenshot: Option<(gtk::gdk::Texture, String, String)>,
+    #[do_not_track]
+    edit_screenshot_action: Option<SimpleAction>,
+    #[do_not_track]
+    page_watch: Option<PageWatch>,
+    #[do_not_track]
+    request_mobile_site_action: Option<SimpleAction>,
+    #[do_not_track]
+    gsettings: webkit6::gio::Settings,
+    #[do_not_track]
+    https_upgrade: Option<String>,
+    /// The host the user chose to continue to over HTTP, until the page they chose it for is loaded
+    #[do_not_track]
+    http_allowed_once: Rc<RefCell<Option<String>>>,
 }
 
 #[derive(Debug)]
@@ -112,8 +214,30 @@ pub enum WebWindowInput {
     TitleChanged(String),
     UrlChanged(String),
     NavigationHistoryChanged(bool, bool),
-    InsecureContentDetected,
-    Screenshot(bool, webkit6::SnapshotRegion),
+    ScanInsecureContent,
+    InsecureResourcesFound(Vec<String>),
+    MixedContentBlockingChanged,
+    SetAllowInsecureContent(bool),
+    SetJavaScriptEnabled(bool),
+    SetSiteUserAgent(Option<String>),
+    CountTrackers,
+    TrackersCounted(usize),
+    ShowTrackers,
+    OfferToSaveCredential(Credential),
+    Authenticate(webkit6::AuthenticationRequest),
+    ShowPasswordManager,
+    Print,
+    SaveAsPdf,
+    SavePage,
+    Screenshot(bool, webkit6::SnapshotRegion, Option<graphene::Rect>),
+    SelectScreenshotRegion,
+    ScreenshotRegionSelected(Option<graphene::Rect>),
+    KeepScreenshot(gtk::gdk::Texture, String, String),
+    EditScreenshot,
+    ScreenshotElement,
+    ShowPageWatch,
+    SetPageWatch(Option<PageWatch>),
+    WatchedPageChanged(PageChange),
     BeginScreenshotFlash,
     ScreenshotFlashFinished,
     RetroactivelyLoadUserContentFilter(webkit6::UserContentFilterStore),
@@ -129,6 +253,23 @@ pub enum WebWindowInput {
     ReleaseHideHeaderBar,
     Peek(String),
     ShowToast(String),
+    ShowCookieManager,
+    ShowClearData,
+    SetCookieException(SiteException),
+    PermissionRequested(webkit6::PermissionRequest, Vec<PermissionKind>, String),
+    LoadStarted(String),
+    UpgradedToHttps(String),
+    LoadCommitted,
+    LoadFailed(String, webkit6::glib::Error),
+    SetAllowHttp(bool),
+    ProceedPastInterstitial,
+    LeaveInterstitial,
+    TlsErrors(
+        String,
+        webkit6::gio::TlsCertificate,
+        webkit6::gio::TlsCertificateFlags,
+    ),
+    ShowCertificate,
 }
 
 #[derive(Debug)]
@@ -146,7 +287,35 @@ relm4::new_stateless_action!(
     WebWindowActionGroup,
     "fullpage-screenshot"
 );
+relm4::new_stateless_action!(
+    RegionScreenshotAction,
+    WebWindowActionGroup,
+    "region-screenshot"
+);
+relm4::new_stateless_action!(
+    EditScreenshotAction,
+    WebWindowActionGroup,
+    "edit-screenshot"
+);
 relm4::new_stateful_action!(PeekAction, WebWindowActionGroup, "peek", String, ());
+relm4::new_stateless_action!(ManageCookiesAction, WebWindowActionGroup, "manage-cookies");
+relm4::new_stateless_action!(ClearDataAction, WebWindowActionGroup, "clear-data");
+relm4::new_stateless_action!(
+    ManagePasswordsAction,
+    WebWindowActionGroup,
+    "manage-passwords"
+);
+relm4::new_stateless_action!(SavePageAction, WebWindowActionGroup, "save-page");
+relm4::new_stateless_action!(WatchPageAction, WebWindowActionGroup, "watch-page");
+relm4::new_stateless_action!(PrintAction, WebWindowActionGroup, "print");
+relm4::new_stateless_action!(SaveAsPdfAction, WebWindowActionGroup, "save-as-pdf");
+relm4::new_stateful_action!(
+    RequestMobileSiteAction,
+    WebWindowActionGroup,
+    "request-mobile-site",
+    (),
+    bool
+);
 #[relm4::component(pub)]
 impl Component for WebWindow {
     type Init = (String, Option<webkit6::UserContentFilterStore>);
@@ -164,6 +333,47 @@ impl Component for WebWindow {
             adw::ToastOverlay {
                 #[name(main_overlay)]
                 gtk::Overlay {
+                    #[name(interstitial_page)]
+                    add_overlay = &adw::StatusPage {
+                        add_css_class: "background",
+                        #[track = "model.changed(WebWindow::interstitial())"]
+                        set_visible: model.interstitial.is_some(),
+                        set_icon_name: Some("padlock2-open"),
+                        #[track = "model.changed(WebWindow::interstitial())"]
+                        set_title: model.interstitial.as_ref().map(|interstitial| interstitial.title()).unwrap_or(""),
+                        #[track = "model.changed(WebWindow::interstitial())"]
+                        set_description: model.interstitial.as_ref().map(|interstitial| interstitial.description()).as_deref(),
+
+                        #[wrap(Some)]
+                        set_child = &gtk::Box {
+                            set_orientation: gtk::Orientation::Horizontal,
+                            set_halign: gtk::Align::Center,
+                            set_spacing: 12,
+
+                            gtk::Button {
+                                set_label: "Go Back",
+                                add_css_class: "pill",
+                                add_css_class: "suggested-action",
+                                connect_clicked => WebWindowInput::LeaveInterstitial,
+                            },
+
+                            gtk::Button {
+                                set_label: "View Certificate",
+                                add_css_class: "pill",
+                                #[track = "model.changed(WebWindow::interstitial())"]
+                                set_visible: model.interstitial.as_ref().is_some_and(|interstitial| interstitial.certificate().is_some()),
+                                connect_clicked => WebWindowInput::ShowCertificate,
+                            },
+
+                            gtk::Button {
+                                add_css_class: "pill",
+                                #[track = "model.changed(WebWindow::interstitial())"]
+                                set_label: model.interstitial.as_ref().map(|interstitial| interstitial.proceed_label()).unwrap_or(""),
+                                connect_clicked => WebWindowInput::ProceedPastInterstitial,
+                            },
+                        },
+                    },
+
                     #[name(toolbar_view)]
                     add_overlay = &adw::ToolbarView {
                         set_halign: gtk::Align::Fill,
@@ -216,7 +426,7 @@ impl Component for WebWindow {
                                 adw::SplitButton {
                                     set_icon_name: "screenshooter",
                                     set_tooltip_text: Some("Take a screenshot"),
-                                    connect_clicked => WebWindowInput::Screenshot(false, webkit6::SnapshotRegion::Visible),
+                                    connect_clicked => WebWindowInput::Screenshot(false, webkit6::SnapshotRegion::Visible, None),
                                     #[wrap(Some)]
                                     set_popover = &gtk::PopoverMenu::from_model(Some(&screenshot_menu)) {
                                         connect_show => WebWindowInput::InhibitHideHeaderBar,
@@ -231,10 +441,171 @@ impl Component for WebWindow {
                                 gtk::Box {
                                     set_orientation: gtk::Orientation::Horizontal,
 
+                                    #[name(padlock_btn)]
+                                    gtk::MenuButton {
+                                        add_css_class: "flat",
+                                        set_tooltip_text: Some("Site information"),
+                                        #[track = "model.changed(WebWindow::url())"]
+                                        set_icon_name: padlock_icon_name(&model.url),
+                                        #[track = "model.changed(WebWindow::insecure_resources())"]
+                                        add_css_class?: if !model.insecure_resources.is_empty() {
+                                            Some("warning")
+                                        } else {None},
+                                        #[track = "model.changed(WebWindow::insecure_resources())"]
+                                        remove_css_class?: if model.insecure_resources.is_empty() {
+                                            Some("warning")
+                                        } else {None},
+                                        #[wrap(Some)]
+                                        set_popover = &gtk::Popover {
+                                            connect_show[sender] => move |_| {
+                                                sender.input(WebWindowInput::InhibitHideHeaderBar);
+                                                sender.input(WebWindowInput::CountTrackers);
+                                            },
+                                            connect_closed => WebWindowInput::ReleaseHideHeaderBar,
+
+                                            #[name(site_popover_box)]
+                                            gtk::Box {
+                                                set_orientation: gtk::Orientation::Vertical,
+                                                set_spacing: 12,
+                                                set_margin_all: 6,
+                                                set_width_request: 300,
+
+                                                gtk::Label {
+                                                    add_css_class: "heading",
+                                                    set_ellipsize: gtk::pango::EllipsizeMode::Middle,
+                                                    #[track = "model.changed(WebWindow::host())"]
+                                                    set_label: model.host.as_deref().unwrap_or(""),
+                                                },
+
+                                                #[name(site_security_label)]
+                                                gtk::Label {
+                                                    set_wrap: true,
+                                                    #[track = "model.changed(WebWindow::url()) || model.changed(WebWindow::insecure_resources())"]
+                                                    set_label: if !model.url.starts_with("https://") {
+                                                        "Your connection to this site is not secure"
+                                                    } else if !model.insecure_resources.is_empty() {
+                                                        "Your connection to this site is secure, but this page has insecure content"
+                                                    } else {
+                                                        "Your connection to this site is secure"
+                                                    },
+                                                },
+
+                                                gtk::Label {
+                                                    add_css_class: "caption",
+                                                    set_wrap: true,
+                                                    set_wrap_mode: gtk::pango::WrapMode::WordChar,
+                                                    set_xalign: 0.0,
+                                                    #[track = "model.changed(WebWindow::insecure_resources())"]
+                                                    set_visible: !model.insecure_resources.is_empty(),
+                                                    #[track = "model.changed(WebWindow::insecure_resources()) || model.changed(WebWindow::mixed_content_blocking()) || model.changed(WebWindow::allow_insecure_content())"]
+                                                    set_label: &describe_insecure_resources(
+                                                        &model.insecure_resources,
+                                                        model.mixed_content_blocking && !model.allow_insecure_content,
+                                                    ),
+                                                },
+
+                                                gtk::Button {
+                                                    set_label: "View Certificate",
+                                                    #[track = "model.changed(WebWindow::url())"]
+                                                    set_visible: model.url.starts_with("https://"),
+                                                    connect_clicked => WebWindowInput::ShowCertificate,
+                                                },
+
+                                                gtk::Box {
+                                                    set_orientation: gtk::Orientation::Horizontal,
+                                                    set_spacing: 6,
+
+                                                    gtk::Label {
+                                                        set_hexpand: true,
+                                                        set_xalign: 0.0,
+                                                        set_wrap: true,
+                                                        #[track = "model.changed(WebWindow::trackers_on_site())"]
+                                                        set_label: &match model.trackers_on_site {
+                                                            0 => String::from("No trackers found on this site"),
+                                                            1 => String::from("1 tracker prevented from following you"),
+                                                            trackers_on_site => format!("{trackers_on_site} trackers prevented from following you"),
+                                                        },
+                                                    },
+
+                                                    gtk::Button {
+                                                        set_label: "Details",
+                                                        set_valign: gtk::Align::Center,
+                                                        #[track = "model.changed(WebWindow::trackers_on_site())"]
+                                                        set_visible: model.trackers_on_site > 0,
+                                                        connect_clicked => WebWindowInput::ShowTrackers,
+                                                    },
+                                                },
+
+                                                #[name(site_settings_list)]
+                                                gtk::ListBox {
+                                                    add_css_class: "boxed-list",
+                                                    set_selection_mode: gtk::SelectionMode::None,
+                                                    #[track = "model.changed(WebWindow::host())"]
+                                                    set_sensitive: model.host.is_some(),
+
+                                                    adw::ComboRow {
+                                                        set_title: "Cookies",
+                                                        set_model: Some(&gtk::StringList::new(&SiteException::LABELS)),
+                                                        #[track = "model.changed(WebWindow::cookie_exception())"]
+                                                        set_selected: model.cookie_exception.index(),
+                                                        connect_selected_notify[sender] => move |this_row| {
+                                                            sender.input(WebWindowInput::SetCookieException(SiteException::from_index(this_row.selected())));
+                                                        },
+                                                    },
+
+                                                    adw::SwitchRow {
+                                                        set_title: "Allow Insecure Connections",
+                                                        set_subtitle: "Don't upgrade to HTTPS",
+                                                        #[track = "model.changed(WebWindow::allow_http())"]
+                                                        set_active: model.allow_http,
+                                                        connect_active_notify[sender] => move |this_row| {
+                                                            sender.input(WebWindowInput::SetAllowHttp(this_row.is_active()));
+                                                        },
+                                                    },
+
+                                                    adw::ComboRow {
+                                                        set_title: "User Agent",
+                                                        set_model: Some(&gtk::StringList::new(&user_agent_labels())),
+                                                        #[track = "model.changed(WebWindow::site_user_agent())"]
+                                                        set_selected: user_agent_index(model.site_user_agent.as_deref()),
+                                                        connect_selected_notify[sender] => move |this_row| {
+                                                            // Choosing Custom leaves the user agent alone until one is entered below
+                                                            if this_row.selected() + 1 < this_row.model().map_or(0, |model| model.n_items()) {
+                                                                sender.input(WebWindowInput::SetSiteUserAgent(user_agent_preset(this_row.selected()).map(String::from)));
+                                                            }
+                                                        },
+                                                    },
+
+                                                    adw::EntryRow {
+                                                        set_title: "Custom User Agent",
+                                                        set_show_apply_button: true,
+                                                        #[track = "model.changed(WebWindow::site_user_agent())"]
+                                                        set_text: model.site_user_agent.as_deref().unwrap_or(""),
+                                                        connect_apply[sender] => move |this_row| {
+                                                            sender.input(WebWindowInput::SetSiteUserAgent(Some(this_row.text().to_string())));
+                                                        },
+                                                    },
+
+                                                    adw::SwitchRow {
+                                                        set_title: "Allow Insecure Content",
+                                                        set_subtitle: "Load resources over HTTP on this site",
+                                                        #[track = "model.changed(WebWindow::mixed_content_blocking())"]
+                                                        set_visible: model.mixed_content_blocking,
+                                                        #[track = "model.changed(WebWindow::allow_insecure_content())"]
+                                                        set_active: model.allow_insecure_content,
+                                                        connect_active_notify[sender] => move |this_row| {
+                                                            sender.input(WebWindowInput::SetAllowInsecureContent(this_row.is_active()));
+                                                        },
+                                                    },
+                                                },
+                                            },
+                                        },
+                                    },
+
                                     if model.in_title_edit_mode {
                                         #[name(title_edit_entry)]
                                         gtk::Entry {
-                                            set_margin_start: 24,
+                                            set_margin_start: 6,
                                             // set_width_request: 350,
                                             #[track = "model.changed(WebWindow::in_title_edit_mode())"]
                                             grab_focus: (),
@@ -257,7 +628,7 @@ impl Component for WebWindow {
                                         }
                                     } else {
                                         gtk::Button {
-                                            set_margin_start: 24,
+                                            set_margin_start: 6,
                                             // set_width_request: 350,
                                             set_can_shrink: true,
                                             set_tooltip_text: Some("Click to enter link or search"),
@@ -266,18 +637,8 @@ impl Component for WebWindow {
                                             set_child = &gtk::Box {
                                                 set_orientation: gtk::Orientation::Horizontal,
                                                 set_halign: gtk::Align::Center,
-                                                #[name(padlock_image)]
-                                                gtk::Image {
-                                                    #[track = "model.changed(WebWindow::url())"]
-                                                    set_icon_name: if model.url.starts_with("https://") || model.url.starts_with("webkit://") {
-                                                        Some("padlock2")
-                                                    } else if model.url.starts_with("http://") {
-                                                        Some("padlock2-open")
-                                                    } else {None},
-                                                },
 
                                                 gtk::Label {
-                                                    set_margin_start: 7,
                                                     set_ellipsize: gtk::pango::EllipsizeMode::End,
                                                     #[track = "model.changed(WebWindow::title())"]
                                                     set_label: model.title.as_str()
@@ -300,6 +661,23 @@ impl Component for WebWindow {
                             pack_end = &gtk::Box {
                                 set_orientation: gtk::Orientation::Horizontal,
 
+                                gtk::ToggleButton {
+                                    set_icon_name: "text-x-script-symbolic",
+                                    #[track = "model.changed(WebWindow::javascript_enabled())"]
+                                    set_tooltip_text: if model.javascript_enabled {
+                                        Some("Block JavaScript on this site")
+                                    } else {
+                                        Some("Allow JavaScript on this site")
+                                    },
+                                    #[track = "model.changed(WebWindow::host())"]
+                                    set_sensitive: model.host.is_some(),
+                                    #[track = "model.changed(WebWindow::javascript_enabled())"]
+                                    set_active: model.javascript_enabled,
+                                    connect_toggled[sender] => move |this_button| {
+                                        sender.input(WebWindowInput::SetJavaScriptEnabled(this_button.is_active()));
+                                    },
+                                },
+
                                 gtk::Button {
                                     set_icon_name: "pin",
                                     #[track = "model.changed(WebWindow::pin_headerbar())"]
@@ -335,6 +713,17 @@ impl Component for WebWindow {
                                     },
                                     set_tooltip_text: Some("Toggle fullscreen"),
                                     connect_clicked => WebWindowInput::ToggleFullscreen,
+                                },
+
+                                #[name(page_menu_btn)]
+                                gtk::MenuButton {
+                                    set_icon_name: "menu",
+                                    set_tooltip_text: Some("Page menu"),
+                                    #[wrap(Some)]
+                                    set_popover = &gtk::PopoverMenu::from_model(Some(&page_menu)) {
+                                        connect_show => WebWindowInput::InhibitHideHeaderBar,
+                                        connect_closed => WebWindowInput::ReleaseHideHeaderBar,
+                                    },
                                 }
                             }
                         },
@@ -352,11 +741,22 @@ impl Component for WebWindow {
                         load_uri: &model.url,
                         set_vexpand: true,
                         set_background_color: &match_style_with_rgb(relm4::main_adw_application()),
-                        connect_load_changed[sender] => move |this_webview, _load_event| {
+                        connect_load_changed[sender] => move |this_webview, load_event| {
                             let url = match this_webview.uri() {
                                 Some(url) => url,
                                 None => GString::new()
                             };
+                            match load_event {
+                                webkit6::LoadEvent::Started | webkit6::LoadEvent::Redirected => {
+                                    if let Some(web_view_settings) = webkit6::prelude::WebViewExt::settings(this_webview) {
+                                        web_view_settings.set_enable_javascript(javascript_enabled_for(origin_of(&url).as_deref()));
+                                    }
+                                    sender.input(WebWindowInput::LoadStarted(url.to_string()));
+                                }
+                                webkit6::LoadEvent::Committed => sender.input(WebWindowInput::LoadCommitted),
+                                webkit6::LoadEvent::Finished => sender.input(WebWindowInput::ScanInsecureContent),
+                                _ => {}
+                            }
                             sender.input(WebWindowInput::NavigationHistoryChanged(this_webview.can_go_back(), this_webview.can_go_forward()));
                             sender.input(WebWindowInput::UrlChanged(url.to_string()))
                         },
@@ -371,8 +771,74 @@ impl Component for WebWindow {
                                 None => String::from("")
                             }));
                         },
+                        connect_decide_policy[sender, http_allowed_once = model.http_allowed_once.clone()] => move |this_webview, decision, decision_type| {
+                            match decision_type {
+                                webkit6::PolicyDecisionType::NavigationAction => {
+                                    let Some(url) = decision
+                                        .downcast_ref::<webkit6::NavigationPolicyDecision>()
+                                        .and_then(|decision| decision.navigation_action())
+                                        .and_then(|mut navigation_action| navigation_action.request())
+                                        .and_then(|request| request.uri())
+                                    else {
+                                        return false;
+                                    };
+                                    // WebKit doesn't say which frame a navigation is for, but loads started with load_uri
+                                    // replace the whole page and already show their address. Other navigations are prepared
+                                    // for once their response says whether it is the page or something inside it.
+                                    if this_webview.uri().as_deref() != Some(url.as_str()) {
+                                        return false;
+                                    }
+                                    prepare_page_load(this_webview, decision, &url, &http_allowed_once, &sender)
+                                }
+                                webkit6::PolicyDecisionType::Response => {
+                                    let page_url = decision
+                                        .downcast_ref::<webkit6::ResponsePolicyDecision>()
+                                        .filter(|decision| decision.is_main_frame_main_resource())
+                                        .and_then(|decision| decision.request())
+                                        .and_then(|request| request.uri());
+                                    if let Some(page_url) = page_url {
+                                        if prepare_page_load(this_webview, decision, &page_url, &http_allowed_once, &sender) {
+                                            return true;
+                                        }
+                                        // Continuing over HTTP only lasts for the navigation it was chosen for
+                                        http_allowed_once.borrow_mut().take();
+                                    }
+                                    decide_response_policy(this_webview, decision)
+                                }
+                                _ => false,
+                            }
+                        },
+                        connect_load_failed[sender] => move |_, _, failing_uri, error| {
+                            sender.input(WebWindowInput::LoadFailed(failing_uri.to_string(), error.clone()));
+                            false
+                        },
+                        connect_load_failed_with_tls_errors[sender] => move |_, failing_uri, certificate, errors| {
+                            sender.input(WebWindowInput::TlsErrors(failing_uri.to_string(), certificate.clone(), errors));
+                            // The interstitial explains what went wrong instead of WebKit's error page
+                            true
+                        },
+                        connect_permission_request[sender] => move |this_webview, permission_request| {
+                            let permission_kinds = permission_kinds(permission_request);
+                            let origin = this_webview.uri().and_then(|uri| origin_of(&uri));
+                            match (permission_kinds.is_empty(), origin) {
+                                (false, Some(origin)) => {
+                                    match permission_decision(&origin, &permission_kinds) {
+                                        Some(true) => permission_request.allow(),
+                                        Some(false) => permission_request.deny(),
+                                        None => sender.input(WebWindowInput::PermissionRequested(permission_request.clone(), permission_kinds, origin)),
+                                    }
+                                    true
+                                }
+                                // Leave requests Spidey doesn't know how to ask about to WebKit
+                                _ => false
+                            }
+                        },
+                        connect_authenticate[sender] => move |_, authentication_request| {
+                            sender.input(WebWindowInput::Authenticate(authentication_request.clone()));
+                            true
+                        },
                         connect_insecure_content_detected[sender] => move |_, _| {
-                            sender.input(WebWindowInput::InsecureContentDetected);
+                            sender.input(WebWindowInput::ScanInsecureContent);
                         },
                         connect_create[sender] => move |this_webview, _navigation_action| {
                             let new_webview = webkit6::glib::Object::builder::<webkit6::WebView>().property("related-view", this_webview).build();
@@ -383,6 +849,16 @@ impl Component for WebWindow {
                             new_webview.into()
 
                         },
+                        connect_context_menu[sender] => move |this_webview, context_menu, _| {
+                            take_context_menu_element(this_webview, context_menu);
+                            let screenshot_element_action = SimpleAction::new("screenshot-element", None);
+                            screenshot_element_action.connect_activate(clone!(@strong sender => move |_, _| {
+                                sender.input(WebWindowInput::ScreenshotElement);
+                            }));
+                            context_menu.append(&ContextMenuItem::new_separator());
+                            context_menu.append(&ContextMenuItem::from_gaction(&screenshot_element_action, "Screenshot this element", None));
+                            false
+                        },
                         // connect_notify: (Some("url"), clone!(@strong sender => move |this_webview, _| {
                         //     sender.input(WebWindowInput::UrlChanged(match this_webview.uri() {Some(url)=> url.to_string(), None=>"".to_string()}))
                         // }))
@@ -412,6 +888,18 @@ impl Component for WebWindow {
     menu! {
         screenshot_menu: {
             "Take screenshot of full page" => FullPageScreenshotAction,
+            "Take screenshot of region" => RegionScreenshotAction,
+            "Edit last screenshot" => EditScreenshotAction,
+        },
+        page_menu: {
+            "Request Mobile Site" => RequestMobileSiteAction,
+            "Manage Cookies" => ManageCookiesAction,
+            "Passwords" => ManagePasswordsAction,
+            "Clear Browsing Data" => ClearDataAction,
+            "Watch for Changes…" => WatchPageAction,
+            "Save Page…" => SavePageAction,
+            "Print…" => PrintAction,
+            "Save as PDF…" => SaveAsPdfAction,
         }
     }
 
@@ -427,6 +915,8 @@ impl Component for WebWindow {
             .halign(gtk::Align::Fill)
             .build();
         screenshot_flash_box.add_css_class("screenshot-in-progress");
+        let host = host_of(&init.0);
+        let origin = origin_of(&init.0);
         let mut model = WebWindow {
             url: init.0.clone(),
             screenshot_flash_box,
@@ -440,6 +930,35 @@ impl Component for WebWindow {
             pin_headerbar: false,
             web_view: None,
             toast_overlay: None,
+            cookie_exception: match &host {
+                Some(host) => site_exception("cookie-exceptions", host),
+                None => SiteException::Default,
+            },
+            allow_http: match &host {
+                Some(host) => site_exception("https-only-exceptions", host) == SiteException::Allow,
+                None => false,
+            },
+            interstitial: None,
+            insecure_resources: vec![],
+            mixed_content_blocking: gsettings().boolean("mixed-content-blocking"),
+            allow_insecure_content: match &host {
+                Some(host) => {
+                    site_exception("mixed-content-exceptions", host) == SiteException::Allow
+                }
+                None => false,
+            },
+            javascript_enabled: javascript_enabled_for(origin.as_deref()),
+            site_user_agent: user_agent_for(host.as_deref()),
+            request_mobile_site_action: None,
+            last_screenshot: None,
+            edit_screenshot_action: None,
+            region_selector: None,
+            page_watch: None,
+            trackers_on_site: 0,
+            gsettings: gsettings(),
+            https_upgrade: None,
+            http_allowed_once: Rc::new(RefCell::new(None)),
+            host,
             tracker: 0,
         };
         let widgets = view_output!();
@@ -447,7 +966,12 @@ impl Component for WebWindow {
         model.set_toast_overlay(Some(widgets.toast_overlay.clone()));
         let fullpage_screenshot_action: RelmAction<FullPageScreenshotAction> = {
             RelmAction::new_stateless(clone!(@strong sender => move |_| {
-                sender.input(WebWindowInput::Screenshot(false, webkit6::SnapshotRegion::FullDocument));
+                sender.input(WebWindowInput::Screenshot(false, webkit6::SnapshotRegion::FullDocument, None));
+            }))
+        };
+        let region_screenshot_action: RelmAction<RegionScreenshotAction> = {
+            RelmAction::new_stateless(clone!(@strong sender => move |_| {
+                sender.input(WebWindowInput::SelectScreenshotRegion);
             }))
         };
         let peek_action: RelmAction<PeekAction> = RelmAction::new_stateful_with_target_value(
@@ -456,21 +980,79 @@ impl Component for WebWindow {
                 sender.input(WebWindowInput::Peek(url));
             }),
         );
+        let manage_cookies_action: RelmAction<ManageCookiesAction> = {
+            RelmAction::new_stateless(clone!(@strong sender => move |_| {
+                sender.input(WebWindowInput::ShowCookieManager);
+            }))
+        };
+        let manage_passwords_action: RelmAction<ManagePasswordsAction> = {
+            RelmAction::new_stateless(clone!(@strong sender => move |_| {
+                sender.input(WebWindowInput::ShowPasswordManager);
+            }))
+        };
+        let clear_data_action: RelmAction<ClearDataAction> = {
+            RelmAction::new_stateless(clone!(@strong sender => move |_| {
+                sender.input(WebWindowInput::ShowClearData);
+            }))
+        };
+        let edit_screenshot_action: RelmAction<EditScreenshotAction> = {
+            RelmAction::new_stateless(clone!(@strong sender => move |_| {
+                sender.input(WebWindowInput::EditScreenshot);
+            }))
+        };
+        // There is nothing to edit until a screenshot is taken
+        edit_screenshot_action.gio_action().set_enabled(false);
+        model.edit_screenshot_action = Some(edit_screenshot_action.gio_action().clone());
+        let watch_page_action: RelmAction<WatchPageAction> = {
+            RelmAction::new_stateless(clone!(@strong sender => move |_| {
+                sender.input(WebWindowInput::ShowPageWatch);
+            }))
+        };
+        let save_page_action: RelmAction<SavePageAction> = {
+            RelmAction::new_stateless(clone!(@strong sender => move |_| {
+                sender.input(WebWindowInput::SavePage);
+            }))
+        };
+        let print_action: RelmAction<PrintAction> = {
+            RelmAction::new_stateless(clone!(@strong sender => move |_| {
+                sender.input(WebWindowInput::Print);
+            }))
+        };
+        let save_as_pdf_action: RelmAction<SaveAsPdfAction> = {
+            RelmAction::new_stateless(clone!(@strong sender => move |_| {
+                sender.input(WebWindowInput::SaveAsPdf);
+            }))
+        };
+        let request_mobile_site_action: RelmAction<RequestMobileSiteAction> =
+            RelmAction::new_stateful(
+                &(model.site_user_agent.as_deref() == Some(MOBILE_USER_AGENT)),
+                clone!(@strong sender => move |_, request_mobile_site| {
+                    *request_mobile_site = !*request_mobile_site;
+                    sender.input(WebWindowInput::SetSiteUserAgent(
+                        request_mobile_site.then(|| String::from(MOBILE_USER_AGENT)),
+                    ));
+                }),
+            );
+        model.request_mobile_site_action = Some(request_mobile_site_action.gio_action().clone());
         let mut webwindow_action_group: RelmActionGroup<WebWindowActionGroup> =
             RelmActionGroup::new();
         webwindow_action_group.add_action(fullpage_screenshot_action);
+        webwindow_action_group.add_action(region_screenshot_action);
+        webwindow_action_group.add_action(edit_screenshot_action);
+        webwindow_action_group.add_action(manage_cookies_action);
+        webwindow_action_group.add_action(manage_passwords_action);
+        webwindow_action_group.add_action(clear_data_action);
+        webwindow_action_group.add_action(watch_page_action);
+        webwindow_action_group.add_action(save_page_action);
+        webwindow_action_group.add_action(print_action);
+        webwindow_action_group.add_action(save_as_pdf_action);
+        webwindow_action_group.add_action(request_mobile_site_action);
         // webwindow_action_group.add_action(peek_action);
         webwindow_action_group.register_for_widget(root.clone());
 
-        widgets.padlock_image.set_icon_name(
-            if model.url.starts_with("https://") || model.url.starts_with("webkit://") {
-                Some("padlock2")
-            } else if model.url.starts_with("http://") {
-                Some("padlock2-open")
-            } else {
-                None
-            },
-        );
+        widgets
+            .padlock_btn
+            .set_icon_name(padlock_icon_name(&model.url));
         // Make the main app be aware of this new window so it doesn't quit when main window is closed
         // relm4::main_adw_application().add_window(&Self::builder().root);
         let show_toolbars_event_controller = EventControllerMotion::new();
@@ -494,11 +1076,46 @@ impl Component for WebWindow {
         {
             web_view_settings.set_media_playback_requires_user_gesture(true);
             web_view_settings.set_enable_back_forward_navigation_gestures(true);
+            web_view_settings.set_enable_javascript(model.javascript_enabled);
+            web_view_settings.set_user_agent(model.site_user_agent.as_deref());
             if PROFILE == "Devel" {
                 web_view_settings.set_enable_developer_extras(true);
             }
         }
 
+        // Set up mixed content blocking, and keep it up to date when it is changed here or in another window
+        if let Some(user_content_manager) = widgets.web_view.user_content_manager() {
+            apply_mixed_content_blocking(&user_content_manager, || {});
+        }
+        for key in ["mixed-content-blocking", "mixed-content-exceptions"] {
+            model.gsettings.connect_changed(
+                Some(key),
+                clone!(@strong sender => move |_, _| {
+                    sender.input(WebWindowInput::MixedContentBlockingChanged);
+                }),
+            );
+        }
+
+        // Fill in saved passwords and offer to save new ones
+        if let Some(user_content_manager) = widgets.web_view.user_content_manager() {
+            set_up_password_autofill(
+                &user_content_manager,
+                clone!(@strong sender => move |credential| {
+                    sender.input(WebWindowInput::OfferToSaveCredential(credential));
+                }),
+            );
+        }
+
+        // Remember what was right-clicked, for screenshots of a single element
+        if let Some(user_content_manager) = widgets.web_view.user_content_manager() {
+            set_up_element_screenshots(&user_content_manager);
+        }
+
+        // Pages are checked in the background, this is just so the watch can be changed from here
+        if let Some(page_watch) = page_watch_for(&model.url) {
+            sender.input(WebWindowInput::SetPageWatch(Some(page_watch)));
+        }
+
         // Set up adblock
         if let Some(user_content_manager) = widgets.web_view.user_content_manager() {
             if let Some(user_content_filter_store) = init.1 {
@@ -517,18 +1134,29 @@ impl Component for WebWindow {
         // Handle things related to the Network Session
         let toast_overlay_widget_clone = widgets.toast_overlay.clone();
         if let Some(session) = widgets.web_view.network_session() {
-            // Handle downloads
+            // Handle downloads. Where they are saved and their progress is handled by the main window.
             session.connect_download_started(clone!(@strong toast_overlay_widget_clone as toast_overlay => move |this_session, download_object| {
+                // PDFs shown in the viewer are not downloads as far as the user is concerned
+                if is_pdf_viewer_download(download_object) {
+                    return;
+                }
                 let did_download_fail = Arc::new(AtomicBool::new(false));
-                download_object.connect_decide_destination(|this_download_object, suggested_filename| {
-                    this_download_object.set_destination(Document::at(User(Downloads(&[])), suggested_filename, Create::No).suggest_rename().as_str());
-                    true
-                });
                 download_object.connect_created_destination(clone!(@strong toast_overlay, @strong did_download_fail, @strong sender => move |this_download_object, destination| {
                     let destination_string = destination.to_string();
+                    let folder_name = std::path::Path::new(&destination_string)
+                        .parent()
+                        .and_then(|folder| folder.file_name())
+                        .map(|folder_name| folder_name.to_string_lossy().to_string())
+                        .unwrap_or(String::from("Downloads"));
+                    let change_location_toast = adw::Toast::new(format!("Saving to {folder_name} folder").as_str());
+                    change_location_toast.set_button_label(Some("Change Location"));
+                    change_location_toast.connect_button_clicked(clone!(@strong this_download_object, @strong toast_overlay => move |_| {
+                        change_download_destination(&this_download_object, &toast_overlay);
+                    }));
+                    toast_overlay.add_toast(change_location_toast);
                     this_download_object.connect_finished(clone!(@strong toast_overlay, @strong did_download_fail, @strong destination_string => move |this_download_object| {
                         if (*did_download_fail).load(std::sync::atomic::Ordering::Relaxed) {return;}
-                        let toast = adw::Toast::new("File saved to Downloads folder");
+                        let toast = adw::Toast::new(format!("File saved to {folder_name} folder").as_str());
                         toast.set_button_label(Some("Open"));
                         let toast_overlay_clone = toast_overlay.clone();
                         let destination_string_clone = destination_string.clone();
@@ -545,14 +1173,26 @@ impl Component for WebWindow {
                 download_object.connect_failed(clone!(@strong toast_overlay ,@strong did_download_fail => move |this_download_object, error| {
                     (*did_download_fail).store(true, std::sync::atomic::Ordering::Relaxed);
                     eprintln!("{}", error.to_string());
+                    if error.matches(webkit6::DownloadError::CancelledByUser) {
+                        return;
+                    }
                     toast_overlay
-                        .add_toast(adw::Toast::new("Download failed"));
+                        .add_toast(adw::Toast::new(format!("Download failed: {}. It can be retried from the downloads list.", error.message()).as_str()));
                 }));
             }));
 
             // Enable Intelligent Tracking Prevention
             session.set_itp_enabled(true);
 
+            // Apply the cookie accept policy and the per-site exceptions to it
+            apply_cookie_policy(&session);
+            if let Some(user_content_manager) = widgets.web_view.user_content_manager() {
+                set_up_cookie_exceptions(&user_content_manager);
+            }
+
+            // Connect through the proxy chosen in the preferences
+            apply_proxy_settings(&session);
+
             // Handle persistent cookies
             with(
                 &[Document::at(
@@ -572,6 +1212,11 @@ impl Component for WebWindow {
             );
         }
 
+        // Show PDFs with the bundled pdf.js
+        if let Some(web_context) = widgets.web_view.context() {
+            register_pdf_viewer_scheme(&web_context);
+        }
+
         ComponentParts {
             model: model,
             widgets: widgets,
@@ -650,6 +1295,34 @@ impl Component for WebWindow {
                         .expect("Could not send output WebWindowOutput::TitleChanged");
                 }
                 WebWindowInput::UrlChanged(url) => {
+                    let host = host_of(&url);
+                    if host != self.host {
+                        self.set_cookie_exception(match &host {
+                            Some(host) => site_exception("cookie-exceptions", host),
+                            None => SiteException::Default,
+                        });
+                        self.set_allow_http(match &host {
+                            Some(host) => {
+                                site_exception("https-only-exceptions", host) == SiteException::Allow
+                            }
+                            None => false,
+                        });
+                        self.set_allow_insecure_content(match &host {
+                            Some(host) => {
+                                site_exception("mixed-content-exceptions", host) == SiteException::Allow
+                            }
+                            None => false,
+                        });
+                        let site_user_agent = user_agent_for(host.as_deref());
+                        if let Some(request_mobile_site_action) = &self.request_mobile_site_action {
+                            request_mobile_site_action.set_state(
+                                &(site_user_agent.as_deref() == Some(MOBILE_USER_AGENT)).to_variant(),
+                            );
+                        }
+                        self.set_site_user_agent(site_user_agent);
+                        self.set_host(host);
+                    }
+                    self.set_javascript_enabled(javascript_enabled_for(origin_of(&url).as_deref()));
                     self.set_url(url.clone());
                     sender.output(WebWindowOutput::UrlChanged(self.url.clone())).discard();
                 }
@@ -678,16 +1351,264 @@ impl Component for WebWindow {
                     self.set_can_go_forward(can_go_forward);
                     _ = sender.output(WebWindowOutput::LoadChanged(can_go_back, can_go_forward));
                 }
-                WebWindowInput::InsecureContentDetected => widgets
-                    .toast_overlay
-                    .add_toast(adw::Toast::new("This page is insecure")),
-                WebWindowInput::Screenshot(need_return_main_app, snapshot_region) => {
+                WebWindowInput::ScanInsecureContent => {
+                    if self.url.starts_with("https://") {
+                        self.web_view.clone()?.evaluate_javascript(
+                            INSECURE_RESOURCES_SCRIPT,
+                            None,
+                            None,
+                            gtk::gio::Cancellable::NONE,
+                            clone!(@strong sender => move |result| match result {
+                                Ok(value) => sender.input(WebWindowInput::InsecureResourcesFound(
+                                    value
+                                        .to_str()
+                                        .lines()
+                                        .map(|url| url.to_string())
+                                        .collect(),
+                                )),
+                                Err(error) => eprintln!("Could not look for insecure content: {error}"),
+                            }),
+                        );
+                    }
+                }
+                WebWindowInput::InsecureResourcesFound(insecure_resources) => {
+                    if insecure_resources != self.insecure_resources {
+                        self.set_insecure_resources(insecure_resources);
+                    }
+                }
+                WebWindowInput::MixedContentBlockingChanged => {
+                    self.set_mixed_content_blocking(self.gsettings.boolean("mixed-content-blocking"));
+                    if let Some(host) = &self.host {
+                        self.set_allow_insecure_content(
+                            site_exception("mixed-content-exceptions", host) == SiteException::Allow,
+                        );
+                    }
+                    let web_view = self.web_view.clone()?;
+                    let needs_reload = !self.insecure_resources.is_empty();
+                    apply_mixed_content_blocking(
+                        &web_view.user_content_manager()?,
+                        clone!(@strong web_view => move || {
+                            // Pages with insecure content have to be loaded again for the change to take effect
+                            if needs_reload {
+                                web_view.reload();
+                            }
+                        }),
+                    );
+                }
+                WebWindowInput::SetJavaScriptEnabled(javascript_enabled) => {
+                    if javascript_enabled != self.javascript_enabled {
+                        set_javascript_enabled_for(&origin_of(&self.url)?, javascript_enabled);
+                        self.set_javascript_enabled(javascript_enabled);
+                        let web_view = self.web_view.clone()?;
+                        webkit6::prelude::WebViewExt::settings(&web_view)?
+                            .set_enable_javascript(javascript_enabled);
+                        let toast = adw::Toast::builder()
+                            .title(if javascript_enabled {
+                                "JavaScript allowed on this site"
+                            } else {
+                                "JavaScript blocked on this site"
+                            })
+                            .button_label("Reload")
+                            .build();
+                        toast.connect_button_clicked(move |_| web_view.reload());
+                        widgets.toast_overlay.add_toast(toast);
+                    }
+                }
+                WebWindowInput::SetSiteUserAgent(user_agent) => {
+                    let user_agent = user_agent.filter(|user_agent| !user_agent.is_empty());
+                    if user_agent != self.site_user_agent {
+                        set_user_agent_for(&self.host.clone()?, user_agent.as_deref());
+                        if let Some(request_mobile_site_action) = &self.request_mobile_site_action {
+                            request_mobile_site_action.set_state(
+                                &(user_agent.as_deref() == Some(MOBILE_USER_AGENT)).to_variant(),
+                            );
+                        }
+                        let web_view = self.web_view.clone()?;
+                        webkit6::prelude::WebViewExt::settings(&web_view)?
+                            .set_user_agent(user_agent.as_deref());
+                        self.set_site_user_agent(user_agent);
+                        web_view.reload();
+                    }
+                }
+                WebWindowInput::CountTrackers => {
+                    let host = self.host.clone()?;
+                    load_trackers(
+                        &self.web_view.clone()?.network_session()?,
+                        clone!(@strong sender => move |trackers| {
+                            sender.input(WebWindowInput::TrackersCounted(
+                                trackers.iter().filter(|tracker| tracker.is_on(&host)).count(),
+                            ));
+                        }),
+                    );
+                }
+                WebWindowInput::TrackersCounted(trackers_on_site) => {
+                    self.set_trackers_on_site(trackers_on_site)
+                }
+                WebWindowInput::ShowTrackers => present_tracking_prevention(
+                    self.web_view.clone()?.network_session()?,
+                    self.host.clone(),
+                    root,
+                ),
+                WebWindowInput::OfferToSaveCredential(credential) => {
+                    offer_to_save_credential(credential, root)
+                }
+                WebWindowInput::ShowPasswordManager => present_password_manager(root),
+                WebWindowInput::Authenticate(authentication_request) => {
+                    widgets.web_window.present();
+                    handle_authentication(authentication_request, root);
+                }
+                WebWindowInput::SetAllowInsecureContent(allow_insecure_content) => {
+                    if allow_insecure_content != self.allow_insecure_content {
+                        set_site_exception(
+                            "mixed-content-exceptions",
+                            &self.host.clone()?,
+                            if allow_insecure_content {
+                                SiteException::Allow
+                            } else {
+                                SiteException::Default
+                            },
+                        );
+                    }
+                }
+                WebWindowInput::ShowPageWatch => {
+                    let url = match &self.page_watch {
+                        Some(page_watch) => page_watch.url.clone(),
+                        None => self.url.clone(),
+                    };
+                    present_page_watch_dialog(
+                        url,
+                        self.page_watch.clone(),
+                        clone!(@strong sender => move |page_watch| {
+                            sender.input(WebWindowInput::SetPageWatch(page_watch));
+                        }),
+                        root,
+                    );
+                }
+                WebWindowInput::SetPageWatch(page_watch) => self.page_watch = page_watch,
+                WebWindowInput::WatchedPageChanged(page_change) => {
+                    let title = self.title.clone();
+                    let toast = adw::Toast::builder()
+                        .title(format!("Page changed by {:.1}%", page_change.percent()))
+                        .button_label("Show Changes")
+                        .timeout(0)
+                        .build();
+                    toast.connect_button_clicked(clone!(@strong root, @strong title, @strong page_change => move |_| {
+                        present_page_changes(title.clone(), page_change.clone(), &root);
+                    }));
+                    widgets.toast_overlay.add_toast(toast);
+                }
+                WebWindowInput::SavePage => {
+                    save_page(
+                        &widgets.web_view,
+                        &self.title,
+                        &widgets.web_window,
+                        clone!(@strong widgets.toast_overlay as toast_overlay => move |saved| match saved {
+                            Ok(_) => toast_overlay.add_toast(adw::Toast::new("Page saved for offline reading")),
+                            Err(error) => {
+                                eprintln!("Could not save page: {error}");
+                                toast_overlay.add_toast(adw::Toast::new(&format!("Could not save page: {error}")));
+                            }
+                        }),
+                    );
+                }
+                WebWindowInput::Print => {
+                    widgets.web_window.present();
+                    print_page(&widgets.web_view, &widgets.web_window);
+                }
+                WebWindowInput::SaveAsPdf => {
+                    widgets.web_window.present();
+                    save_page_as_pdf(
+                        &widgets.web_view,
+                        &self.title,
+                        &widgets.web_window,
+                        clone!(@strong widgets.toast_overlay as toast_overlay => move |saved| match saved {
+                            Ok(file) => {
+                                let toast = adw::Toast::builder()
+                                    .title("Page saved as PDF")
+                                    .button_label("Open")
+                                    .build();
+                                toast.connect_button_clicked(move |_| {
+                                    if let Err(error) = open::that(file.uri().as_str()) {
+                                        eprintln!("Could not open PDF: {error}");
+                                    }
+                                });
+                                toast_overlay.add_toast(toast);
+                            }
+                            Err(error) => {
+                                eprintln!("Could not save page as PDF: {error}");
+                                toast_overlay.add_toast(adw::Toast::new(&format!("Could not save page as PDF: {error}")));
+                            }
+                        }),
+                    );
+                }
+                WebWindowInput::SelectScreenshotRegion => {
+                    if self.region_selector.is_some() {
+                        return Some(());
+                    }
+                    let region_selector = region_selector(clone!(@strong sender => move |region| {
+                        sender.input(WebWindowInput::ScreenshotRegionSelected(region));
+                    }));
+                    widgets.main_overlay.add_overlay(&region_selector);
+                    region_selector.grab_focus();
+                    self.region_selector = Some(region_selector);
+                    self.set_show_headerbar(false);
+                    widgets.toast_overlay.add_toast(adw::Toast::new("Drag over the part of the page to capture, or press Escape to cancel"));
+                }
+                WebWindowInput::ScreenshotRegionSelected(region) => {
+                    if let Some(region_selector) = self.region_selector.take() {
+                        widgets.main_overlay.remove_overlay(&region_selector);
+                    }
+                    let region = region?;
+                    // The selection covers the whole window, so find where it is on the page
+                    let origin = widgets.main_overlay.compute_point(
+                        &widgets.web_view,
+                        &graphene::Point::new(region.x(), region.y()),
+                    )?;
+                    // Only keep the part over the page, as parts above or left of it would shift the crop
+                    let web_view_bounds = graphene::Rect::new(
+                        0.0,
+                        0.0,
+                        widgets.web_view.width() as f32,
+                        widgets.web_view.height() as f32,
+                    );
+                    let Some(region) = graphene::Rect::new(origin.x(), origin.y(), region.width(), region.height())
+                        .intersection(&web_view_bounds)
+                    else {
+                        widgets.toast_overlay.add_toast(adw::Toast::new("The selected area is not on the page"));
+                        return Some(());
+                    };
+                    sender.input(WebWindowInput::Screenshot(
+                        false,
+                        webkit6::SnapshotRegion::Visible,
+                        Some(region),
+                    ));
+                }
+                WebWindowInput::ScreenshotElement => find_context_menu_element(
+                    &widgets.web_view,
+                    clone!(@strong sender, @strong widgets.toast_overlay as toast_overlay => move |element_bounds| match element_bounds {
+                        // The element may be partly or entirely scrolled out of view, so capture the whole page and cut it out
+                        Some(element_bounds) => sender.input(WebWindowInput::Screenshot(
+                            false,
+                            webkit6::SnapshotRegion::FullDocument,
+                            Some(element_bounds),
+                        )),
+                        None => toast_overlay.add_toast(adw::Toast::new("Could not find the element to capture")),
+                    }),
+                ),
+                WebWindowInput::Screenshot(need_return_main_app, snapshot_region, crop_region) => {
+                    let web_view_width = widgets.web_view.width().max(1);
+                    let title = self.title.clone();
+                    let url = self.url.clone();
                     widgets.web_view.snapshot(
                         snapshot_region,
                         webkit6::SnapshotOptions::INCLUDE_SELECTION_HIGHLIGHTING,
                         gtk::gio::Cancellable::NONE,
                         clone!(@strong widgets.web_window as web_window, @strong widgets.toast_overlay as toast_overlay => move |snapshot_result| match snapshot_result {
                             Ok(texture) => {
+                                let texture = match &crop_region {
+                                    Some(crop_region) => crop_texture(&texture, crop_region, texture.width() as f64 / web_view_width as f64).unwrap_or(texture),
+                                    None => texture,
+                                };
                                 // Present the WebWindow to show off the beautiful animation that took an afternoon to figure out
                                 web_window.present();
                                 // Using async but not threads because WebWindowInput cannot be sent across threads due to one of the variants carrying a WebView
@@ -703,24 +1624,42 @@ impl Component for WebWindow {
                                             .expect("Could not send output WebWindowOutput::ReturnToMainAppWindow");
                                     }
                                 }));
-                                with(&[Document::at(User(Pictures(&["Screenshots"])), "Screenshot.png", Create::AutoRenameIfExists)],
-                                    |mut d| {
-                                        d["Screenshot.png"].replace_with(&texture.save_to_png_bytes())?;
+                                sender.input(WebWindowInput::KeepScreenshot(texture.clone(), title.clone(), url.clone()));
+                                match deliver_screenshot(&texture, &title, &url, &web_window) {
+                                    // Saved screenshots can still be edited from the screenshot menu
+                                    Ok(Some(path)) => {
                                         let toast = adw::Toast::builder()
-                                            .title("Screenshot saved to Pictures → Screenshots")
+                                            .title(describe_delivered_screenshot(Some(&path)).unwrap_or_default())
                                             .button_label("Open")
                                             .build();
-                                        let png_document = d["Screenshot.png"].clone();
-                                        let toast_overlay_clone = toast_overlay.clone();
-                                        toast.connect_button_clicked(move |_| {
-                                            match png_document.launch_with_default_app() {
-                                                Ok(_) => {}
-                                                Err(error) => toast_overlay_clone.add_toast(adw::Toast::new(format!("{}", error).as_str()))
-                                            }
-                                        });
+                                        toast.connect_button_clicked(clone!(@strong toast_overlay => move |_| {
+                                            gtk::FileLauncher::new(Some(&gtk::gio::File::for_path(&path))).launch(
+                                                gtk::Window::NONE,
+                                                gtk::gio::Cancellable::NONE,
+                                                clone!(@strong toast_overlay => move |result| {
+                                                    if let Err(error) = result {
+                                                        toast_overlay.add_toast(adw::Toast::new(&format!("Could not open screenshot: {error}")));
+                                                    }
+                                                }),
+                                            );
+                                        }));
+                                        toast_overlay.add_toast(toast);
+                                    }
+                                    Ok(None) => {
+                                        let toast = adw::Toast::builder()
+                                            .title(describe_delivered_screenshot(None).unwrap_or(String::from("Screenshot taken")))
+                                            .button_label("Edit")
+                                            .build();
+                                        toast.connect_button_clicked(clone!(@strong sender => move |_| {
+                                            sender.input(WebWindowInput::EditScreenshot);
+                                        }));
                                         toast_overlay.add_toast(toast);
-                                        Ok(())
-                                    });
+                                    }
+                                    Err(error) => {
+                                        eprintln!("Could not save screenshot: {error}");
+                                        toast_overlay.add_toast(adw::Toast::new(format!("Could not save screenshot: {error}").as_str()))
+                                    }
+                                }
                             }
                             Err(error) => {
                                 eprintln!("Could not save screenshot: {}", error);
@@ -730,6 +1669,18 @@ impl Component for WebWindow {
                         }),
                     )
                 }
+                WebWindowInput::KeepScreenshot(texture, title, url) => {
+                    self.last_screenshot = Some((texture, title, url));
+                    if let Some(edit_screenshot_action) = &self.edit_screenshot_action {
+                        edit_screenshot_action.set_enabled(true);
+                    }
+                }
+                WebWindowInput::EditScreenshot => {
+                    let (texture, title, url) = self.last_screenshot.clone()?;
+                    if let Err(error) = present_screenshot_editor(texture, title, url, &widgets.toast_overlay) {
+                        widgets.toast_overlay.add_toast(adw::Toast::new(&error));
+                    }
+                }
                 WebWindowInput::BeginScreenshotFlash => {
                     widgets.main_overlay.add_overlay(&self.screenshot_flash_box)
                 }
@@ -773,7 +1724,142 @@ impl Component for WebWindow {
                 WebWindowInput::Peek(url) => {
                     println!("{url}");
                 }
-                WebWindowInput::ShowToast(message) => self.toast_overlay.clone()?.add_toast(Toast::new(&message))
+                WebWindowInput::ShowToast(message) => self.toast_overlay.clone()?.add_toast(Toast::new(&message)),
+                WebWindowInput::ShowCookieManager => {
+                    present_cookie_manager(self.web_view.clone()?.network_session()?, root)
+                }
+                WebWindowInput::ShowClearData => {
+                    present_clear_data(self.web_view.clone()?.network_session()?, root)
+                }
+                WebWindowInput::SetCookieException(cookie_exception) => {
+                    if cookie_exception != self.cookie_exception {
+                        let host = self.host.clone()?;
+                        set_site_exception("cookie-exceptions", &host, cookie_exception);
+                        self.set_cookie_exception(cookie_exception);
+                        let web_view = self.web_view.clone()?;
+                        apply_cookie_policy(&web_view.network_session()?);
+                        // The page has to be loaded again for the change to take effect
+                        apply_cookie_exceptions(
+                            &web_view.user_content_manager()?,
+                            clone!(@strong web_view => move || web_view.reload()),
+                        );
+                    }
+                }
+                WebWindowInput::PermissionRequested(permission_request, permission_kinds, origin) => {
+                    widgets.web_window.present();
+                    present_permission_dialog(permission_request, permission_kinds, origin, root);
+                }
+                WebWindowInput::LoadStarted(url) => {
+                    if self
+                        .interstitial
+                        .as_ref()
+                        .is_some_and(|interstitial| interstitial.failing_url() != url)
+                    {
+                        self.set_interstitial(None);
+                    }
+                }
+                WebWindowInput::UpgradedToHttps(http_url) => {
+                    self.https_upgrade = Some(http_url);
+                }
+                WebWindowInput::LoadCommitted => {
+                    self.https_upgrade = None;
+                    if !self.insecure_resources.is_empty() {
+                        self.set_insecure_resources(vec![]);
+                    }
+                }
+                WebWindowInput::LoadFailed(failing_url, error) => {
+                    // A load that is replaced by another one is cancelled, which is not a failure
+                    if error.matches(webkit6::NetworkError::Cancelled) {
+                        return Some(());
+                    }
+                    self.http_allowed_once.borrow_mut().take();
+                    if let Some(http_url) = self.https_upgrade.take() {
+                        if host_of(&http_url) == host_of(&failing_url) {
+                            self.set_interstitial(Some(Interstitial::HttpsUnavailable {
+                                http_url,
+                                failing_url,
+                            }));
+                        }
+                    }
+                }
+                WebWindowInput::SetAllowHttp(allow_http) => {
+                    if allow_http != self.allow_http {
+                        let host = self.host.clone()?;
+                        set_site_exception(
+                            "https-only-exceptions",
+                            &host,
+                            if allow_http {
+                                SiteException::Allow
+                            } else {
+                                SiteException::Default
+                            },
+                        );
+                        self.set_allow_http(allow_http);
+                    }
+                }
+                WebWindowInput::ProceedPastInterstitial => {
+                    match self.interstitial.clone()? {
+                        Interstitial::HttpsUnavailable { http_url, .. } => {
+                            *self.http_allowed_once.borrow_mut() = host_of(&http_url);
+                            self.web_view.clone()?.load_uri(&http_url);
+                        }
+                        Interstitial::TlsError {
+                            failing_url,
+                            host,
+                            certificate,
+                            ..
+                        } => {
+                            let web_view = self.web_view.clone()?;
+                            add_tls_exception(&web_view.network_session()?, &host, &certificate);
+                            web_view.load_uri(&failing_url);
+                        }
+                    }
+                    self.set_interstitial(None);
+                }
+                WebWindowInput::TlsErrors(failing_url, certificate, errors) => {
+                    let host = host_of(&failing_url)?;
+                    // A site that was only upgraded to HTTPS by HTTPS-Only Mode can still be offered over HTTP
+                    let interstitial = match self.https_upgrade.take() {
+                        Some(http_url) if host_of(&http_url).as_ref() == Some(&host) => {
+                            Interstitial::HttpsUnavailable {
+                                http_url,
+                                failing_url,
+                            }
+                        }
+                        _ => Interstitial::TlsError {
+                            failing_url,
+                            host,
+                            certificate,
+                            errors,
+                        },
+                    };
+                    self.set_interstitial(Some(interstitial));
+                }
+                WebWindowInput::ShowCertificate => {
+                    // The interstitial's certificate belongs to the page that failed, which may not be the one shown
+                    let (host, certificate, errors) = match self.interstitial.clone() {
+                        Some(Interstitial::TlsError {
+                            host,
+                            certificate,
+                            errors,
+                            ..
+                        }) => (host, certificate, errors),
+                        _ => {
+                            let (certificate, errors) = self.web_view.clone()?.tls_info()?;
+                            (self.host.clone()?, certificate, errors)
+                        }
+                    };
+                    present_certificate_dialog(host, certificate, errors, root);
+                }
+                WebWindowInput::LeaveInterstitial => {
+                    self.set_interstitial(None);
+                    let web_view = self.web_view.clone()?;
+                    if web_view.can_go_back() {
+                        web_view.go_back();
+                    } else {
+                        web_view.load_uri("about:blank");
+                    }
+                }
             };
         self.update_view(widgets, sender_clone);
         Some(())
@@ -34,6 +34,8 @@ pub enum WebWindowControlBarInput {
     Refresh,
     Focus,
     Screenshot,
+    Print,
+    SaveAsPdf,
     ReturnToMainAppWindow,
     RetroactivelyLoadUserContentFilter(webkit6::UserContentFilterStore),
     UrlChanged(String),
@@ -59,6 +61,12 @@ relm4::new_stateless_action!(
     WebWindowControlBarActionGroup,
     "screenshot"
 );
+relm4::new_stateless_action!(PrintAction, WebWindowControlBarActionGroup, "print");
+relm4::new_stateless_action!(
+    SaveAsPdfAction,
+    WebWindowControlBarActionGroup,
+    "save-as-pdf"
+);
 relm4::new_stateless_action!(FocusAction, WebWindowControlBarActionGroup, "focus");
 relm4::new_stateless_action!(CopyLinkAction, WebWindowControlBarActionGroup, "copy-link");
 #[relm4::factory(pub)]
@@ -153,6 +161,8 @@ impl FactoryComponent for WebWindowControlBar {
             "Forward" => ForwardAction,
             "Refresh" => RefreshAction,
             "Screenshot" => ScreenshotAction,
+            "Print…" => PrintAction,
+            "Save as PDF…" => SaveAsPdfAction,
             "Focus" => FocusAction,
             "Copy Link" => CopyLinkAction,
         }
@@ -175,8 +185,10 @@ impl FactoryComponent for WebWindowControlBar {
                 WebWindowControlBarInput::Screenshot => self
                     .webwindow
                     .sender()
-                    .send(WebWindowInput::Screenshot(true, webkit6::SnapshotRegion::Visible))
+                    .send(WebWindowInput::Screenshot(true, webkit6::SnapshotRegion::Visible, None))
                     .expect("Could not send WebWindowInput::Screenshot to WebWindow"),
+                WebWindowControlBarInput::Print => _ = self.webwindow.sender().send(WebWindowInput::Print),
+                WebWindowControlBarInput::SaveAsPdf => _ = self.webwindow.sender().send(WebWindowInput::SaveAsPdf),
                 WebWindowControlBarInput::Focus => self.webwindow.widgets().web_window.present(),
                 WebWindowControlBarInput::ReturnToMainAppWindow => _ = sender.output(WebWindowControlBarOutput::ReturnToMainAppWindow),
                 WebWindowControlBarInput::LoadChanged(can_go_back, can_go_forward) => {
@@ -275,6 +287,16 @@ impl FactoryComponent for WebWindowControlBar {
                 sender.input(WebWindowControlBarInput::Screenshot);
             }))
         };
+        let print_action: RelmAction<PrintAction> = {
+            RelmAction::new_stateless(clone!(@strong sender => move |_| {
+                sender.input(WebWindowControlBarInput::Print);
+            }))
+        };
+        let save_as_pdf_action: RelmAction<SaveAsPdfAction> = {
+            RelmAction::new_stateless(clone!(@strong sender => move |_| {
+                sender.input(WebWindowControlBarInput::SaveAsPdf);
+            }))
+        };
         let focus_action: RelmAction<FocusAction> = {
             RelmAction::new_stateless(clone!(@strong sender => move |_| {
                 sender.input(WebWindowControlBarInput::Focus);
@@ -294,6 +316,8 @@ impl FactoryComponent for WebWindowControlBar {
         webwindow_control_bar_action_group.add_action(forward_action);
         webwindow_control_bar_action_group.add_action(refresh_action);
         webwindow_control_bar_action_group.add_action(screenshot_action);
+        webwindow_control_bar_action_group.add_action(print_action);
+        webwindow_control_bar_action_group.add_action(save_as_pdf_action);
         webwindow_control_bar_action_group.add_action(focus_action);
         webwindow_control_bar_action_group.add_action(copy_link_action);
         webwindow_control_bar_action_group.register_for_widget(root.clone());